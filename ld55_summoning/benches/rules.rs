@@ -0,0 +1,123 @@
+// Benchmarks for the rules the bots lean on. Everything is built from fixed
+// seeds so runs are comparable. Check perft counts still match before trusting
+// a speedup here.
+
+use std::hint::black_box;
+
+use criterion::{ criterion_group, criterion_main, BenchmarkId, Criterion };
+use rand::{ rngs::StdRng, seq::SliceRandom, SeedableRng };
+
+use ld55_summoning::bitboard::Bitboard;
+use ld55_summoning::gamestate::*;
+use ld55_summoning::mapgen::generate_map;
+
+const SEEDS : [u64; 3] = [ 1, 55, 2024 ];
+
+fn opening( seed : u64 ) -> GameSnapshot
+{
+    let mut rng = StdRng::seed_from_u64( seed );
+    generate_map( &mut rng, [ true; 4 ], 16 )
+}
+
+// A couple of random moves each, so there's more than four stacks to look at
+fn midgame( seed : u64 ) -> GameSnapshot
+{
+    let mut rng = StdRng::seed_from_u64( seed );
+    let mut snap = generate_map( &mut rng, [ true; 4 ], 16 );
+    for turn in 0..12 {
+        if let Some(next) = gen_valid_moves( snap, turn % 4 ).choose( &mut rng ) {
+            snap = *next;
+        }
+    }
+    snap
+}
+
+fn bench_movegen( c : &mut Criterion )
+{
+    let mut group = c.benchmark_group( "gen_valid_moves" );
+    for seed in SEEDS {
+        let snap = opening( seed );
+        group.bench_with_input( BenchmarkId::new( "opening", seed ), &snap, |b, snap| {
+            b.iter( || gen_valid_moves( black_box( *snap ), 0 ) )
+        });
+
+        let snap = midgame( seed );
+        group.bench_with_input( BenchmarkId::new( "midgame", seed ), &snap, |b, snap| {
+            b.iter( || gen_valid_moves( black_box( *snap ), 0 ) )
+        });
+    }
+    group.finish();
+}
+
+fn bench_evaluate( c : &mut Criterion )
+{
+    let mut group = c.benchmark_group( "evaluate_position" );
+    for seed in SEEDS {
+        let snap = midgame( seed );
+        group.bench_with_input( BenchmarkId::new( "midgame", seed ), &snap, |b, snap| {
+            b.iter( || evaluate_position( black_box( *snap ) ) )
+        });
+    }
+    group.finish();
+
+    // What a bot does every turn, move generation plus an eval per move
+    let mut group = c.benchmark_group( "best_move" );
+    for seed in SEEDS {
+        let snap = midgame( seed );
+        group.bench_with_input( BenchmarkId::new( "midgame", seed ), &snap, |b, snap| {
+            b.iter( || best_move( black_box( *snap ), 0, 4 ) )
+        });
+    }
+    group.finish();
+}
+
+fn bench_perft( c : &mut Criterion )
+{
+    let mut group = c.benchmark_group( "perft" );
+    group.sample_size( 10 );
+    for seed in SEEDS {
+        let snap = opening( seed );
+        group.bench_with_input( BenchmarkId::new( "depth 3", seed ), &snap, |b, snap| {
+            b.iter( || perft( black_box( *snap ), &[ 0, 1, 2, 3 ], 0, 3 ) )
+        });
+    }
+    group.finish();
+}
+
+// The same work on the bitboard, to compare against the groups above
+fn bench_bitboard( c : &mut Criterion )
+{
+    let mut group = c.benchmark_group( "bitboard" );
+    for seed in SEEDS {
+        let board = Bitboard::from_snapshot( &midgame( seed ) );
+        group.bench_with_input( BenchmarkId::new( "gen_moves midgame", seed ), &board, |b, board| {
+            b.iter( || black_box( board ).gen_moves( 0 ) )
+        });
+        group.bench_with_input( BenchmarkId::new( "evaluate midgame", seed ), &board, |b, board| {
+            b.iter( || black_box( board ).evaluate() )
+        });
+
+        let board = Bitboard::from_snapshot( &opening( seed ) );
+        group.bench_with_input( BenchmarkId::new( "perft depth 3", seed ), &board, |b, board| {
+            b.iter( || black_box( board ).perft( &[ 0, 1, 2, 3 ], 0, 3 ) )
+        });
+    }
+    group.finish();
+}
+
+fn bench_mapgen( c : &mut Criterion )
+{
+    let mut group = c.benchmark_group( "generate_map" );
+    for players in [ 2, 4 ] {
+        let mut active = [ false; 4 ];
+        active[ ..players ].fill( true );
+        group.bench_with_input( BenchmarkId::new( "players", players ), &active, |b, active| {
+            // Same seed every time, so every sample builds the same map
+            b.iter( || generate_map( &mut StdRng::seed_from_u64( 1 ), *active, 16 ) )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!( benches, bench_movegen, bench_evaluate, bench_perft, bench_bitboard, bench_mapgen );
+criterion_main!( benches );
@@ -0,0 +1,298 @@
+use bevy::{ pbr::NotShadowCaster, prelude::* };
+use std::f32::consts::{ FRAC_PI_2, PI };
+
+use crate::summongame::{ playing, GameAppState, GameplayCleanup, GoodStuff, MapSpaceVisual, MatchSettings, PlayerType, SummonGame, TurnAdvance };
+use crate::gamestate::{ access_map, rank_moves, MapDirection, MapSpaceContents };
+use crate::map::worldpos_from_mapindex;
+use crate::position::Position;
+use crate::{ draw_map_dir, GameCamera };
+
+// Debug/teaching overlay that shows who can reach each space and the
+// best few moves for whoever's turn it is
+#[derive(Resource)]
+pub struct AnalysisOverlay {
+    pub enabled : bool,
+    pub top_n : usize,
+    tint_mesh : Handle<Mesh>,
+    tint_mtl : Vec<Handle<StandardMaterial>>,  // one per access mask
+    candidates : Vec<Candidate>,
+}
+
+impl Default for AnalysisOverlay {
+    fn default() -> AnalysisOverlay {
+        AnalysisOverlay {
+            enabled: false,
+            top_n: 5,
+            tint_mesh: Handle::default(),
+            tint_mtl: Vec::new(),
+            candidates: Vec::new(),
+        }
+    }
+}
+
+struct Candidate {
+    src : i32,
+    dest : i32,
+    dir : MapDirection,
+    amount : u8,
+    score : f32,
+}
+
+#[derive(Component)]
+struct AnalysisTint {
+    ndx : usize,
+}
+
+#[derive(Component)]
+struct AnalysisLabel {
+    rank : usize,
+}
+
+pub struct AnalysisPlugin;
+
+impl Plugin for AnalysisPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource( AnalysisOverlay::default() )
+            .add_systems(Update, (
+                toggle_analysis,
+                update_candidates,
+                update_tints,
+                draw_candidates,
+                )
+                .chain()
+                .run_if(playing))
+            .add_systems(Update, save_position.run_if(playing))
+            .add_systems( OnExit(GameAppState::Gameplay), reset_analysis );
+    }
+}
+
+// The tints went with the map, start the next match with the overlay off
+fn reset_analysis(
+    mut overlay: ResMut<AnalysisOverlay>,
+)
+{
+    overlay.enabled = false;
+    overlay.candidates.clear();
+}
+
+pub const POSITION_FILE : &str = "summoning_position.txt";
+
+// F2 writes out the board for perft or a bug report
+fn save_position(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    stuff: Res<GoodStuff>,
+    settings: Res<MatchSettings>,
+    game: Res<SummonGame>,
+)
+{
+    if !keyboard_input.just_pressed( KeyCode::F2 ) {
+        return;
+    }
+
+    let players : Vec<usize> = (0..4)
+        .filter( |p| stuff.player_stuff[*p].ptype != PlayerType::NotActive && !game.conceded[*p] )
+        .collect();
+    let Some(turn) = players.iter().position( |p| *p == game.player_turn as usize ) else {
+        return;
+    };
+
+    // The clocks go in too when the match has them
+    let clocks = settings.clock.seconds().map( |_| game.clocks );
+    write_position( &Position { snapshot: game.snapshot, players, turn, clocks }.to_text() );
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_position( text : &str ) {
+    match std::fs::write( POSITION_FILE, text ) {
+        Ok(_) => println!("Saved position to {}", POSITION_FILE ),
+        Err(err) => println!("Couldn't save {}: {}", POSITION_FILE, err ),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_position( _text : &str ) {
+}
+
+fn toggle_analysis(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut commands: Commands,
+    mut overlay: ResMut<AnalysisOverlay>,
+    stuff: Res<GoodStuff>,
+    game: Res<SummonGame>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mapvis_q: Query<&MapSpaceVisual>,
+    despawn_q: Query<Entity, Or<(With<AnalysisTint>, With<AnalysisLabel>)>>,
+)
+{
+    if !keyboard_input.just_pressed( KeyCode::Tab ) {
+        return;
+    }
+
+    overlay.enabled = !overlay.enabled;
+    println!("Analysis overlay {}", if overlay.enabled { "on" } else { "off" } );
+
+    if !overlay.enabled {
+        for ent in &despawn_q {
+            commands.entity( ent ).despawn_recursive();
+        }
+        overlay.candidates.clear();
+        return;
+    }
+
+    // Blend the colors of every player that can reach a space
+    overlay.tint_mesh = meshes.add( RegularPolygon::new( 0.8, 6 ) );
+    overlay.tint_mtl.clear();
+    for mask in 0..16 {
+        let mut color = Color::rgba( 0.0, 0.0, 0.0, 0.0 );
+        let mut count = 0.0;
+        for p in 0..4 {
+            if mask & (1 << p) != 0 {
+                color = color + stuff.player_stuff[p].color;
+                count += 1.0;
+            }
+        }
+        if count > 0.0 {
+            color = color * (1.0 / count);
+            color.set_a( 0.45 );
+        }
+
+        let mtl = materials.add( StandardMaterial {
+            base_color: color,
+            unlit: true,
+            alpha_mode: AlphaMode::Blend,
+            ..default()
+        });
+        overlay.tint_mtl.push( mtl );
+    }
+
+    // Lay a flat hex over every playable space
+    for ent_vis in &game.map_visuals {
+        let Ok(vis) = mapvis_q.get( *ent_vis ) else {
+            continue;
+        };
+        if game.snapshot.map.spaces[ vis.ndx ].contents != MapSpaceContents::Playable {
+            continue;
+        }
+
+        let ent_tint = commands.spawn(( PbrBundle {
+            mesh: overlay.tint_mesh.clone(),
+            material: overlay.tint_mtl[0].clone(),
+            transform: Transform::from_xyz( 0.0, 0.12, 0.0 )
+                .with_rotation( Quat::from_rotation_y( PI / 6.0 ) * Quat::from_rotation_x( -FRAC_PI_2 ) ),
+            ..default()
+        }, NotShadowCaster, AnalysisTint { ndx : vis.ndx } )).id();
+
+        commands.entity( *ent_vis ).add_child( ent_tint );
+    }
+
+    // Score labels for the top moves
+    for rank in 0..overlay.top_n {
+        commands.spawn((
+            TextBundle::from_section("",
+                TextStyle {
+                    font_size: 18.,
+                    ..default()
+                },
+            )
+            .with_style( Style {
+                position_type: PositionType::Absolute,
+                ..default()
+            }),
+            AnalysisLabel { rank },
+            GameplayCleanup,
+        ));
+    }
+}
+
+fn update_candidates(
+    mut overlay: ResMut<AnalysisOverlay>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    game: Res<SummonGame>,
+    mut ev_turn: EventReader<TurnAdvance>,
+)
+{
+    // Only rescore when the board changes or the overlay was just turned on
+    let turn_changed = ev_turn.read().count() > 0;
+    if !overlay.enabled || !(turn_changed || keyboard_input.just_pressed( KeyCode::Tab )) {
+        return;
+    }
+
+    let pnum = game.player_turn as usize;
+    overlay.candidates = rank_moves( game.snapshot, pnum, game.player_count ).into_iter()
+        .filter_map( |mv| {
+            let dir = game.snapshot.map.dir_to( mv.src, mv.dest )?;
+            Some( Candidate { src: mv.src, dest: mv.dest, dir, amount: mv.amount, score: mv.delta as f32 / 10000.0 } )
+        })
+        .take( overlay.top_n )
+        .collect();
+}
+
+fn update_tints(
+    overlay: Res<AnalysisOverlay>,
+    game: Res<SummonGame>,
+    mut tint_q: Query<(&AnalysisTint, &mut Handle<StandardMaterial>)>,
+)
+{
+    if !overlay.enabled {
+        return;
+    }
+
+    let access = access_map( &game.snapshot );
+    for (tint, mut mtl) in &mut tint_q {
+        let want = &overlay.tint_mtl[ (access[ tint.ndx ] & 0xf) as usize ];
+        if *mtl != *want {
+            *mtl = want.clone();
+        }
+    }
+}
+
+fn draw_candidates(
+    overlay: Res<AnalysisOverlay>,
+    stuff: Res<GoodStuff>,
+    game: Res<SummonGame>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+    mut label_q: Query<(&AnalysisLabel, &mut Style, &mut Text, &mut Visibility)>,
+    mut gizmos: Gizmos,
+)
+{
+    if !overlay.enabled {
+        return;
+    }
+
+    let offs = Vec3 { x : 0.0, y : 0.3, z : 0.0 };
+    let player_col = stuff.player_stuff[ game.player_turn as usize].color;
+    let (camera, camera_global_transform) = camera_q.single();
+
+    for (lbl, mut style, mut text, mut vis) in &mut label_q {
+        let Some(cand) = overlay.candidates.get( lbl.rank ) else {
+            *vis = Visibility::Hidden;
+            continue;
+        };
+
+        // Best move is brightest, the rest fade out
+        let fade = 1.0 - (lbl.rank as f32 / overlay.top_n as f32) * 0.7;
+        let mut color = player_col * fade;
+        color.set_a( 1.0 );
+
+        draw_map_dir( &mut gizmos, &game, cand.src, cand.dir, color, false );
+        let src_pos = worldpos_from_mapindex( cand.src ) + offs;
+        let dst_pos = worldpos_from_mapindex( cand.dest ) + offs;
+        gizmos.arrow( src_pos, dst_pos, color );
+
+        text.sections[0].value = format!("#{} {}: {:+.2}", lbl.rank + 1, cand.amount, cand.score );
+        text.sections[0].style.color = color;
+
+        match camera.world_to_viewport( camera_global_transform, src_pos.lerp( dst_pos, 0.5 ) ) {
+            Some(viewport_position) => {
+                style.top = Val::Px( viewport_position.y );
+                style.left = Val::Px( viewport_position.x );
+                *vis = Visibility::Visible;
+            }
+            None => {
+                *vis = Visibility::Hidden;
+            }
+        }
+    }
+}
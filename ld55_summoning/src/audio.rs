@@ -0,0 +1,208 @@
+use bevy::{ audio::Volume, prelude::* };
+use std::time::Duration;
+
+use crate::summongame::{ CircleLanded, GameOver, GameStateChanged, GoodStuff, PlayerType, SummonGame, TurnAdvance };
+use crate::gamestate::gen_valid_moves;
+
+// Music and sound effects. Browsers won't play audio until the page has
+// been interacted with, so the music waits for the first key, click or tap.
+#[derive(Resource)]
+pub struct AudioSettings {
+    pub music_volume : f32,
+    pub sfx_volume : f32,
+    pub muted : bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> AudioSettings {
+        AudioSettings {
+            music_volume: 0.7,
+            sfx_volume: 0.5,
+            muted: false,
+        }
+    }
+}
+
+impl AudioSettings {
+    fn music_level( &self ) -> f32 {
+        if self.muted { 0.0 } else { self.music_volume }
+    }
+}
+
+#[derive(Component)]
+struct MusicTrack;
+
+#[derive(Clone, Copy, Debug)]
+enum Sfx {
+    Split,
+    Land,
+    Pass,
+    TurnStart,
+    GameOver,
+}
+
+impl Sfx {
+    // No sound files for these yet, so they're simple tones
+    fn tone( &self ) -> (f32, f32) {
+        match self {
+            Sfx::Split => (660.0, 0.08),
+            Sfx::Land => (440.0, 0.06),
+            Sfx::Pass => (196.0, 0.25),
+            Sfx::TurnStart => (880.0, 0.05),
+            Sfx::GameOver => (262.0, 0.8),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct SfxHandles {
+    tones : Vec<(f32, f32, Handle<Pitch>)>,
+}
+
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource( AudioSettings::default() )
+            .insert_resource( SfxHandles::default() )
+            .add_systems(Update, (
+                start_music,
+                audio_settings_keys,
+                play_game_sfx,
+                ));
+    }
+}
+
+fn start_music(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<AudioSettings>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
+    music_q: Query<(), With<MusicTrack>>,
+)
+{
+    if !music_q.is_empty() {
+        return;
+    }
+
+    let interacted = keyboard_input.get_just_pressed().len() > 0 ||
+                     mouse_button_input.get_just_pressed().len() > 0 ||
+                     touches.any_just_pressed();
+    if !interacted {
+        return;
+    }
+
+    // MUUUUSSSIICC
+    println!("Starting music");
+    commands.spawn(( AudioBundle {
+        source: asset_server.load("SummoningStuff_OGG.ogg"),
+        settings: PlaybackSettings::LOOP.with_volume( Volume::new( settings.music_level() ) ),
+    }, MusicTrack ));
+}
+
+fn audio_settings_keys(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<AudioSettings>,
+    music_q: Query<&AudioSink, With<MusicTrack>>,
+)
+{
+    let mut changed = false;
+    if keyboard_input.just_pressed( KeyCode::KeyM ) {
+        settings.muted = !settings.muted;
+        changed = true;
+    }
+
+    let step = if keyboard_input.just_pressed( KeyCode::Equal ) || keyboard_input.just_pressed( KeyCode::NumpadAdd ) {
+        0.1
+    } else if keyboard_input.just_pressed( KeyCode::Minus ) || keyboard_input.just_pressed( KeyCode::NumpadSubtract ) {
+        -0.1
+    } else {
+        0.0
+    };
+
+    if step != 0.0 {
+        settings.music_volume = (settings.music_volume + step).clamp( 0.0, 1.0 );
+        settings.sfx_volume = (settings.sfx_volume + step).clamp( 0.0, 1.0 );
+        changed = true;
+    }
+
+    if changed {
+        println!("Audio: music {:.1} sfx {:.1} muted {}", settings.music_volume, settings.sfx_volume, settings.muted );
+        for sink in &music_q {
+            sink.set_volume( settings.music_level() );
+        }
+    }
+}
+
+fn play_sfx(
+    commands: &mut Commands,
+    pitches: &mut Assets<Pitch>,
+    handles: &mut SfxHandles,
+    settings: &AudioSettings,
+    sfx: Sfx,
+)
+{
+    if settings.muted || settings.sfx_volume <= 0.0 {
+        return;
+    }
+
+    let (freq, secs) = sfx.tone();
+    let handle = match handles.tones.iter().find( |(f, s, _)| *f == freq && *s == secs ) {
+        Some((_, _, handle)) => handle.clone(),
+        None => {
+            let handle = pitches.add( Pitch::new( freq, Duration::from_secs_f32( secs ) ) );
+            handles.tones.push( (freq, secs, handle.clone()) );
+            handle
+        }
+    };
+
+    commands.spawn( PitchBundle {
+        source: handle,
+        settings: PlaybackSettings::DESPAWN.with_volume( Volume::new( settings.sfx_volume ) ),
+    });
+}
+
+fn play_game_sfx(
+    mut commands: Commands,
+    mut pitches: ResMut<Assets<Pitch>>,
+    mut handles: ResMut<SfxHandles>,
+    settings: Res<AudioSettings>,
+    stuff: Res<GoodStuff>,
+    game: Res<SummonGame>,
+    mut ev_gamestate: EventReader<GameStateChanged>,
+    mut ev_landed: EventReader<CircleLanded>,
+    mut ev_turn: EventReader<TurnAdvance>,
+    mut ev_gameover: EventReader<GameOver>,
+)
+{
+    let mut sounds = Vec::new();
+
+    for ev in ev_gamestate.read() {
+        if let GameStateChanged::CircleSplit( _, _ ) = ev {
+            sounds.push( Sfx::Split );
+        }
+    }
+
+    if ev_landed.read().count() > 0 {
+        sounds.push( Sfx::Land );
+    }
+
+    for ev in ev_turn.read() {
+        if gen_valid_moves( game.snapshot, ev.0 as usize ).is_empty() {
+            sounds.push( Sfx::Pass );
+        } else if stuff.player_stuff[ ev.0 as usize ].ptype == PlayerType::Local {
+            sounds.push( Sfx::TurnStart );
+        }
+    }
+
+    if ev_gameover.read().count() > 0 {
+        sounds.push( Sfx::GameOver );
+    }
+
+    for sfx in sounds {
+        play_sfx( &mut commands, &mut pitches, &mut handles, &settings, sfx );
+    }
+}
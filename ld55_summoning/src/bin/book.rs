@@ -0,0 +1,125 @@
+// Builds the opening book from self-play. Plays a batch of games on each map
+// and records how the first few moves of each game turned out. Adds to the
+// book that's already there, so it can be built up over several runs.
+//
+//   cargo run --release --bin book -- [--maps 32] [--games 8] [--plies 6] [--players 2]
+//       [--bot Overclock] [--noise 1000] [--seed 1] [--position file] [--out summoning_book.ron]
+//
+// Maps come from seeds unless --position gives a board saved with F2.
+
+use rand::{ rngs::StdRng, Rng, SeedableRng };
+
+use ld55_summoning::book::{ OpeningBook, BOOK_FILE };
+use ld55_summoning::gamestate::GameSnapshot;
+use ld55_summoning::mapgen::generate_map;
+use ld55_summoning::position::Position;
+use ld55_summoning::ronfile::RonFile;
+use ld55_summoning::weights::{ WeightsFile, BOT_NAMES, WEIGHTS_FILE };
+
+struct Options {
+    maps : u32,
+    games : u32,
+    plies : u32,
+    players : usize,
+    bot : String,
+    noise : i32,
+    seed : u64,
+    position : Option<String>,
+    out : String,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: book [--maps <n>] [--games <n>] [--plies <n>] [--players 2-4] [--bot <name>] [--noise <n>] [--seed <n>] [--position <file>] [--out <file>]");
+    std::process::exit( 1 );
+}
+
+fn parse_options() -> Options {
+    let mut opts = Options {
+        maps: 32,
+        games: 8,
+        plies: 6,
+        players: 2,
+        bot: BOT_NAMES[ BOT_NAMES.len() - 1 ].into(),
+        noise: 1000,
+        seed: 1,
+        position: None,
+        out: BOOK_FILE.into(),
+    };
+
+    let args : Vec<String> = std::env::args().skip( 1 ).collect();
+    for pair in args.chunks( 2 ) {
+        let [flag, value] = pair else { usage() };
+        let ok = match flag.as_str() {
+            "--maps" => value.parse().map( |v| opts.maps = v ).is_ok(),
+            "--games" => value.parse().map( |v| opts.games = v ).is_ok(),
+            "--plies" => value.parse().map( |v| opts.plies = v ).is_ok(),
+            "--players" => value.parse().map( |v| opts.players = v ).is_ok() && (2..=4).contains( &opts.players ),
+            "--bot" => { opts.bot = value.clone(); BOT_NAMES.contains( &value.as_str() ) }
+            "--noise" => value.parse().map( |v| opts.noise = v ).is_ok(),
+            "--seed" => value.parse().map( |v| opts.seed = v ).is_ok(),
+            "--position" => { opts.position = Some( value.clone() ); true }
+            "--out" => { opts.out = value.clone(); true }
+            _ => false,
+        };
+        if !ok {
+            usage();
+        }
+    }
+
+    opts
+}
+
+fn load_maps( opts : &Options, rng : &mut StdRng ) -> (Vec<GameSnapshot>, Vec<usize>) {
+    if let Some(path) = &opts.position {
+        let position = std::fs::read_to_string( path )
+            .map_err( |err| err.to_string() )
+            .and_then( |text| Position::from_text( &text ) )
+            .unwrap_or_else( |err| {
+                eprintln!("Couldn't load {}: {}", path, err );
+                std::process::exit( 1 );
+            });
+        return ( vec![ position.snapshot ], position.players );
+    }
+
+    let mut active = [ false; 4 ];
+    active[ ..opts.players ].fill( true );
+    let maps = (0..opts.maps)
+        .map( |_| generate_map( &mut StdRng::seed_from_u64( rng.gen() ), active, 16 ) )
+        .collect();
+    ( maps, (0..opts.players).collect() )
+}
+
+fn main() {
+    let opts = parse_options();
+    let mut rng = StdRng::seed_from_u64( opts.seed );
+
+    let mut book = match OpeningBook::load( &opts.out ) {
+        Ok(book) => book,
+        Err(_) if !std::path::Path::new( &opts.out ).exists() => OpeningBook::new( opts.plies ),
+        Err(err) => {
+            eprintln!("Couldn't read {}: {}", opts.out, err );
+            std::process::exit( 1 );
+        }
+    };
+    book.plies = opts.plies;
+    let before = book.len();
+
+    let weights = WeightsFile::load( WEIGHTS_FILE ).unwrap_or_default().for_bot( &opts.bot );
+    let (maps, players) = load_maps( &opts, &mut rng );
+    println!("Playing {} games on each of {} maps with {}'s weights", opts.games, maps.len(), opts.bot );
+
+    for (i, map) in maps.iter().enumerate() {
+        book.build( &mut rng, std::slice::from_ref( map ), &players, opts.games, &[ weights; 4 ], opts.noise );
+        if (i + 1) % 10 == 0 || i + 1 == maps.len() {
+            println!("map {}: {} positions", i + 1, book.len() );
+        }
+    }
+
+    match book.save( &opts.out ) {
+        Ok(_) => println!("Saved {} positions ({} new) to {}", book.len(), book.len() - before, opts.out ),
+        Err(err) => {
+            eprintln!("Couldn't save {}: {}", opts.out, err );
+            std::process::exit( 1 );
+        }
+    }
+}
@@ -0,0 +1,82 @@
+// Counts move generation leaf nodes from a position, one line per depth, so
+// the counts can be compared before and after changing gen_valid_moves. Runs
+// both the snapshot rules and the bitboard, and complains if they disagree.
+//
+//   cargo run --release --bin perft -- <depth> [position.txt]
+//   cargo run --release --bin perft -- <depth> --seed <n>
+//
+// Positions are in the text format from position.rs. F2 during a match saves
+// one. Without a position it uses the 4 player map from seed 1.
+
+use std::time::Instant;
+
+use rand::{ rngs::StdRng, SeedableRng };
+
+use ld55_summoning::bitboard::Bitboard;
+use ld55_summoning::gamestate::perft;
+use ld55_summoning::mapgen::generate_map;
+use ld55_summoning::position::Position;
+
+fn usage() -> ! {
+    eprintln!("usage: perft <depth> [position.txt | --seed <n>]");
+    std::process::exit( 1 );
+}
+
+fn main() {
+    let args : Vec<String> = std::env::args().skip( 1 ).collect();
+    let Some(depth) = args.first().and_then( |arg| arg.parse::<u32>().ok() ) else {
+        usage();
+    };
+
+    let position = match args.get( 1 ).map( |arg| arg.as_str() ) {
+        Some("--seed") => {
+            let Some(seed) = args.get( 2 ).and_then( |arg| arg.parse::<u64>().ok() ) else {
+                usage();
+            };
+            seeded_position( seed )
+        }
+        Some(path) => {
+            let text = std::fs::read_to_string( path ).unwrap_or_else( |err| {
+                eprintln!("Couldn't read {}: {}", path, err );
+                std::process::exit( 1 );
+            });
+            Position::from_text( &text ).unwrap_or_else( |err| {
+                eprintln!("Couldn't load {}: {}", path, err );
+                std::process::exit( 1 );
+            })
+        }
+        None => seeded_position( 1 ),
+    };
+
+    print!( "{}", position.to_text() );
+
+    let board = Bitboard::from_snapshot( &position.snapshot );
+    let mut mismatch = false;
+    for d in 1..=depth {
+        let start = Instant::now();
+        let nodes = perft( position.snapshot, &position.players, position.turn, d );
+        let secs = start.elapsed().as_secs_f64();
+
+        let start = Instant::now();
+        let bit_nodes = board.perft( &position.players, position.turn, d );
+        let bit_secs = start.elapsed().as_secs_f64();
+
+        println!("depth {}  nodes {:>14}  snapshot {:8.3}s  bitboard {:8.3}s{}", d, nodes, secs, bit_secs,
+            if bit_nodes == nodes { "".to_string() } else { format!("  MISMATCH bitboard {}", bit_nodes ) } );
+        mismatch |= bit_nodes != nodes;
+    }
+
+    if mismatch {
+        std::process::exit( 1 );
+    }
+}
+
+fn seeded_position( seed : u64 ) -> Position {
+    let mut rng = StdRng::seed_from_u64( seed );
+    Position {
+        snapshot: generate_map( &mut rng, [ true; 4 ], 16 ),
+        players: vec![ 0, 1, 2, 3 ],
+        turn: 0,
+        clocks: None,
+    }
+}
@@ -0,0 +1,196 @@
+// Tunes the evaluation weights by self-play, using SPSA. Every iteration
+// nudges all the weights at once in a random direction, plays the nudged
+// weights against the opposite nudge on seeded maps, and steps towards
+// whichever side won more. Saves the result under a bot's name in the
+// weights file, which the game loads on startup.
+//
+//   cargo run --release --bin tune -- [--bot Overclock] [--iterations 200] [--games 16]
+//       [--players 2] [--noise 1000] [--seed 1] [--out summoning_weights.ron]
+
+use rand::{ rngs::StdRng, Rng, SeedableRng };
+
+use ld55_summoning::gamestate::{ EvalWeights, GameSnapshot, DEFAULT_WEIGHTS };
+use ld55_summoning::mapgen::generate_map;
+use ld55_summoning::ronfile::RonFile;
+use ld55_summoning::selfplay::play_game;
+use ld55_summoning::weights::{ WeightsFile, BOT_NAMES, WEIGHTS_FILE };
+
+struct Options {
+    bot : String,
+    iterations : u32,
+    games : u32,
+    players : usize,
+    noise : i32,
+    seed : u64,
+    out : String,
+}
+
+// Tuned in units of these, so one step means about the same for each weight
+const NAMES : [&str; 5] = [ "stack", "spare_power", "reach_start", "open_decay", "contested_decay" ];
+const SCALE : [f64; 5] = [ 2000.0, 2000.0, 2000.0, 50.0, 50.0 ];
+const LIMITS : [(f64, f64); 5] = [ (0.0, 100000.0), (1.0, 100000.0), (1.0, 100000.0), (0.0, 1000.0), (0.0, 1000.0) ];
+
+// SPSA gain schedules, the usual exponents from Spall
+const STEP_A : f64 = 2.0;
+const PERTURB_C : f64 = 1.0;
+const ALPHA : f64 = 0.602;
+const GAMMA : f64 = 0.101;
+
+fn usage() -> ! {
+    eprintln!("usage: tune [--bot <name>] [--iterations <n>] [--games <n>] [--players 2-4] [--noise <n>] [--seed <n>] [--out <file>]");
+    std::process::exit( 1 );
+}
+
+fn parse_options() -> Options {
+    let mut opts = Options {
+        bot: BOT_NAMES[ BOT_NAMES.len() - 1 ].into(),
+        iterations: 200,
+        games: 16,
+        players: 2,
+        noise: 1000,
+        seed: 1,
+        out: WEIGHTS_FILE.into(),
+    };
+
+    let args : Vec<String> = std::env::args().skip( 1 ).collect();
+    for pair in args.chunks( 2 ) {
+        let [flag, value] = pair else { usage() };
+        let ok = match flag.as_str() {
+            "--bot" => { opts.bot = value.clone(); true }
+            "--iterations" => value.parse().map( |v| opts.iterations = v ).is_ok(),
+            "--games" => value.parse().map( |v| opts.games = v ).is_ok() && opts.games >= 1,
+            "--players" => value.parse().map( |v| opts.players = v ).is_ok() && (2..=4).contains( &opts.players ),
+            "--noise" => value.parse().map( |v| opts.noise = v ).is_ok(),
+            "--seed" => value.parse().map( |v| opts.seed = v ).is_ok(),
+            "--out" => { opts.out = value.clone(); true }
+            _ => false,
+        };
+        if !ok {
+            usage();
+        }
+    }
+
+    if !BOT_NAMES.contains( &opts.bot.as_str() ) {
+        eprintln!("No bot called {}, the bots are {:?}", opts.bot, BOT_NAMES );
+        std::process::exit( 1 );
+    }
+
+    opts
+}
+
+fn to_params( weights : &EvalWeights ) -> [f64; 5] {
+    let raw = [ weights.stack, weights.spare_power, weights.reach_start, weights.open_decay, weights.contested_decay ];
+    let mut params = [ 0.0; 5 ];
+    for i in 0..5 {
+        params[i] = raw[i] as f64 / SCALE[i];
+    }
+    params
+}
+
+fn to_weights( params : &[f64; 5] ) -> EvalWeights {
+    let raw : Vec<i32> = (0..5)
+        .map( |i| (params[i] * SCALE[i]).clamp( LIMITS[i].0, LIMITS[i].1 ).round() as i32 )
+        .collect();
+    EvalWeights {
+        stack: raw[0],
+        spare_power: raw[1],
+        reach_start: raw[2],
+        open_decay: raw[3],
+        contested_decay: raw[4],
+        ..DEFAULT_WEIGHTS
+    }
+}
+
+// Plays `a` against `b` and gives back a's share of the wins minus b's, from
+// -1 to 1. Each map is played twice with the seats swapped, so neither side
+// gets the better start.
+fn play_match( rng : &mut StdRng, a : &EvalWeights, b : &EvalWeights, opts : &Options ) -> f64 {
+    let players : Vec<usize> = (0..opts.players).collect();
+    let mut active = [ false; 4 ];
+    active[ ..opts.players ].fill( true );
+
+    let mut total = 0.0;
+    let mut map = GameSnapshot::default();
+    for game in 0..opts.games {
+        if game % 2 == 0 {
+            map = generate_map( &mut StdRng::seed_from_u64( rng.gen() ), active, 16 );
+        }
+
+        // a takes the even seats on one game and the odd seats on the next
+        let a_seat = |p : usize| (p + game as usize) & 1 == 0;
+        let weights : [EvalWeights; 4] = std::array::from_fn( |p| if a_seat( p ) { *a } else { *b } );
+
+        let result = play_game( rng, &map, &players, &weights, opts.noise );
+        let winners = result.winners( &players );
+        for p in &winners {
+            let share = 1.0 / winners.len() as f64;
+            total += if a_seat( *p ) { share } else { -share };
+        }
+    }
+
+    total / opts.games as f64
+}
+
+fn print_weights( label : &str, weights : &EvalWeights ) {
+    let raw = [ weights.stack, weights.spare_power, weights.reach_start, weights.open_decay, weights.contested_decay ];
+    let parts : Vec<String> = NAMES.iter().zip( raw.iter() ).map( |(n, v)| format!("{} {}", n, v ) ).collect();
+    println!("{:>10}: {}", label, parts.join(", ") );
+}
+
+fn main() {
+    let opts = parse_options();
+    let mut rng = StdRng::seed_from_u64( opts.seed );
+
+    // Keep whatever the other bots already have
+    let mut file = match WeightsFile::load( &opts.out ) {
+        Ok(file) => file,
+        Err(_) if !std::path::Path::new( &opts.out ).exists() => WeightsFile::default(),
+        Err(err) => {
+            eprintln!("Couldn't read {}: {}", opts.out, err );
+            std::process::exit( 1 );
+        }
+    };
+    let start = file.for_bot( &opts.bot );
+    print_weights( "start", &start );
+
+    let mut params = to_params( &start );
+    let stability = opts.iterations as f64 / 10.0;
+    for k in 0..opts.iterations {
+        let ak = STEP_A / (k as f64 + 1.0 + stability).powf( ALPHA );
+        let ck = PERTURB_C / (k as f64 + 1.0).powf( GAMMA );
+
+        let delta : Vec<f64> = (0..5).map( |_| if rng.gen::<bool>() { 1.0 } else { -1.0 } ).collect();
+        let mut plus = params;
+        let mut minus = params;
+        for i in 0..5 {
+            plus[i] += ck * delta[i];
+            minus[i] -= ck * delta[i];
+        }
+
+        let result = play_match( &mut rng, &to_weights( &plus ), &to_weights( &minus ), &opts );
+        for i in 0..5 {
+            params[i] += ak * result / (2.0 * ck * delta[i]);
+            params[i] = params[i].clamp( LIMITS[i].0 / SCALE[i], LIMITS[i].1 / SCALE[i] );
+        }
+
+        if (k + 1) % 10 == 0 || k + 1 == opts.iterations {
+            print_weights( &format!("iter {}", k + 1 ), &to_weights( &params ) );
+        }
+    }
+
+    // How it does against the weights we started with
+    let tuned = to_weights( &params );
+    let check = Options { games: opts.games * 4, ..opts };
+    let score = play_match( &mut rng, &tuned, &start, &check );
+    print_weights( "tuned", &tuned );
+    println!("Tuned vs start over {} games: {:+.3} (1 is winning every game)", check.games, score );
+
+    file.bots.insert( check.bot.clone(), tuned );
+    match file.save( &check.out ) {
+        Ok(_) => println!("Saved weights for {} to {}", check.bot, check.out ),
+        Err(err) => {
+            eprintln!("Couldn't save {}: {}", check.out, err );
+            std::process::exit( 1 );
+        }
+    }
+}
@@ -0,0 +1,346 @@
+use std::sync::OnceLock;
+
+use crate::gamestate::*;
+
+// A compact board for deep searches. GameSnapshot is over 1.6k and gets copied
+// for every candidate move, this is about 200 bytes. Each space is one bit in
+// a u128, bit n is map index n. Moves are found by walking precomputed rays
+// and only testing bits, and evaluation matches evaluate_position exactly.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Bitboard {
+    pub in_map : u128,       // Blocked or Playable
+    pub playable : u128,
+    pub occupied : u128,     // any stack
+    pub owned : [u128; 4],   // stacks per player
+    pub power : [u8; MAP_SZ * MAP_SZ],
+}
+
+// A split, as (src, dest, amount) like find_split
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BitMove {
+    pub src : u8,
+    pub dest : u8,
+    pub amount : u8,
+}
+
+// Same order as MapDirection::iterator, so moves come out in the same order
+// as gen_valid_moves and ties are broken the same way
+pub const DIRECTIONS : [MapDirection; 6] = [ MapDirection::North, MapDirection::NorthEast, MapDirection::SouthEast,
+                                             MapDirection::South, MapDirection::SouthWest, MapDirection::NorthWest ];
+
+// Every space a stack could slide over from each start, nearest first. These
+// only depend on the board edges, not what's on the board.
+pub struct RayTables {
+    pub cells : [[[u8; MAP_SZ]; MAP_SZ * MAP_SZ]; 6],
+    pub len : [[u8; MAP_SZ * MAP_SZ]; 6],
+    pub mask : [[u128; MAP_SZ * MAP_SZ]; 6],
+}
+
+pub fn rays() -> &'static RayTables
+{
+    static RAYS : OnceLock<RayTables> = OnceLock::new();
+    RAYS.get_or_init( || {
+        let mut tables = RayTables {
+            cells: [[[0; MAP_SZ]; MAP_SZ * MAP_SZ]; 6],
+            len: [[0; MAP_SZ * MAP_SZ]; 6],
+            mask: [[0; MAP_SZ * MAP_SZ]; 6],
+        };
+
+        for (dndx, dir) in DIRECTIONS.iter().enumerate() {
+            for start in 0..MAP_SZ * MAP_SZ {
+                let mut count = 0;
+                let mut curr = move_dir( start as i32, *dir );
+                while curr != INVALID as i32 {
+                    tables.cells[dndx][start][count] = curr as u8;
+                    tables.mask[dndx][start] |= bit( curr as usize );
+                    count += 1;
+                    curr = move_dir( curr, *dir );
+                }
+                tables.len[dndx][start] = count as u8;
+            }
+        }
+
+        tables
+    })
+}
+
+#[inline]
+pub fn bit( ndx : usize ) -> u128
+{
+    1u128 << ndx
+}
+
+// The set spaces in a mask, lowest first
+pub fn bits( mut mask : u128 ) -> impl Iterator<Item = usize>
+{
+    std::iter::from_fn( move || {
+        if mask == 0 {
+            None
+        } else {
+            let ndx = mask.trailing_zeros() as usize;
+            mask &= mask - 1;
+            Some( ndx )
+        }
+    })
+}
+
+impl Default for Bitboard {
+    fn default() -> Bitboard {
+        Bitboard {
+            in_map: 0,
+            playable: 0,
+            occupied: 0,
+            owned: [ 0; 4 ],
+            power: [ 0; MAP_SZ * MAP_SZ ],
+        }
+    }
+}
+
+impl Bitboard {
+    pub fn from_snapshot( snap : &GameSnapshot ) -> Bitboard
+    {
+        let mut board = Bitboard::default();
+        for (ndx, mapsq) in snap.map.spaces.iter().enumerate() {
+            match mapsq.contents {
+                MapSpaceContents::NotInMap => continue,
+                MapSpaceContents::Blocked => board.in_map |= bit( ndx ),
+                MapSpaceContents::Playable => {
+                    board.in_map |= bit( ndx );
+                    board.playable |= bit( ndx );
+                }
+            }
+
+            if mapsq.power > 0 && mapsq.player > 0 {
+                board.occupied |= bit( ndx );
+                board.owned[ (mapsq.player - 1) as usize ] |= bit( ndx );
+                board.power[ ndx ] = mapsq.power;
+            }
+        }
+        board
+    }
+
+    pub fn to_snapshot( &self ) -> GameSnapshot
+    {
+        let mut snap = GameSnapshot::default();
+        for (ndx, mapsq) in (&mut snap.map).into_iter().enumerate() {
+            mapsq.ndx = ndx as i32;
+            mapsq.contents = if self.playable & bit( ndx ) != 0 {
+                MapSpaceContents::Playable
+            } else if self.in_map & bit( ndx ) != 0 {
+                MapSpaceContents::Blocked
+            } else {
+                MapSpaceContents::NotInMap
+            };
+
+            if let Some(player) = self.owner( ndx ) {
+                mapsq.player = (player + 1) as u8;
+                mapsq.power = self.power[ ndx ];
+            }
+        }
+        snap.update_scores();
+        snap
+    }
+
+    // Which player has a stack here, 0 based
+    pub fn owner( &self, ndx : usize ) -> Option<usize>
+    {
+        self.owned.iter().position( |mask| mask & bit( ndx ) != 0 )
+    }
+
+    // Spaces a stack can't slide into or through
+    #[inline]
+    pub fn blockers( &self ) -> u128
+    {
+        !self.playable | self.occupied
+    }
+
+    // Same as GameMap::search_dir, where a stack at `ndx` stops
+    #[inline]
+    pub fn slide( &self, ndx : usize, dndx : usize ) -> usize
+    {
+        let tables = rays();
+        let len = tables.len[dndx][ndx] as usize;
+        let ray = &tables.cells[dndx][ndx];
+        let blockers = self.blockers();
+
+        // Nothing in the way, it goes all the way to the edge
+        if tables.mask[dndx][ndx] & blockers == 0 {
+            return if len == 0 { ndx } else { ray[ len - 1 ] as usize };
+        }
+
+        let mut last = ndx;
+        for cell in &ray[ ..len ] {
+            if blockers & bit( *cell as usize ) != 0 {
+                break;
+            }
+            last = *cell as usize;
+        }
+        last
+    }
+
+    // Same hash as GameSnapshot::zobrist
+    pub fn zobrist( &self, to_move : usize ) -> u64
+    {
+        let mut hash = zobrist_turn( to_move );
+        for player in 0..4 {
+            for ndx in bits( self.owned[ player ] ) {
+                hash ^= zobrist_key( ndx, (player + 1) as u8, self.power[ ndx ] );
+            }
+        }
+        hash
+    }
+
+    pub fn score( &self, player : usize ) -> i32
+    {
+        self.owned[ player ].count_ones() as i32
+    }
+
+    // Same moves in the same order as gen_valid_moves
+    pub fn gen_moves( &self, for_player : usize ) -> Vec<BitMove>
+    {
+        let mut result = Vec::new();
+        self.gen_moves_into( for_player, &mut result );
+        result
+    }
+
+    // For searches that want to reuse one buffer per ply
+    pub fn gen_moves_into( &self, for_player : usize, result : &mut Vec<BitMove> )
+    {
+        result.clear();
+        for src in bits( self.owned[ for_player ] ) {
+            let power = self.power[ src ];
+            if power < 2 {
+                continue;
+            }
+
+            for dndx in 0..DIRECTIONS.len() {
+                let dest = self.slide( src, dndx );
+                if dest == src {
+                    continue;
+                }
+
+                for amount in 1..power {
+                    result.push( BitMove { src: src as u8, dest: dest as u8, amount } );
+                }
+            }
+        }
+    }
+
+    pub fn apply( &self, mv : BitMove, for_player : usize ) -> Bitboard
+    {
+        let mut next = *self;
+        let (src, dest) = (mv.src as usize, mv.dest as usize);
+        next.power[ src ] -= mv.amount;
+        next.power[ dest ] += mv.amount;
+        next.occupied |= bit( dest );
+        next.owned[ for_player ] |= bit( dest );
+        next
+    }
+
+    pub fn count_moves( &self, for_player : usize ) -> u64
+    {
+        let mut count = 0;
+        for src in bits( self.owned[ for_player ] ) {
+            let power = self.power[ src ] as u64;
+            if power < 2 {
+                continue;
+            }
+            for dndx in 0..DIRECTIONS.len() {
+                if self.slide( src, dndx ) != src {
+                    count += power - 1;
+                }
+            }
+        }
+        count
+    }
+
+    // Same as gamestate::perft
+    pub fn perft( &self, players : &[usize], turn : usize, depth : u32 ) -> u64
+    {
+        if depth == 0 {
+            return 1;
+        }
+
+        let for_player = players[ turn ];
+        let next_turn = (turn + 1) % players.len();
+        if depth == 1 {
+            return self.count_moves( for_player ).max( 1 );
+        }
+
+        let moves = self.gen_moves( for_player );
+        if moves.is_empty() {
+            return self.perft( players, next_turn, depth - 1 );
+        }
+
+        moves.iter().map( |mv| self.apply( *mv, for_player ).perft( players, next_turn, depth - 1 ) ).sum()
+    }
+
+    // Which spaces each player could split onto next move, like access_map
+    pub fn reach( &self ) -> [u128; 4]
+    {
+        let mut reach = [ 0; 4 ];
+        for (player, player_reach) in reach.iter_mut().enumerate() {
+            for src in bits( self.owned[ player ] ) {
+                if self.power[ src ] > 1 {
+                    for dndx in 0..DIRECTIONS.len() {
+                        let dest = self.slide( src, dndx );
+                        if dest != src {
+                            *player_reach |= bit( dest );
+                        }
+                    }
+                }
+            }
+        }
+        reach
+    }
+
+    // Same numbers as evaluate_position
+    pub fn evaluate( &self ) -> [i32; 4]
+    {
+        self.evaluate_with( &DEFAULT_WEIGHTS )
+    }
+
+    // Same numbers as evaluate_position_with
+    pub fn evaluate_with( &self, weights : &EvalWeights ) -> [i32; 4]
+    {
+        let tables = rays();
+        let reach = self.reach();
+        let blockers = self.blockers();
+        let mut eval_score = [ 0; 4 ];
+
+        for (player, player_score) in eval_score.iter_mut().enumerate() {
+            let others = reach.iter().enumerate()
+                .filter( |(p, _)| *p != player )
+                .fold( 0, |acc, (_, mask)| acc | mask );
+
+            for src in bits( self.owned[ player ] ) {
+                let mut weight : i32 = weights.stack;
+                let power = self.power[ src ];
+                if power > 1 {
+                    let movepower : i32 = ((power - 1) as i32) * weights.spare_power;
+                    let mut opportunity : i32 = 0;
+                    for dndx in 0..DIRECTIONS.len() {
+                        let mut distancefactor : i32 = weights.reach_start;
+                        let len = tables.len[dndx][src] as usize;
+                        for cell in &tables.cells[dndx][src][ ..len ] {
+                            let cell_bit = bit( *cell as usize );
+                            if blockers & cell_bit != 0 {
+                                break;
+                            }
+                            if others & cell_bit == 0 {
+                                distancefactor *= weights.open_decay;
+                            } else {
+                                distancefactor *= weights.contested_decay;
+                            }
+                            distancefactor /= 1000;
+                            opportunity += distancefactor;
+                        }
+                    }
+                    weight += harmonic( movepower, opportunity, weights.harmonic_scale );
+                }
+                *player_score += weight;
+            }
+        }
+        eval_score
+    }
+}
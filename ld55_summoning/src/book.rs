@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+
+use rand::Rng;
+use serde::{ Deserialize, Serialize };
+
+use crate::bitboard::{ bits, BitMove, Bitboard };
+use crate::gamestate::*;
+use crate::ronfile::RonFile;
+use crate::selfplay::play_game;
+
+// Opening moves from self-play, so bots don't have to think hard about the
+// first few splits off a full starting stack. Boards are looked up by a hash of
+// the terrain and stacks, after turning them to a standard orientation, so a
+// board seen mirrored or the other way up still finds its entry.
+pub const BOOK_FILE : &str = "summoning_book.ron";
+
+// A move needs this many games behind it before bots trust it
+pub const BOOK_MIN_GAMES : u32 = 4;
+
+// One way of laying a board onto the grid. With the columns shifted up on
+// even columns, flipping the rows only lines the hexes back up if the columns
+// move by an odd amount, and mirroring the columns needs an even amount.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Orientation {
+    pub flip_rows : bool,
+    pub mirror_cols : bool,
+    pub row_shift : i32,
+    pub col_shift : i32,
+}
+
+pub const IDENTITY : Orientation = Orientation { flip_rows: false, mirror_cols: false, row_shift: 0, col_shift: 0 };
+
+impl Orientation {
+    // Flips first, then shifts, None if it ends up off the grid
+    pub fn apply( &self, ndx : usize ) -> Option<usize>
+    {
+        let row = (ndx / MAP_SZ) as i32;
+        let col = (ndx % MAP_SZ) as i32;
+        let row = if self.flip_rows { -row } else { row } + self.row_shift;
+        let col = if self.mirror_cols { -col } else { col } + self.col_shift;
+        let ndx = map_index( row, col );
+        if ndx == INVALID as i32 { None } else { Some( ndx as usize ) }
+    }
+
+    pub fn invert( &self, ndx : usize ) -> Option<usize>
+    {
+        let row = (ndx / MAP_SZ) as i32 - self.row_shift;
+        let col = (ndx % MAP_SZ) as i32 - self.col_shift;
+        let row = if self.flip_rows { -row } else { row };
+        let col = if self.mirror_cols { -col } else { col };
+        let ndx = map_index( row, col );
+        if ndx == INVALID as i32 { None } else { Some( ndx as usize ) }
+    }
+
+    // Every orientation of this board, each slid down into the bottom left
+    // corner as far as the hexes allow
+    pub fn candidates( board : &Bitboard ) -> Vec<Orientation>
+    {
+        let mut result = Vec::new();
+        for (flip_rows, mirror_cols) in [ (false, false), (false, true), (true, false), (true, true) ] {
+            let mut orient = Orientation { flip_rows, mirror_cols, row_shift: 0, col_shift: 0 };
+            let placed : Vec<(i32, i32)> = bits( board.in_map ).map( |ndx| {
+                let row = (ndx / MAP_SZ) as i32;
+                let col = (ndx % MAP_SZ) as i32;
+                ( if flip_rows { -row } else { row }, if mirror_cols { -col } else { col } )
+            }).collect();
+
+            orient.row_shift = -placed.iter().map( |p| p.0 ).min().unwrap_or( 0 );
+            orient.col_shift = -placed.iter().map( |p| p.1 ).min().unwrap_or( 0 );
+            if (orient.col_shift % 2 != 0) != flip_rows {
+                orient.col_shift += 1;
+            }
+
+            if bits( board.in_map ).all( |ndx| orient.apply( ndx ).is_some() ) {
+                result.push( orient );
+            }
+        }
+        result
+    }
+
+    pub fn board( &self, board : &Bitboard ) -> Bitboard
+    {
+        let moved = |mask : u128| bits( mask ).filter_map( |ndx| self.apply( ndx ) )
+            .fold( 0u128, |acc, ndx| acc | (1u128 << ndx) );
+
+        let mut result = Bitboard {
+            in_map: moved( board.in_map ),
+            playable: moved( board.playable ),
+            occupied: moved( board.occupied ),
+            owned: [ 0; 4 ],
+            power: [ 0; MAP_SZ * MAP_SZ ],
+        };
+        for player in 0..4 {
+            result.owned[ player ] = moved( board.owned[ player ] );
+        }
+        for ndx in bits( board.occupied ) {
+            if let Some(dest) = self.apply( ndx ) {
+                result.power[ dest ] = board.power[ ndx ];
+            }
+        }
+        result
+    }
+
+    pub fn apply_move( &self, mv : BitMove ) -> Option<BitMove>
+    {
+        Some( BitMove { src: self.apply( mv.src as usize )? as u8, dest: self.apply( mv.dest as usize )? as u8, amount: mv.amount } )
+    }
+
+    pub fn invert_move( &self, mv : BitMove ) -> Option<BitMove>
+    {
+        Some( BitMove { src: self.invert( mv.src as usize )? as u8, dest: self.invert( mv.dest as usize )? as u8, amount: mv.amount } )
+    }
+}
+
+// Like Bitboard::zobrist but with the terrain too, since the book sees many maps
+pub fn board_key( board : &Bitboard, to_move : usize ) -> u64
+{
+    let mut hash = board.zobrist( to_move );
+    for ndx in bits( board.in_map ) {
+        // No stack has player 0, so these can't clash with the stack keys
+        hash ^= zobrist_key( ndx, 0, if board.playable & (1u128 << ndx) != 0 { 1 } else { 2 } );
+    }
+    hash
+}
+
+// The key a board is filed under, and how to get it there. Picks whichever
+// orientation hashes lowest, so every orientation of a board agrees.
+pub fn normalize( board : &Bitboard, to_move : usize ) -> (u64, Orientation)
+{
+    Orientation::candidates( board ).into_iter()
+        .map( |orient| (board_key( &orient.board( board ), to_move ), orient) )
+        .min_by_key( |(key, _)| *key )
+        .unwrap_or( (board_key( board, to_move ), IDENTITY) )
+}
+
+// How a move has done for the player who made it. Score is the player's share
+// of the wins summed over the games it was played in.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct BookMove {
+    pub src : u8,
+    pub dest : u8,
+    pub amount : u8,
+    pub played : u32,
+    pub score : f32,
+}
+
+impl BookMove {
+    pub fn bit_move( &self ) -> BitMove
+    {
+        BitMove { src: self.src, dest: self.dest, amount: self.amount }
+    }
+
+    pub fn average( &self ) -> f32
+    {
+        if self.played == 0 { 0.0 } else { self.score / self.played as f32 }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct OpeningBook {
+    pub plies : u32,  // how many moves into each game get recorded
+    pub positions : BTreeMap<u64, Vec<BookMove>>,
+}
+
+impl OpeningBook {
+    pub fn new( plies : u32 ) -> OpeningBook
+    {
+        OpeningBook { plies, positions: BTreeMap::new() }
+    }
+
+    pub fn len( &self ) -> usize
+    {
+        self.positions.len()
+    }
+
+    pub fn is_empty( &self ) -> bool
+    {
+        self.positions.is_empty()
+    }
+
+    pub fn record( &mut self, board : &Bitboard, to_move : usize, mv : BitMove, score : f32 )
+    {
+        let (key, orient) = normalize( board, to_move );
+        let Some(mv) = orient.apply_move( mv ) else { return };
+
+        let moves = self.positions.entry( key ).or_default();
+        match moves.iter_mut().find( |m| m.bit_move() == mv ) {
+            Some(entry) => {
+                entry.played += 1;
+                entry.score += score;
+            }
+            None => moves.push( BookMove { src: mv.src, dest: mv.dest, amount: mv.amount, played: 1, score } ),
+        }
+    }
+
+    // Everything known about this board, turned to match it
+    pub fn moves( &self, board : &Bitboard, to_move : usize ) -> Vec<BookMove>
+    {
+        let (key, orient) = normalize( board, to_move );
+        let Some(moves) = self.positions.get( &key ) else { return Vec::new() };
+
+        moves.iter().filter_map( |m| {
+            let mv = orient.invert_move( m.bit_move() )?;
+            Some( BookMove { src: mv.src, dest: mv.dest, amount: mv.amount, ..*m } )
+        }).collect()
+    }
+
+    // The best scoring move with enough games behind it. Checked against the
+    // real moves in case two boards ever share a hash.
+    pub fn lookup( &self, board : &Bitboard, to_move : usize ) -> Option<BitMove>
+    {
+        let legal = board.gen_moves( to_move );
+        self.moves( board, to_move ).into_iter()
+            .filter( |m| m.played >= BOOK_MIN_GAMES && legal.contains( &m.bit_move() ) )
+            .max_by( |a, b| a.average().total_cmp( &b.average() ).then( a.played.cmp( &b.played ) ) )
+            .map( |m| m.bit_move() )
+    }
+
+    // Files the first few moves of a finished game under each mover
+    pub fn add_game( &mut self, start : &GameSnapshot, players : &[usize], scores : [i32; 4], history : &[(usize, BitMove)] )
+    {
+        let best = players.iter().map( |p| scores[ *p ] ).max().unwrap_or( 0 );
+        let winners = players.iter().filter( |p| best > 0 && scores[ **p ] == best ).count();
+
+        let mut board = Bitboard::from_snapshot( start );
+        for (player, mv) in history.iter().take( self.plies as usize ) {
+            let score = if winners > 0 && scores[ *player ] == best { 1.0 / winners as f32 } else { 0.0 };
+            self.record( &board, *player, *mv, score );
+            board = board.apply( *mv, *player );
+        }
+    }
+
+    // Plays `games` self-play games on each map and records them
+    pub fn build<R: Rng + ?Sized>( &mut self, rng : &mut R, maps : &[GameSnapshot], players : &[usize],
+                                   games : u32, weights : &[EvalWeights; 4], noise : i32 )
+    {
+        for map in maps {
+            for _ in 0..games {
+                let result = play_game( rng, map, players, weights, noise );
+                self.add_game( map, players, result.scores, &result.history );
+            }
+        }
+    }
+}
+
+impl RonFile for OpeningBook {}
@@ -0,0 +1,222 @@
+use bevy::{
+    input::mouse::{ MouseMotion, MouseScrollUnit, MouseWheel },
+    prelude::*,
+    window::WindowResized,
+};
+
+use crate::summongame::{ playing, MapBuilt, SummonGame, HEX_SZ };
+use crate::gamestate::MapSpaceContents;
+use crate::map::worldpos_from_mapindex;
+use crate::GameCamera;
+
+// Orbit camera around a focus point on the board. The controls move the
+// target_ values and the camera eases toward them.
+#[derive(Component)]
+pub struct CameraRig {
+    pub focus : Vec3,
+    pub yaw : f32,
+    pub pitch : f32,
+    pub distance : f32,
+
+    pub target_focus : Vec3,
+    pub target_yaw : f32,
+    pub target_pitch : f32,
+    pub target_distance : f32,
+
+    // Framing of the whole board, what reset goes back to
+    home_focus : Vec3,
+    home_distance : f32,
+}
+
+const DEFAULT_PITCH : f32 = 1.030_376_8; // atan2(15, 9), the original view
+const MIN_PITCH : f32 = 0.35;
+const MAX_PITCH : f32 = 1.5;
+const MIN_DISTANCE : f32 = 5.0;
+const MAX_DISTANCE : f32 = 40.0;
+
+impl Default for CameraRig {
+    fn default() -> CameraRig {
+        // Matches the old fixed camera at (0, 15, 12) looking at (0, 0, 3)
+        let focus = Vec3::new( 0.0, 0.0, 3.0 );
+        let distance = Vec2::new( 15.0, 9.0 ).length();
+        CameraRig {
+            focus,
+            yaw: 0.0,
+            pitch: DEFAULT_PITCH,
+            distance,
+            target_focus: focus,
+            target_yaw: 0.0,
+            target_pitch: DEFAULT_PITCH,
+            target_distance: distance,
+            home_focus: focus,
+            home_distance: distance,
+        }
+    }
+}
+
+impl CameraRig {
+    pub fn transform( &self ) -> Transform {
+        let offset = Vec3::new(
+            self.yaw.sin() * self.pitch.cos(),
+            self.pitch.sin(),
+            self.yaw.cos() * self.pitch.cos() ) * self.distance;
+
+        Transform::from_translation( self.focus + offset ).looking_at( self.focus, Vec3::Y )
+    }
+
+    pub fn reset( &mut self ) {
+        self.target_focus = self.home_focus;
+        self.target_yaw = 0.0;
+        self.target_pitch = DEFAULT_PITCH;
+        self.target_distance = self.home_distance;
+    }
+
+    // Slide the focus along the ground, in screen-ish directions
+    pub fn pan( &mut self, delta : Vec2 ) {
+        let right = Vec3::new( self.yaw.cos(), 0.0, -self.yaw.sin() );
+        let forward = Vec3::new( -self.yaw.sin(), 0.0, -self.yaw.cos() );
+        self.target_focus += (right * delta.x + forward * delta.y) * self.target_distance;
+    }
+
+    pub fn zoom( &mut self, factor : f32 ) {
+        self.target_distance = (self.target_distance * factor).clamp( MIN_DISTANCE, MAX_DISTANCE );
+    }
+
+    pub fn orbit( &mut self, yaw : f32, pitch : f32 ) {
+        self.target_yaw += yaw;
+        self.target_pitch = (self.target_pitch + pitch).clamp( MIN_PITCH, MAX_PITCH );
+    }
+}
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, (
+                frame_board,
+                camera_controls,
+                update_camera_rig,
+                )
+                .chain()
+                .run_if(playing));
+    }
+}
+
+// Fit the playable spaces of the board into view. Re-run when the map is
+// built or the window changes shape.
+fn frame_board(
+    game: Res<SummonGame>,
+    mut camera_q: Query<(&mut CameraRig, &Projection), With<GameCamera>>,
+    mut ev_built: EventReader<MapBuilt>,
+    mut ev_resize: EventReader<WindowResized>,
+)
+{
+    let built = ev_built.read().count() > 0;
+    let resized = ev_resize.read().count() > 0;
+    if !built && !resized {
+        return;
+    }
+
+    let mut bounds_min = Vec3::splat( f32::MAX );
+    let mut bounds_max = Vec3::splat( f32::MIN );
+    for mapsq in &game.snapshot.map {
+        if mapsq.contents == MapSpaceContents::Playable {
+            let pos = worldpos_from_mapindex( mapsq.ndx );
+            bounds_min = bounds_min.min( pos );
+            bounds_max = bounds_max.max( pos );
+        }
+    }
+
+    if bounds_min.x > bounds_max.x {
+        return;
+    }
+
+    let (mut rig, projection) = camera_q.single_mut();
+
+    let (fov, aspect) = match projection {
+        Projection::Perspective( persp ) => (persp.fov, persp.aspect_ratio),
+        _ => (std::f32::consts::FRAC_PI_4, 1.0),
+    };
+
+    // Fit a sphere around the board into the narrower of the two fovs
+    let radius = (bounds_max - bounds_min).length() * 0.5 + HEX_SZ;
+    let hfov = 2.0 * ((fov * 0.5).tan() * aspect).atan();
+    let distance = radius / (fov.min( hfov ) * 0.5).sin();
+
+    // Only move the camera if the player hasn't been steering it
+    let at_home = rig.target_focus.distance( rig.home_focus ) < 0.01 &&
+                  (rig.target_distance - rig.home_distance).abs() < 0.01;
+
+    rig.home_focus = (bounds_min + bounds_max) * 0.5;
+    rig.home_distance = distance.clamp( MIN_DISTANCE, MAX_DISTANCE );
+    println!("Framing board, focus {:?} distance {}", rig.home_focus, rig.home_distance );
+
+    if built || at_home {
+        rig.reset();
+    }
+}
+
+fn camera_controls(
+    time: Res<Time>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    mut ev_motion: EventReader<MouseMotion>,
+    mut ev_wheel: EventReader<MouseWheel>,
+    mut camera_q: Query<&mut CameraRig, With<GameCamera>>,
+)
+{
+    let mut rig = camera_q.single_mut();
+
+    let mut motion = Vec2::ZERO;
+    for ev in ev_motion.read() {
+        motion += ev.delta;
+    }
+
+    // Right drag orbits, middle drag pans
+    if mouse_button_input.pressed( MouseButton::Right ) {
+        rig.orbit( -motion.x * 0.005, motion.y * 0.005 );
+    }
+    if mouse_button_input.pressed( MouseButton::Middle ) {
+        rig.pan( Vec2::new( -motion.x, motion.y ) * 0.0015 );
+    }
+
+    for ev in ev_wheel.read() {
+        let lines = match ev.unit {
+            MouseScrollUnit::Line => ev.y,
+            MouseScrollUnit::Pixel => ev.y / 40.0,
+        };
+        rig.zoom( 0.9_f32.powf( lines ) );
+    }
+
+    // WASD pans too
+    let mut pan = Vec2::ZERO;
+    if keyboard_input.pressed( KeyCode::KeyW ) { pan.y += 1.0; }
+    if keyboard_input.pressed( KeyCode::KeyS ) { pan.y -= 1.0; }
+    if keyboard_input.pressed( KeyCode::KeyA ) { pan.x -= 1.0; }
+    if keyboard_input.pressed( KeyCode::KeyD ) { pan.x += 1.0; }
+    if pan != Vec2::ZERO {
+        rig.pan( pan * time.delta_seconds() * 0.8 );
+    }
+
+    if keyboard_input.just_pressed( KeyCode::KeyR ) || keyboard_input.just_pressed( KeyCode::Home ) {
+        rig.reset();
+    }
+}
+
+fn update_camera_rig(
+    time: Res<Time>,
+    mut camera_q: Query<(&mut CameraRig, &mut Transform), With<GameCamera>>,
+)
+{
+    let (mut rig, mut xform) = camera_q.single_mut();
+
+    // Ease toward the target, framerate independent
+    let t = 1.0 - (-8.0 * time.delta_seconds()).exp();
+    rig.focus = rig.focus.lerp( rig.target_focus, t );
+    rig.yaw += (rig.target_yaw - rig.yaw) * t;
+    rig.pitch += (rig.target_pitch - rig.pitch) * t;
+    rig.distance += (rig.target_distance - rig.distance) * t;
+
+    *xform = rig.transform();
+}
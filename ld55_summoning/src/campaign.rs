@@ -0,0 +1,518 @@
+use bevy::prelude::*;
+use serde::{ Deserialize, Serialize };
+
+use crate::summongame::{ playing, BotDifficulty, ClockMode, GameAppState, GameOver, GameplayCleanup, GoodStuff, MatchSettings,
+                         PlayerType, SummonGame, TimeoutPenalty };
+use crate::titlescreen::TitleScreenCleanup;
+use crate::mapgen::MapGenOptions;
+use crate::profiles::BOT_NAMES;
+use crate::ronfile::RonFile;
+
+// A ladder of matches against the bots, each a bit harder than the last. Beat
+// a stage to unlock the next one. Stages can be replayed once unlocked.
+pub const CAMPAIGN_FILE : &str = "summoning_campaign.ron";
+
+// Twists on the normal rules for a stage
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RulesVariant {
+    Standard,
+    BotsFirst,   // the player takes the last seat
+    Blitz(u32),  // seconds per turn, then the bot moves for you
+}
+
+impl RulesVariant {
+    pub fn label( &self ) -> String {
+        match self {
+            RulesVariant::Standard => "Standard rules".into(),
+            RulesVariant::BotsFirst => "Bots move first".into(),
+            RulesVariant::Blitz( secs ) => format!("Blitz, {}s a turn", secs ),
+        }
+    }
+}
+
+pub struct CampaignStage {
+    pub name : &'static str,
+    pub intro : &'static str,
+    pub bots : &'static [(i32, BotDifficulty)],  // bot profile and difficulty for each opponent
+    pub start_power : u8,
+    pub map : MapGenOptions,
+    pub variant : RulesVariant,
+}
+
+pub const STAGES : [CampaignStage; 6] = [
+    CampaignStage {
+        name: "Boot Sequence",
+        intro: "Glitch is still warming up. A small board with room to spread.",
+        bots: &[ (0, BotDifficulty::Easy) ],
+        start_power: 12,
+        map: MapGenOptions { radius: 6.0, blocked_one_in: 0 },
+        variant: RulesVariant::Standard,
+    },
+    CampaignStage {
+        name: "Static",
+        intro: "Glitch takes this seriously now, and goes first.",
+        bots: &[ (0, BotDifficulty::Normal) ],
+        start_power: 16,
+        map: MapGenOptions { radius: 8.0, blocked_one_in: 8 },
+        variant: RulesVariant::BotsFirst,
+    },
+    CampaignStage {
+        name: "Daemon's Den",
+        intro: "Daemon likes a cluttered board.",
+        bots: &[ (1, BotDifficulty::Normal) ],
+        start_power: 16,
+        map: MapGenOptions { radius: 8.0, blocked_one_in: 4 },
+        variant: RulesVariant::Standard,
+    },
+    CampaignStage {
+        name: "Two Minds",
+        intro: "Glitch and Daemon team up against you.",
+        bots: &[ (0, BotDifficulty::Normal), (1, BotDifficulty::Normal) ],
+        start_power: 16,
+        map: MapGenOptions { radius: 8.0, blocked_one_in: 8 },
+        variant: RulesVariant::Standard,
+    },
+    CampaignStage {
+        name: "Overclocked",
+        intro: "Overclock doesn't wait around. Neither can you.",
+        bots: &[ (2, BotDifficulty::Hard) ],
+        start_power: 20,
+        map: MapGenOptions { radius: 8.0, blocked_one_in: 6 },
+        variant: RulesVariant::Blitz( 15 ),
+    },
+    CampaignStage {
+        name: "Full Stack",
+        intro: "Everyone at full strength, and you move last.",
+        bots: &[ (0, BotDifficulty::Hard), (1, BotDifficulty::Hard), (2, BotDifficulty::Hard) ],
+        start_power: 16,
+        map: MapGenOptions { radius: 8.0, blocked_one_in: 8 },
+        variant: RulesVariant::BotsFirst,
+    },
+];
+
+impl CampaignStage {
+    pub fn opponents( &self ) -> String {
+        let names : Vec<String> = self.bots.iter()
+            .map( |(profile, difficulty)| format!("{} ({:?})", BOT_NAMES[ *profile as usize ], difficulty ) )
+            .collect();
+        names.join(", ")
+    }
+}
+
+// How far the player has got, kept between launches
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct CampaignProgress {
+    pub cleared : usize,        // stages beaten, in order
+    pub attempts : Vec<u32>,    // per stage
+}
+
+impl RonFile for CampaignProgress {}
+
+impl CampaignProgress {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load() -> CampaignProgress {
+        CampaignProgress::load_or_report( CAMPAIGN_FILE ).unwrap_or_default()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load() -> CampaignProgress {
+        CampaignProgress::default()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save( &self ) {
+        self.save_or_report( CAMPAIGN_FILE );
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save( &self ) {
+    }
+
+    pub fn unlocked( &self, stage : usize ) -> bool {
+        stage <= self.cleared
+    }
+
+    pub fn attempts( &self, stage : usize ) -> u32 {
+        self.attempts.get( stage ).copied().unwrap_or( 0 )
+    }
+}
+
+// Set while the campaign screen or one of its matches is up. The seats and
+// rules belong to the stage then, so they don't get saved as the settings.
+#[derive(Resource, Default)]
+pub struct ActiveCampaign {
+    pub active : bool,
+    pub stage : Option<usize>,
+    pub player : usize,
+    pub won : Option<bool>,
+}
+
+#[derive(Component)]
+struct CampaignButton;
+
+#[derive(Component)]
+struct CampaignCleanup;
+
+#[derive(Component)]
+enum CampaignAction {
+    Play(usize),
+    Back,
+}
+
+#[derive(Component)]
+struct CampaignLabel;
+
+pub struct CampaignPlugin;
+
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource( CampaignProgress::load() )
+            .insert_resource( ActiveCampaign::default() )
+            .add_systems( OnEnter(GameAppState::TitleScreen), setup_campaign_button )
+            .add_systems(Update, open_campaign.run_if(in_state(GameAppState::TitleScreen)))
+            .add_systems( OnEnter(GameAppState::Campaign), campaign_setup )
+            .add_systems(Update, campaign_action.run_if(in_state(GameAppState::Campaign)))
+            .add_systems( OnExit(GameAppState::Campaign), campaign_teardown )
+            .add_systems( OnEnter(GameAppState::Gameplay), setup_stage_label )
+            .add_systems(Update, record_stage_result.run_if(in_state(GameAppState::Gameplay)))
+            .add_systems(Update, continue_campaign.run_if(playing));
+    }
+}
+
+fn setup_campaign_button(
+    mut commands: Commands,
+    mut campaign: ResMut<ActiveCampaign>,
+)
+{
+    // Back on the title screen, so the saved seats and rules are in charge again
+    *campaign = ActiveCampaign::default();
+
+    commands.spawn((
+        ButtonBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(12.0),
+                left: Val::Px(12.0),
+                padding: UiRect::horizontal( Val::Px( 8.0 )),
+                ..default()
+            },
+            background_color: BackgroundColor( Color::rgba( 0.0, 0.0, 0.0, 0.5 ) ),
+            ..default()
+        },
+        CampaignButton,
+        TitleScreenCleanup,
+    ))
+    .with_children(|parent| {
+        parent.spawn(TextBundle::from_section(
+            "Campaign",
+            TextStyle {
+                font_size: 20.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        ));
+    });
+}
+
+fn open_campaign(
+    button_q: Query<&Interaction, (Changed<Interaction>, With<CampaignButton>)>,
+    mut game_state: ResMut<NextState<GameAppState>>,
+)
+{
+    if button_q.iter().any( |interaction| *interaction == Interaction::Pressed ) {
+        game_state.set( GameAppState::Campaign );
+    }
+}
+
+fn campaign_setup(
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+    progress: Res<CampaignProgress>,
+    mut campaign: ResMut<ActiveCampaign>,
+)
+{
+    println!("Campaign setup!");
+    *campaign = ActiveCampaign { active: true, ..default() };
+
+    commands.spawn(( NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(6.0),
+            padding: UiRect::all( Val::Px( 12.0 )),
+            ..default()
+        },
+        ..default()
+    }, CampaignCleanup ))
+    .with_children( |parent| {
+        parent.spawn(TextBundle::from_section(
+            "Campaign",
+            TextStyle {
+                font: asset_server.load("Cyberthrone.ttf"),
+                font_size: 40.0,
+                color: Color::rgb(1.0, 0.3, 0.9),
+            },
+        ));
+
+        for (ndx, stage) in STAGES.iter().enumerate() {
+            let unlocked = progress.unlocked( ndx );
+            let status = if ndx < progress.cleared {
+                "Cleared".to_string()
+            } else if unlocked {
+                "Next".to_string()
+            } else {
+                "Locked".to_string()
+            };
+
+            let mut row = parent.spawn( ButtonBundle {
+                style: Style {
+                    width: Val::Percent(80.0),
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(10.0),
+                    padding: UiRect::all( Val::Px( 4.0 )),
+                    ..default()
+                },
+                background_color: BackgroundColor( Color::rgba( 0.0, 0.0, 0.0, if unlocked { 0.6 } else { 0.3 } ) ),
+                ..default()
+            });
+            if unlocked {
+                row.insert( CampaignAction::Play( ndx ) );
+            }
+
+            row.with_children( |row| {
+                // The strongest bot in the stage is the face of it
+                let boss = stage.bots.iter().map( |(profile, _)| *profile ).max().unwrap_or( 0 );
+                row.spawn( ImageBundle {
+                    style: Style {
+                        width: Val::Px(48.0),
+                        height: Val::Px(48.0),
+                        ..default()
+                    },
+                    image: UiImage::new( asset_server.load( format!("portrait_bot{}.png", boss + 1 ) ) ),
+                    background_color: BackgroundColor( if unlocked { Color::WHITE } else { Color::rgb( 0.3, 0.3, 0.3 ) } ),
+                    ..default()
+                });
+
+                row.spawn( NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        flex_grow: 1.0,
+                        ..default()
+                    },
+                    ..default()
+                }).with_children( |text_parent| {
+                    text_parent.spawn(TextBundle::from_section(
+                        format!("{}. {}", ndx + 1, stage.name ),
+                        TextStyle {
+                            font_size: 20.0,
+                            color: if unlocked { Color::WHITE } else { Color::GRAY },
+                            ..default()
+                        },
+                    ));
+                    text_parent.spawn(TextBundle::from_section(
+                        format!("{} vs {}. Power {}, {}.", stage.intro, stage.opponents(), stage.start_power, stage.variant.label() ),
+                        TextStyle {
+                            font_size: 14.0,
+                            color: Color::rgb( 0.7, 0.7, 0.7 ),
+                            ..default()
+                        },
+                    ));
+                });
+
+                let attempts = progress.attempts( ndx );
+                row.spawn(TextBundle::from_section(
+                    if attempts > 0 { format!("{}\n{} tries", status, attempts ) } else { status },
+                    TextStyle {
+                        font_size: 16.0,
+                        color: if ndx < progress.cleared { Color::rgb( 0.5, 1.0, 0.5 ) } else { Color::WHITE },
+                        ..default()
+                    },
+                ));
+            });
+        }
+
+        parent.spawn((
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::horizontal( Val::Px( 8.0 )),
+                    margin: UiRect::top( Val::Px( 10.0 )),
+                    ..default()
+                },
+                background_color: BackgroundColor( Color::rgba( 0.0, 0.0, 0.0, 0.5 ) ),
+                ..default()
+            },
+            CampaignAction::Back,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Back",
+                TextStyle {
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+        });
+    });
+}
+
+// Seats the player and the stage's bots, and sets the stage's rules
+fn start_stage( ndx : usize, stuff : &mut GoodStuff, settings : &mut MatchSettings ) -> usize {
+    let stage = &STAGES[ ndx ];
+    let seats = stage.bots.len() + 1;
+    let player = if stage.variant == RulesVariant::BotsFirst { seats - 1 } else { 0 };
+
+    // Whoever was in the first seat on the title screen is the player
+    let human_profile = stuff.player_stuff[0].human_profile;
+    let mut bots = stage.bots.iter();
+    for (pnum, pinfo) in stuff.player_stuff.iter_mut().enumerate() {
+        if pnum == player {
+            pinfo.ptype = PlayerType::Local;
+            pinfo.human_profile = human_profile;
+        } else if pnum < seats {
+            let (profile, difficulty) = bots.next().copied().unwrap_or( (0, BotDifficulty::Normal) );
+            pinfo.ptype = PlayerType::AI;
+            pinfo.bot_profile = profile;
+            pinfo.bot_difficulty = difficulty;
+        } else {
+            pinfo.ptype = PlayerType::NotActive;
+        }
+    }
+
+    settings.start_power = stage.start_power;
+    (settings.clock, settings.timeout_penalty) = match stage.variant {
+        RulesVariant::Blitz( secs ) => (ClockMode::PerTurn( secs ), TimeoutPenalty::AutoMove),
+        _ => (ClockMode::Off, TimeoutPenalty::AutoPass),
+    };
+
+    player
+}
+
+fn campaign_action(
+    mut stuff: ResMut<GoodStuff>,
+    mut settings: ResMut<MatchSettings>,
+    mut campaign: ResMut<ActiveCampaign>,
+    mut game_state: ResMut<NextState<GameAppState>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    interaction_query: Query<(&Interaction, &CampaignAction), Changed<Interaction>>,
+)
+{
+    if keyboard_input.just_pressed( KeyCode::Escape ) {
+        game_state.set( GameAppState::TitleScreen );
+        return;
+    }
+
+    for (interaction, action) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match action {
+            CampaignAction::Play( ndx ) => {
+                println!("Starting campaign stage {}", STAGES[ *ndx ].name );
+                campaign.player = start_stage( *ndx, &mut stuff, &mut settings );
+                campaign.stage = Some( *ndx );
+                campaign.won = None;
+                game_state.set( GameAppState::Gameplay );
+            }
+            CampaignAction::Back => game_state.set( GameAppState::TitleScreen ),
+        }
+    }
+}
+
+fn campaign_teardown(
+    mut commands: Commands,
+    despawn_q: Query<Entity, With<CampaignCleanup>>,
+)
+{
+    for entity in &despawn_q {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// Restarting from the pause menu comes through here too, and counts as another try
+fn setup_stage_label(
+    mut commands: Commands,
+    mut progress: ResMut<CampaignProgress>,
+    mut campaign: ResMut<ActiveCampaign>,
+)
+{
+    let Some(ndx) = campaign.stage else {
+        return;
+    };
+    campaign.won = None;
+
+    if progress.attempts.len() <= ndx {
+        progress.attempts.resize( ndx + 1, 0 );
+    }
+    progress.attempts[ ndx ] += 1;
+    progress.save();
+
+    let stage = &STAGES[ ndx ];
+    commands.spawn((
+        TextBundle::from_section( format!("Stage {}: {}\n{}", ndx + 1, stage.name, stage.variant.label() ),
+            TextStyle {
+                font_size: 24.,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(44.0),
+            right: Val::Px(12.0),
+            ..default()
+        }),
+        CampaignLabel,
+        GameplayCleanup,
+    ));
+}
+
+// Only an outright win clears a stage
+fn record_stage_result(
+    game: Res<SummonGame>,
+    mut progress: ResMut<CampaignProgress>,
+    mut campaign: ResMut<ActiveCampaign>,
+    mut label_q: Query<&mut Text, With<CampaignLabel>>,
+    mut ev_gameover: EventReader<GameOver>,
+)
+{
+    if ev_gameover.read().count() == 0 {
+        return;
+    }
+    let Some(ndx) = campaign.stage else {
+        return;
+    };
+
+    let won = game.leaders() == vec![ campaign.player ];
+    campaign.won = Some( won );
+    if won && ndx == progress.cleared {
+        progress.cleared += 1;
+        progress.save();
+    }
+
+    for mut text in &mut label_q {
+        text.sections[0].value = if won {
+            format!("Stage {} cleared! Enter to continue.", ndx + 1 )
+        } else {
+            "Defeated. Esc, then Restart to retry. Enter to go back.".into()
+        };
+        text.sections[0].style.color = if won { Color::rgb( 0.5, 1.0, 0.5 ) } else { Color::rgb( 1.0, 0.4, 0.4 ) };
+    }
+}
+
+fn continue_campaign(
+    campaign: Res<ActiveCampaign>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut game_state: ResMut<NextState<GameAppState>>,
+)
+{
+    if campaign.won.is_some() && keyboard_input.just_pressed( KeyCode::Enter ) {
+        game_state.set( GameAppState::Campaign );
+    }
+}
@@ -0,0 +1,138 @@
+use bevy::prelude::*;
+
+use crate::summongame::{ playing, BoardAnimation, ClockMode, GameAppState, GameStateChanged, GameplayCleanup,
+                         GoodStuff, MapBuilt, MatchSettings, PlayerType, SummonGame, TimeoutPenalty, TurnAdvance };
+use crate::gamestate::best_move;
+use crate::{ advance_turn, commit_move, GameCursor };
+
+// Turn clocks for human seats. Bots don't need one. The time left lives in
+// SummonGame with the rest of the match, and only runs while the game does.
+#[derive(Component)]
+struct PlayerClock(usize);
+
+pub struct ClockPlugin;
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems( OnEnter(GameAppState::Gameplay), setup_clock_labels )
+            .add_systems(Update, (
+                tick_clock,
+                update_clock_labels,
+                )
+                .chain()
+                .run_if(playing));
+    }
+}
+
+fn setup_clock_labels(
+    mut commands: Commands,
+)
+{
+    // Next to the score labels
+    let mut yy = 24.0;
+    for pnum in 0..4 {
+        commands.spawn((
+            TextBundle::from_section("",
+                TextStyle {
+                    font_size: 24.,
+                    ..default()
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(yy),
+                left: Val::Px(72.0),
+                ..default()
+            }),
+            PlayerClock( pnum ),
+            GameplayCleanup,
+        ));
+
+        yy += 50.0;
+    }
+}
+
+fn tick_clock(
+    time: Res<Time>,
+    settings: Res<MatchSettings>,
+    stuff: Res<GoodStuff>,
+    anim: Res<BoardAnimation>,
+    mut game: ResMut<SummonGame>,
+    mut cursor_q: Query<&mut GameCursor>,
+    mut last_turn: Local<i32>,
+    mut ev_built: EventReader<MapBuilt>,
+    mut ev_turn: EventWriter<TurnAdvance>,
+    mut ev_gamestate: EventWriter<GameStateChanged>,
+)
+{
+    let Some(limit) = settings.clock.seconds() else {
+        return;
+    };
+
+    if ev_built.read().count() > 0 {
+        game.clocks = [ limit; 4 ];
+    }
+
+    // A fresh turn gets a fresh clock, unless it's a clock for the whole game
+    let pnum = game.player_turn as usize;
+    if game.turn_num != *last_turn {
+        *last_turn = game.turn_num;
+        if let ClockMode::PerTurn( _ ) = settings.clock {
+            game.clocks[ pnum ] = limit;
+        }
+    }
+
+    let pinfo = &stuff.player_stuff[ pnum ];
+    if game.game_over || anim.busy || pinfo.ptype != PlayerType::Local || pinfo.out_of_moves {
+        return;
+    }
+
+    game.clocks[ pnum ] = (game.clocks[ pnum ] - time.delta_seconds()).max( 0.0 );
+    if game.clocks[ pnum ] > 0.0 {
+        return;
+    }
+
+    println!("Player {} is out of time, penalty {:?}", pnum + 1, settings.timeout_penalty );
+    for mut cursor_info in &mut cursor_q {
+        cursor_info.drag_from = None;
+        cursor_info.key_dir = None;
+    }
+
+    if settings.timeout_penalty == TimeoutPenalty::AutoMove {
+        if let Some(next) = best_move( game.snapshot, pnum, game.player_count ) {
+            commit_move( &mut game, next, &mut ev_gamestate );
+        }
+    }
+
+    let next = advance_turn( &mut game, &stuff );
+    ev_turn.send( TurnAdvance( next ) );
+}
+
+fn update_clock_labels(
+    settings: Res<MatchSettings>,
+    stuff: Res<GoodStuff>,
+    game: Res<SummonGame>,
+    mut clock_q: Query<(&mut Text, &PlayerClock)>,
+)
+{
+    for (mut text, clock) in &mut clock_q {
+        let pinfo = &stuff.player_stuff[ clock.0 ];
+        if settings.clock == ClockMode::Off || pinfo.ptype != PlayerType::Local || game.conceded[ clock.0 ] {
+            text.sections[0].value = "".into();
+            continue;
+        }
+
+        let secs = game.clocks[ clock.0 ].ceil() as i32;
+        text.sections[0].value = format!("{}:{:02}", secs / 60, secs % 60 );
+
+        // Whoever's turn it is in their color, and red when it's getting short
+        text.sections[0].style.color = if clock.0 as i32 != game.player_turn {
+            Color::rgba( 1.0, 1.0, 1.0, 0.5 )
+        } else if secs <= 5 {
+            Color::rgb( 1.0, 0.2, 0.2 )
+        } else {
+            pinfo.color
+        };
+    }
+}
@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+use serde::{ Deserialize, Serialize };
+
+use crate::summongame::{ GameAppState, GoodStuff, SummonGame };
+use crate::gamestate::MapSpaceContents;
+use crate::map::worldpos_from_mapindex;
+use crate::PlayerScore;
+
+// Player colors. Each palette is a list of swatches (main color and a darker
+// support color) that seats pick from. The ring materials are shared by every
+// ring on the board so recoloring them in place updates the whole board.
+#[derive(Default, PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Palette {
+    #[default]
+    Neon,
+    OkabeIto,   // colorblind safe
+    TolBright,  // colorblind safe
+}
+
+impl Palette {
+    pub fn next( &self ) -> Palette {
+        match self {
+            Palette::Neon => Palette::OkabeIto,
+            Palette::OkabeIto => Palette::TolBright,
+            Palette::TolBright => Palette::Neon,
+        }
+    }
+
+    pub fn name( &self ) -> &'static str {
+        match self {
+            Palette::Neon => "Neon",
+            Palette::OkabeIto => "Okabe-Ito",
+            Palette::TolBright => "Tol Bright",
+        }
+    }
+
+    pub fn swatches( &self ) -> Vec<(Color, Color)> {
+        match self {
+            // The original jam colors, plus a couple of spares
+            Palette::Neon => vec![
+                (Color::rgb_u8( 255, 113, 206 ), Color::rgb_u8( 161, 45, 172 )),
+                (Color::rgb_u8( 1, 205, 254 ), Color::rgb_u8( 1, 150, 114 )),
+                (Color::rgb_u8( 5, 254, 161 ), Color::rgb_u8( 1, 152, 30 )),
+                (Color::rgb_u8( 161, 39, 255 ), Color::rgb_u8( 52, 37, 174 )),
+                (Color::rgb_u8( 255, 190, 40 ), Color::rgb_u8( 170, 110, 10 )),
+                (Color::rgb_u8( 255, 70, 70 ), Color::rgb_u8( 150, 30, 30 )),
+            ],
+            Palette::OkabeIto => [
+                (230, 159, 0),
+                (86, 180, 233),
+                (0, 158, 115),
+                (204, 121, 167),
+                (240, 228, 66),
+                (0, 114, 178),
+                (213, 94, 0),
+            ].iter().map( |&(r, g, b)| with_support( r, g, b ) ).collect(),
+            Palette::TolBright => [
+                (68, 119, 170),
+                (238, 102, 119),
+                (34, 136, 51),
+                (204, 187, 68),
+                (102, 204, 238),
+                (170, 51, 119),
+                (187, 187, 187),
+            ].iter().map( |&(r, g, b)| with_support( r, g, b ) ).collect(),
+        }
+    }
+}
+
+fn with_support( r : u8, g : u8, b : u8 ) -> (Color, Color) {
+    let dim = |c : u8| (c as f32 * 0.6) as u8;
+    (Color::rgb_u8( r, g, b ), Color::rgb_u8( dim( r ), dim( g ), dim( b ) ))
+}
+
+// Base and emissive colors for a ring material of the given player color
+pub fn ring_colors( color : Color ) -> (Color, Color) {
+    let mut color_support = color * 1.5;
+    color_support.set_a( 1.0 );
+
+    let mut color_main = color * 200.0;
+    color_main.set_a( 1.0 );
+
+    (color_support, color_main)
+}
+
+#[derive(Resource, Clone, PartialEq, Debug)]
+pub struct ColorSettings {
+    pub palette : Palette,
+    pub seat_swatch : [usize; 4],
+    pub markers : bool,  // draw a shape on each stack so players aren't told apart by color alone
+}
+
+impl Default for ColorSettings {
+    fn default() -> ColorSettings {
+        ColorSettings {
+            palette: Palette::Neon,
+            seat_swatch: [ 0, 1, 2, 3 ],
+            markers: false,
+        }
+    }
+}
+
+impl ColorSettings {
+    pub fn seat_colors( &self, pnum : usize ) -> (Color, Color) {
+        let swatches = self.palette.swatches();
+        swatches[ self.seat_swatch[ pnum ] % swatches.len() ]
+    }
+
+    pub fn set_palette( &mut self, palette : Palette ) {
+        self.palette = palette;
+        self.seat_swatch = [ 0, 1, 2, 3 ];
+    }
+
+    // Step a seat to the next swatch nobody else is using
+    pub fn cycle_seat( &mut self, pnum : usize, inc : i32 ) {
+        let count = self.palette.swatches().len() as i32;
+        let mut ndx = self.seat_swatch[ pnum ] as i32;
+        for _ in 0..count {
+            ndx = (ndx + inc).rem_euclid( count );
+            let taken = (0..4).any( |p| p != pnum && self.seat_swatch[ p ] as i32 == ndx );
+            if !taken {
+                break;
+            }
+        }
+        self.seat_swatch[ pnum ] = ndx as usize;
+    }
+
+    pub fn apply( &self, stuff : &mut GoodStuff ) {
+        for (pnum, pinfo) in stuff.player_stuff.iter_mut().enumerate() {
+            (pinfo.color, pinfo.color2) = self.seat_colors( pnum );
+        }
+    }
+}
+
+pub struct ColorsPlugin;
+
+impl Plugin for ColorsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource( ColorSettings::default() )
+            .add_systems(Update, recolor_players )
+            .add_systems(Update, draw_player_markers.run_if(in_state(GameAppState::Gameplay)));
+    }
+}
+
+fn recolor_players(
+    colors: Res<ColorSettings>,
+    mut stuff: ResMut<GoodStuff>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut score_q: Query<(&mut Text, &PlayerScore)>,
+)
+{
+    if !colors.is_changed() {
+        return;
+    }
+
+    colors.apply( &mut stuff );
+
+    for pinfo in &stuff.player_stuff {
+        let (base, emissive) = ring_colors( pinfo.color );
+        for handle in &pinfo.ring_mtl {
+            if let Some(mtl) = materials.get_mut( handle ) {
+                mtl.base_color = base;
+                mtl.emissive = emissive;
+            }
+        }
+    }
+
+    for (mut text, score) in &mut score_q {
+        text.sections[0].style.color = stuff.player_stuff[ score.0 as usize ].color;
+    }
+}
+
+// A different outline per player: circle, square, triangle, diamond
+fn draw_player_markers(
+    colors: Res<ColorSettings>,
+    game: Res<SummonGame>,
+    mut gizmos: Gizmos,
+)
+{
+    if !colors.markers {
+        return;
+    }
+
+    let size = 0.22;
+    for mapsq in &game.snapshot.map {
+        if mapsq.contents != MapSpaceContents::Playable || mapsq.player == 0 {
+            continue;
+        }
+
+        let pos = worldpos_from_mapindex( mapsq.ndx ) + Vec3::new( 0.0, 0.2, 0.0 );
+        let corners = |count : usize, start : f32| -> Vec<Vec3> {
+            (0..=count).map( |i| {
+                let a = start + i as f32 * std::f32::consts::TAU / count as f32;
+                pos + Vec3::new( a.cos(), 0.0, a.sin() ) * size
+            }).collect()
+        };
+
+        match mapsq.player {
+            1 => { gizmos.circle( pos, Direction3d::Y, size, Color::WHITE ); }
+            2 => gizmos.linestrip( corners( 4, std::f32::consts::FRAC_PI_4 ), Color::WHITE ),
+            3 => gizmos.linestrip( corners( 3, -std::f32::consts::FRAC_PI_2 ), Color::WHITE ),
+            _ => gizmos.linestrip( corners( 4, 0.0 ), Color::WHITE ),
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use bevy::prelude::*;
+
+use crate::summongame::{ playing, BoardAnimation, GameStateChanged, GoodStuff, PlayerType, SummonGame, TurnAdvance };
+use crate::gamestate::{ move_dir, GameMap, MapDirection, MapSpaceContents, INVALID, MAP_SZ };
+use crate::map::worldpos_from_mapindex;
+use crate::{ step_split, try_split, GameCursor, InputMode };
+
+// Keyboard and gamepad controls, an alternative to dragging with the mouse.
+// Move the cursor onto a stack, select it, pick a direction and how
+// many to split off, then confirm.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum PadAction {
+    Up,
+    Down,
+    Left,
+    Right,
+    Confirm,
+    Cancel,
+    SplitLess,
+    SplitMore,
+}
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, handle_pad_input.run_if(playing));
+    }
+}
+
+fn gather_actions(
+    keyboard_input: &ButtonInput<KeyCode>,
+    gamepads: &Gamepads,
+    gamepad_buttons: &ButtonInput<GamepadButton>,
+) -> Vec<PadAction>
+{
+    let key_binds = [
+        (KeyCode::ArrowUp, PadAction::Up),
+        (KeyCode::ArrowDown, PadAction::Down),
+        (KeyCode::ArrowLeft, PadAction::Left),
+        (KeyCode::ArrowRight, PadAction::Right),
+        (KeyCode::Enter, PadAction::Confirm),
+        (KeyCode::Space, PadAction::Confirm),
+        (KeyCode::Backspace, PadAction::Cancel),
+        (KeyCode::KeyX, PadAction::Cancel),
+        (KeyCode::KeyQ, PadAction::SplitLess),
+        (KeyCode::BracketLeft, PadAction::SplitLess),
+        (KeyCode::KeyE, PadAction::SplitMore),
+        (KeyCode::BracketRight, PadAction::SplitMore),
+    ];
+
+    let pad_binds = [
+        (GamepadButtonType::DPadUp, PadAction::Up),
+        (GamepadButtonType::DPadDown, PadAction::Down),
+        (GamepadButtonType::DPadLeft, PadAction::Left),
+        (GamepadButtonType::DPadRight, PadAction::Right),
+        (GamepadButtonType::South, PadAction::Confirm),
+        (GamepadButtonType::East, PadAction::Cancel),
+        (GamepadButtonType::LeftTrigger, PadAction::SplitLess),
+        (GamepadButtonType::RightTrigger, PadAction::SplitMore),
+    ];
+
+    let mut actions = Vec::new();
+    for (key, action) in key_binds {
+        if keyboard_input.just_pressed( key ) {
+            actions.push( action );
+        }
+    }
+
+    for gamepad in gamepads.iter() {
+        for (button, action) in pad_binds {
+            if gamepad_buttons.just_pressed( GamepadButton::new( gamepad, button ) ) {
+                actions.push( action );
+            }
+        }
+    }
+
+    actions
+}
+
+// Step the cursor to the next space on the board, hopping over gaps. Left and
+// right zig-zag between the two diagonals so they follow a screen row.
+fn step_cursor( map : &GameMap, ndx : i32, action : PadAction ) -> i32
+{
+    let mut curr = ndx;
+    loop {
+        let odd_col = (curr % MAP_SZ as i32) % 2 == 1;
+        let dir = match action {
+            PadAction::Up => MapDirection::North,
+            PadAction::Down => MapDirection::South,
+            PadAction::Left => if odd_col { MapDirection::NorthWest } else { MapDirection::SouthWest },
+            PadAction::Right => if odd_col { MapDirection::NorthEast } else { MapDirection::SouthEast },
+            _ => return ndx,
+        };
+
+        curr = move_dir( curr, dir );
+        if curr == INVALID as i32 {
+            return ndx;
+        }
+        if map.spaces[ curr as usize ].contents != MapSpaceContents::NotInMap {
+            return curr;
+        }
+    }
+}
+
+// Next direction that actually goes somewhere, clockwise or counter-clockwise
+fn cycle_dir( map : &GameMap, src : i32, curr : Option<MapDirection>, clockwise : bool ) -> Option<MapDirection>
+{
+    let mut dirs : Vec<MapDirection> = MapDirection::iterator().collect();
+    if !clockwise {
+        dirs.reverse();
+    }
+
+    let start = curr.and_then( |d| dirs.iter().position( |x| *x == d ) ).map_or( 0, |p| p + 1 );
+    (0..dirs.len())
+        .map( |i| dirs[ (start + i) % dirs.len() ] )
+        .find( |d| map.search_dir( src, *d ) != src )
+}
+
+fn handle_pad_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    mut cursor_q: Query<(&mut Transform, &mut GameCursor)>,
+    stuff: Res<GoodStuff>,
+    anim: Res<BoardAnimation>,
+    mut game: ResMut<SummonGame>,
+    mut ev_gamestate: EventWriter<GameStateChanged>,
+    mut ev_turn: EventWriter<TurnAdvance>,
+)
+{
+    let actions = gather_actions( &keyboard_input, &gamepads, &gamepad_buttons );
+    if actions.is_empty() {
+        return;
+    }
+
+    let pnum = game.player_turn;
+    if stuff.player_stuff[ pnum as usize ].ptype != PlayerType::Local {
+        return;
+    }
+
+    let (mut cursor_transform, mut cursor_info) = cursor_q.single_mut();
+
+    // Taking over from the mouse, start on one of our stacks if the
+    // cursor isn't on the board
+    if cursor_info.input_mode != InputMode::Keys {
+        cursor_info.input_mode = InputMode::Keys;
+        cursor_info.drag_from = None;
+        cursor_info.key_dir = None;
+
+        if game.snapshot.map.spaces[ cursor_info.ndx ].contents == MapSpaceContents::NotInMap {
+            if let Some(mapsq) = game.snapshot.map.spaces.iter().find( |sq| sq.player == (pnum + 1) as u8 ) {
+                cursor_info.ndx = mapsq.ndx as usize;
+            }
+        }
+    }
+
+    for action in actions {
+        match (cursor_info.drag_from, action) {
+
+            // Nothing selected, move the cursor around or pick up a stack
+            (None, PadAction::Up | PadAction::Down | PadAction::Left | PadAction::Right) => {
+                cursor_info.ndx = step_cursor( &game.snapshot.map, cursor_info.ndx as i32, action ) as usize;
+            }
+
+            (None, PadAction::Confirm) => {
+                let ndx = cursor_info.ndx;
+                let mapsq = game.snapshot.map.spaces[ ndx ];
+                if mapsq.power > 1 && mapsq.player == (pnum + 1) as u8 {
+                    let dir = cycle_dir( &game.snapshot.map, ndx as i32, None, true );
+                    if dir.is_some() {
+                        println!("Selected: {}", ndx );
+                        cursor_info.drag_from = Some( ndx );
+                        cursor_info.key_dir = dir;
+                        // Half the stack, but at least one so a power 2 stack can go
+                        cursor_info.split_pct = step_split( 0.5, mapsq.power as i32, 0 );
+                    }
+                }
+            }
+
+            // A stack is selected, aim it and choose the split
+            (Some(src), PadAction::Left | PadAction::Right) => {
+                cursor_info.key_dir = cycle_dir( &game.snapshot.map, src as i32, cursor_info.key_dir, action == PadAction::Right );
+            }
+
+            (Some(src), PadAction::SplitLess | PadAction::SplitMore) => {
+                let src_pow = game.snapshot.map.spaces[ src ].power as i32;
+                let step = if action == PadAction::SplitMore { 1 } else { -1 };
+                cursor_info.split_pct = step_split( cursor_info.split_pct, src_pow, step );
+            }
+
+            (Some(src), PadAction::Confirm) => {
+                if let (Some(dir), false) = (cursor_info.key_dir, anim.busy) {
+                    let split_pct = cursor_info.split_pct;
+                    if try_split( &mut game, &stuff, src as i32, dir, split_pct, &mut ev_gamestate, &mut ev_turn ) {
+                        cursor_info.drag_from = None;
+                        cursor_info.key_dir = None;
+                        break;
+                    }
+                }
+            }
+
+            (Some(_), PadAction::Cancel) => {
+                cursor_info.drag_from = None;
+                cursor_info.key_dir = None;
+            }
+
+            _ => {}
+        }
+    }
+
+    // Keep the cursor and the drag target where the feedback drawing expects them
+    let cursor_pos = worldpos_from_mapindex( cursor_info.ndx as i32 );
+    cursor_transform.translation = cursor_pos;
+    cursor_info.cursor_world = cursor_pos;
+
+    if let (Some(src), Some(dir)) = (cursor_info.drag_from, cursor_info.key_dir) {
+        let found = game.snapshot.map.search_dir( src as i32, dir );
+        cursor_info.cursor_world = worldpos_from_mapindex( found );
+    }
+}
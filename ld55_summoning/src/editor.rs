@@ -0,0 +1,424 @@
+use bevy::prelude::*;
+
+use crate::summongame::{ GameAppState, GoodStuff, MatchSettings, MIN_START_POWER, MAX_START_POWER };
+use crate::gamestate::{ GameSnapshot, MapSpaceContents, MAP_SZ };
+use crate::mapgen::{ generate_map_with, hex_offset, MapGenOptions };
+use crate::mapfile::{ seat_reach, unused_map_name, validate_map, MapChoice, MapFile, MAPS_DIR };
+
+// The map editor. The board is laid out flat as a grid of buttons, clicking
+// one with the terrain tool steps it through not in the map, playable and
+// blocked, and with a seat tool places or removes that seat's starting stack.
+// Saving checks the map is fair, writes it to MAPS_DIR and picks it for the
+// next match on the title screen.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EditorTool {
+    Terrain,
+    Stack(usize),  // seat, 0 based
+}
+
+#[derive(Resource)]
+pub struct MapEditor {
+    pub name : String,
+    pub snapshot : GameSnapshot,
+    pub tool : EditorTool,
+    pub power : u8,
+    pub message : String,
+}
+
+impl Default for MapEditor {
+    fn default() -> MapEditor {
+        MapEditor {
+            name: String::new(),
+            snapshot: GameSnapshot::default(),
+            tool: EditorTool::Terrain,
+            power: MatchSettings::default().start_power,
+            message: String::new(),
+        }
+    }
+}
+
+impl MapEditor {
+    pub fn edit_space( &mut self, ndx : usize ) {
+        let mapsq = &mut self.snapshot.map.spaces[ ndx ];
+        match self.tool {
+            EditorTool::Terrain => {
+                mapsq.contents = match mapsq.contents {
+                    MapSpaceContents::NotInMap => MapSpaceContents::Playable,
+                    MapSpaceContents::Playable => MapSpaceContents::Blocked,
+                    MapSpaceContents::Blocked => MapSpaceContents::NotInMap,
+                };
+                mapsq.player = 0;
+                mapsq.power = 0;
+            }
+            EditorTool::Stack( seat ) => {
+                if mapsq.power > 0 && mapsq.player as usize == seat + 1 {
+                    mapsq.player = 0;
+                    mapsq.power = 0;
+                } else {
+                    mapsq.contents = MapSpaceContents::Playable;
+                    mapsq.player = seat as u8 + 1;
+                    mapsq.power = self.power;
+                }
+            }
+        }
+        self.snapshot.update_scores();
+        self.message.clear();
+    }
+}
+
+#[derive(Component)]
+struct EditorCleanup;
+
+#[derive(Component)]
+struct EditorSpace( usize );
+
+#[derive(Component)]
+struct EditorSpaceText( usize );
+
+#[derive(Component, Clone, Copy, PartialEq)]
+enum EditorAction {
+    Tool( EditorTool ),
+    Power( i32 ),
+    New,
+    Blank,
+    Save,
+    Back,
+}
+
+#[derive(Component)]
+enum EditorLabel {
+    Name,
+    Power,
+    Status,
+}
+
+// Where the board sits on screen, in pixels
+const BOARD_WIDTH : f32 = 500.0;
+const BOARD_HEIGHT : f32 = 560.0;
+const SPACE_SIZE : f32 = 36.0;
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource( MapEditor::default() )
+            .add_systems( OnEnter(GameAppState::Editor), editor_setup )
+            .add_systems(Update, (
+                editor_action,
+                refresh_editor,
+                ).chain().run_if(in_state(GameAppState::Editor)))
+            .add_systems( OnExit(GameAppState::Editor), editor_teardown );
+    }
+}
+
+fn fresh_map( start_power : u8 ) -> GameSnapshot {
+    generate_map_with( &mut rand::thread_rng(), [ true, true, false, false ], start_power, &MapGenOptions::default() )
+}
+
+fn editor_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<MatchSettings>,
+    mut editor: ResMut<MapEditor>,
+)
+{
+    println!("Editor setup!");
+
+    // Open the map picked on the title screen, or start from a generated one
+    *editor = match &settings.map {
+        MapChoice::Saved(map) => MapEditor { name: map.name.clone(), snapshot: map.snapshot, ..default() },
+        _ => MapEditor { name: unused_map_name( MAPS_DIR ), snapshot: fresh_map( settings.start_power ), power: settings.start_power, ..default() },
+    };
+    if let Some(stack) = editor.snapshot.map.spaces.iter().find( |s| s.power > 0 ) {
+        editor.power = stack.power;
+    }
+
+    commands.spawn(( NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Row,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            column_gap: Val::Px(20.0),
+            ..default()
+        },
+        background_color: BackgroundColor( Color::rgba( 0.0, 0.0, 0.0, 0.85 ) ),
+        ..default()
+    }, EditorCleanup ))
+    .with_children( |parent| {
+
+        // ---- The board -----------------------
+        parent.spawn( NodeBundle {
+            style: Style {
+                width: Val::Px( BOARD_WIDTH ),
+                height: Val::Px( BOARD_HEIGHT ),
+                ..default()
+            },
+            ..default()
+        }).with_children( |board| {
+            for ndx in 0..MAP_SZ * MAP_SZ {
+                // Rows go up the screen, like the board in 3D
+                let (x, y) = hex_offset( ndx as i32 );
+                board.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            left: Val::Px( BOARD_WIDTH / 2.0 + x * 32.0 - SPACE_SIZE / 2.0 ),
+                            top: Val::Px( BOARD_HEIGHT / 2.0 + (y - 1.3) * 30.0 - SPACE_SIZE / 2.0 ),
+                            width: Val::Px( SPACE_SIZE ),
+                            height: Val::Px( SPACE_SIZE ),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    EditorSpace( ndx ),
+                ))
+                .with_children( |space| {
+                    space.spawn(( TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 14.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ), EditorSpaceText( ndx ) ));
+                });
+            }
+        });
+
+        // ---- Tools -----------------------
+        parent.spawn( NodeBundle {
+            style: Style {
+                width: Val::Px( 300.0 ),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(8.0),
+                ..default()
+            },
+            ..default()
+        }).with_children( |panel| {
+            panel.spawn((TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("Cyberthrone.ttf"),
+                    font_size: 30.0,
+                    color: Color::rgb(1.0, 0.3, 0.9),
+                },
+            ), EditorLabel::Name ));
+
+            let button = |panel : &mut ChildBuilder, label : String, action : EditorAction| {
+                panel.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::horizontal( Val::Px( 8.0 )),
+                            ..default()
+                        },
+                        background_color: BackgroundColor( Color::rgba( 1.0, 1.0, 1.0, 0.1 ) ),
+                        ..default()
+                    },
+                    action,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        label,
+                        TextStyle {
+                            font_size: 20.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                });
+            };
+
+            button( panel, "Terrain".into(), EditorAction::Tool( EditorTool::Terrain ) );
+            for seat in 0..4 {
+                button( panel, format!("Player {} Stack", seat + 1 ), EditorAction::Tool( EditorTool::Stack( seat ) ) );
+            }
+
+            panel.spawn( NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }).with_children( |row| {
+                for (inc, image) in [ (-1, "btn-arrow-left.png"), (1, "btn-arrow-right.png") ] {
+                    row.spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(16.0),
+                                height: Val::Px(16.0),
+                                ..default()
+                            },
+                            image: asset_server.load( image ).into(),
+                            ..default()
+                        },
+                        EditorAction::Power( inc ),
+                    ));
+                    if inc < 0 {
+                        row.spawn((TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font_size: 20.0,
+                                color: Color::WHITE,
+                                ..default()
+                            },
+                        ), EditorLabel::Power ));
+                    }
+                }
+            });
+
+            panel.spawn((TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ), EditorLabel::Status ));
+
+            button( panel, "Save".into(), EditorAction::Save );
+            button( panel, "New".into(), EditorAction::New );
+            button( panel, "Blank".into(), EditorAction::Blank );
+            button( panel, "Back".into(), EditorAction::Back );
+        });
+    });
+}
+
+fn editor_action(
+    mut editor: ResMut<MapEditor>,
+    mut settings: ResMut<MatchSettings>,
+    mut game_state: ResMut<NextState<GameAppState>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    space_q: Query<(&Interaction, &EditorSpace), Changed<Interaction>>,
+    action_q: Query<(&Interaction, &EditorAction), Changed<Interaction>>,
+)
+{
+    if keyboard_input.just_pressed( KeyCode::Escape ) {
+        game_state.set( GameAppState::TitleScreen );
+        return;
+    }
+
+    for (interaction, space) in &space_q {
+        if *interaction == Interaction::Pressed {
+            editor.edit_space( space.0 );
+        }
+    }
+
+    for (interaction, action) in &action_q {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match *action {
+            EditorAction::Tool( tool ) => editor.tool = tool,
+            EditorAction::Power( inc ) => {
+                editor.power = (editor.power as i32 + inc).clamp( MIN_START_POWER as i32, MAX_START_POWER as i32 ) as u8;
+            }
+            EditorAction::New => {
+                let power = editor.power;
+                *editor = MapEditor { name: unused_map_name( MAPS_DIR ), snapshot: fresh_map( power ), power, ..default() };
+            }
+            EditorAction::Blank => {
+                for mapsq in &mut editor.snapshot.map {
+                    mapsq.contents = MapSpaceContents::Playable;
+                    mapsq.player = 0;
+                    mapsq.power = 0;
+                }
+                editor.snapshot.update_scores();
+                editor.message.clear();
+            }
+            EditorAction::Save => {
+                editor.message = match validate_map( &editor.snapshot ) {
+                    Err(_) => "Fix the map before saving.".into(),
+                    Ok(_) => {
+                        let map = MapFile { name: editor.name.clone(), snapshot: editor.snapshot };
+                        match map.save( MAPS_DIR ) {
+                            Ok(()) => {
+                                println!("Saved map {}", map.name );
+                                let message = format!("Saved to {}, picked for the next match.", MapFile::path( MAPS_DIR, &map.name ).display() );
+                                settings.map = MapChoice::Saved( Box::new( map ) );
+                                message
+                            }
+                            Err(err) => format!("Couldn't save: {}", err ),
+                        }
+                    }
+                };
+            }
+            EditorAction::Back => game_state.set( GameAppState::TitleScreen ),
+        }
+    }
+}
+
+fn space_color( stuff : &GoodStuff, snapshot : &GameSnapshot, ndx : usize ) -> Color {
+    let mapsq = snapshot.map.spaces[ ndx ];
+    match mapsq.contents {
+        MapSpaceContents::NotInMap => Color::rgba( 1.0, 1.0, 1.0, 0.06 ),
+        MapSpaceContents::Blocked => Color::rgb_u8( 96, 60, 100 ),
+        MapSpaceContents::Playable if mapsq.power > 0 => stuff.player_stuff[ mapsq.player as usize - 1 ].color,
+        MapSpaceContents::Playable => Color::rgb( 0.35, 0.35, 0.4 ),
+    }
+}
+
+// Redraws the board and the labels whenever the map or the tools change
+fn refresh_editor(
+    editor: Res<MapEditor>,
+    stuff: Res<GoodStuff>,
+    mut space_q: Query<(&EditorSpace, &mut BackgroundColor), Without<EditorAction>>,
+    mut space_text_q: Query<(&EditorSpaceText, &mut Text), Without<EditorLabel>>,
+    mut action_q: Query<(&EditorAction, &mut BackgroundColor), Without<EditorSpace>>,
+    mut label_q: Query<(&EditorLabel, &mut Text), Without<EditorSpaceText>>,
+)
+{
+    if !editor.is_changed() {
+        return;
+    }
+
+    for (space, mut color) in &mut space_q {
+        color.0 = space_color( &stuff, &editor.snapshot, space.0 );
+    }
+    for (space, mut text) in &mut space_text_q {
+        let power = editor.snapshot.map.spaces[ space.0 ].power;
+        text.sections[0].value = if power > 0 { power.to_string() } else { "".into() };
+    }
+
+    for (action, mut color) in &mut action_q {
+        if let EditorAction::Tool( tool ) = action {
+            color.0 = if *tool == editor.tool { Color::rgba( 1.0, 0.3, 0.9, 0.5 ) } else { Color::rgba( 1.0, 1.0, 1.0, 0.1 ) };
+        }
+    }
+
+    let reach = seat_reach( &editor.snapshot );
+    for (label, mut text) in &mut label_q {
+        let (value, color) = match label {
+            EditorLabel::Name => (editor.name.clone(), None),
+            EditorLabel::Power => (format!("Stack Power: {}", editor.power ), None),
+            EditorLabel::Status => match validate_map( &editor.snapshot ) {
+                Ok(seats) => {
+                    let room : Vec<String> = seats.iter().map( |s| format!("P{} {}", s + 1, reach[*s] ) ).collect();
+                    (format!("Ready for {} players.\nRoom: {}\n{}", seats.len(), room.join(", "), editor.message ), Some( Color::rgb( 0.5, 1.0, 0.5 ) ))
+                }
+                Err(err) => (format!("{}\n{}", err, editor.message ), Some( Color::rgb( 1.0, 0.4, 0.4 ) )),
+            },
+        };
+        text.sections[0].value = value;
+        if let Some(color) = color {
+            text.sections[0].style.color = color;
+        }
+    }
+}
+
+fn editor_teardown(
+    mut commands: Commands,
+    despawn_q: Query<Entity, With<EditorCleanup>>,
+)
+{
+    for entity in &despawn_q {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -1,4 +1,5 @@
 //use std::slice::Iter;
+use serde::{ Deserialize, Serialize };
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum MapSpaceContents {
@@ -7,7 +8,7 @@ pub enum MapSpaceContents {
     Playable,  // A square that can be played on
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum MapDirection {
     North,
     NorthEast,
@@ -114,6 +115,25 @@ pub fn move_dir( ndx : i32, dir : MapDirection ) -> i32
     }
 }
 
+// The spaces in a straight line from src to dest, both included. Ignores
+// what's on the board, so it still works after a split has landed.
+pub fn line_between( src : i32, dest : i32 ) -> Vec<i32>
+{
+    for mapdir in MapDirection::iterator() {
+        let mut line = vec![ src ];
+        let mut curr = src;
+        while curr != INVALID as i32 {
+            curr = move_dir( curr, mapdir );
+            line.push( curr );
+            if curr == dest {
+                return line;
+            }
+        }
+    }
+
+    vec![ src, dest ]
+}
+
 impl GameMap {
      pub fn search_dir( &self, ndx : i32, dir : MapDirection ) -> i32 {
 
@@ -130,6 +150,11 @@ impl GameMap {
         }
      }
 
+     // Which direction slides a stack from src to dest, if any
+     pub fn dir_to( &self, src : i32, dest : i32 ) -> Option<MapDirection> {
+        MapDirection::iterator().find( |dir| self.search_dir( src, *dir ) == dest )
+     }
+
      pub fn neighbors( &self, ndx : i32, valid_only : bool ) -> Vec::<i32> {
 
         let mut result = Vec::new();
@@ -299,10 +324,146 @@ pub fn gen_valid_moves( gamecurr : GameSnapshot, for_player : usize ) -> Vec<Gam
     result
 }
 
-pub fn evaluate_position(snap:GameSnapshot) -> [i32;4]{
-    //let mut result = Vec::new();
+// Find the split that turns `before` into `after`, as (src, dest, amount)
+pub fn find_split( before : &GameSnapshot, after : &GameSnapshot ) -> Option<(i32, i32, u8)>
+{
+    let mut src = None;
+    let mut dest = None;
+    for mapsq in &after.map {
+        let oldsq = before.map.spaces[ mapsq.ndx as usize ];
+        if oldsq.power > mapsq.power {
+            src = Some( mapsq.ndx );
+        } else if oldsq.power < mapsq.power {
+            dest = Some( (mapsq.ndx, mapsq.power - oldsq.power) );
+        }
+    }
+
+    match (src, dest) {
+        (Some(src), Some((dest, amt))) => Some( (src, dest, amt) ),
+        _ => None,
+    }
+}
+
+// How good a position is for one player, relative to everyone else
+pub fn rate_position( plyr_evals : [i32;4], for_player : usize, player_count : i32 ) -> i32
+{
+    let mut strength = 0;
+    for player in 0..4 {
+        if player == for_player {
+            strength += plyr_evals[player] * (player_count - 1);
+        } else {
+            strength -= plyr_evals[player];
+        }
+    }
+    strength
+}
+
+// The highest rated move for a player, without any of the bot's randomness
+pub fn best_move( gamecurr : GameSnapshot, for_player : usize, player_count : i32 ) -> Option<GameSnapshot>
+{
+    gen_valid_moves( gamecurr, for_player ).into_iter()
+        .max_by_key( |next| rate_position( evaluate_position( *next ), for_player, player_count ) )
+}
+
+// A move scored against the board as it is now, in rate_position terms where
+// a stack standing alone is worth 10000
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RankedMove {
+    pub src : i32,
+    pub dest : i32,
+    pub amount : u8,
+    pub delta : i32,
+}
+
+// Every move for a player, best first. The hint and the analysis overlay
+// both go by this so they agree on the best move and what it's worth.
+pub fn rank_moves( gamecurr : GameSnapshot, for_player : usize, player_count : i32 ) -> Vec<RankedMove>
+{
+    let base = rate_position( evaluate_position( gamecurr ), for_player, player_count );
+    let mut ranked : Vec<RankedMove> = gen_valid_moves( gamecurr, for_player ).into_iter()
+        .filter_map( |next| {
+            let (src, dest, amount) = find_split( &gamecurr, &next )?;
+            let delta = rate_position( evaluate_position( next ), for_player, player_count ) - base;
+            Some( RankedMove { src, dest, amount, delta } )
+        })
+        .collect();
+    ranked.sort_by_key( |mv| std::cmp::Reverse( mv.delta ) );
+    ranked
+}
+
+// Counts the positions `depth` moves ahead, for checking that changes to move
+// generation still find the same moves. Seats take turns in order, and a seat
+// with no moves passes, which counts as its one move.
+pub fn perft( gamecurr : GameSnapshot, players : &[usize], turn : usize, depth : u32 ) -> u64
+{
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = gen_valid_moves( gamecurr, players[ turn ] );
+    let next_turn = (turn + 1) % players.len();
+    if moves.is_empty() {
+        return perft( gamecurr, players, next_turn, depth - 1 );
+    }
+
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    moves.into_iter().map( |next| perft( next, players, next_turn, depth - 1 ) ).sum()
+}
+
+// Zobrist hashing, so a search can spot the same board reached through a
+// different order of moves. Only the stacks and whose turn it is go in, the
+// spaces themselves never change during a game. Keys are mixed from the space,
+// owner and power rather than kept in a table, any power up to 255 gets one.
+fn splitmix64( mut x : u64 ) -> u64
+{
+    x = x.wrapping_add( 0x9E3779B97F4A7C15 );
+    x = (x ^ (x >> 30)).wrapping_mul( 0xBF58476D1CE4E5B9 );
+    x = (x ^ (x >> 27)).wrapping_mul( 0x94D049BB133111EB );
+    x ^ (x >> 31)
+}
+
+// Player is 1 based like MapSpace
+#[inline]
+pub fn zobrist_key( ndx : usize, player : u8, power : u8 ) -> u64
+{
+    splitmix64( ((ndx as u64) << 16) | ((player as u64) << 8) | power as u64 )
+}
+
+// Seat is 0 based like gen_valid_moves
+#[inline]
+pub fn zobrist_turn( seat : usize ) -> u64
+{
+    splitmix64( (1 << 32) | seat as u64 )
+}
+
+impl GameSnapshot {
+    pub fn zobrist( &self, to_move : usize ) -> u64
+    {
+        let mut hash = zobrist_turn( to_move );
+        for mapsq in &self.map {
+            if mapsq.power > 0 {
+                hash ^= zobrist_key( mapsq.ndx as usize, mapsq.player, mapsq.power );
+            }
+        }
+        hash
+    }
+}
+
+// Updates a hash for a split without rehashing the board. The destination was
+// empty and the source keeps at least one summon, so only two spaces change.
+pub fn zobrist_split( hash : u64, player : u8, src : usize, src_power : u8, dest : usize, amount : u8 ) -> u64
+{
+    hash ^ zobrist_key( src, player, src_power )
+         ^ zobrist_key( src, player, src_power - amount )
+         ^ zobrist_key( dest, player, amount )
+}
+
+// Bitmask per space of which players could split onto it next move
+pub fn access_map(snap:&GameSnapshot) -> [i32;100]{
     let mut access_map : [ i32 ; 100]=[0; 100];
-    let mut eval_score:[i32;4]=[0; 4];
     for hex in &snap.map{
         if hex.power>1{
             let player=1<<(hex.player-1);
@@ -315,38 +476,88 @@ pub fn evaluate_position(snap:GameSnapshot) -> [i32;4]{
             }
         }
     }
+    access_map
+}
+
+// The numbers evaluate_position runs on. The defaults are the hand tuned ones
+// the bots have always used, the tuner binary looks for better ones and
+// writes them out per bot.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvalWeights {
+    pub stack : i32,            // every stack on the board
+    pub spare_power : i32,      // per summon that could still split off
+    pub reach_start : i32,      // the first open space in a line
+    pub open_decay : i32,       // per mille kept each space along a line nobody else can reach
+    pub contested_decay : i32,  // per mille kept each space someone else can reach
+    pub harmonic_scale : i32,   // fixed point for combining spare power with room to move
+}
+
+pub const DEFAULT_WEIGHTS : EvalWeights = EvalWeights {
+    stack: 10000,
+    spare_power: 10000,
+    reach_start: 10000,
+    open_decay: 900,
+    contested_decay: 500,
+    harmonic_scale: 1000000000,
+};
+
+impl Default for EvalWeights {
+    fn default() -> EvalWeights {
+        DEFAULT_WEIGHTS
+    }
+}
+
+pub fn evaluate_position(snap:GameSnapshot) -> [i32;4]{
+    evaluate_position_with( snap, &DEFAULT_WEIGHTS )
+}
+
+pub fn evaluate_position_with(snap:GameSnapshot, weights:&EvalWeights) -> [i32;4]{
+    //let mut result = Vec::new();
+    let access_map=access_map(&snap);
+    let mut eval_score:[i32;4]=[0; 4];
     for hex in &snap.map{
         if hex.power>0{
-            let mut weight:i32=10000;
+            let mut weight:i32=weights.stack;
             if hex.power>1{
                 let player=1<<(hex.player-1);
                 let not_player=!player;
-                let movepower:i32=((hex.power-1) as i32)*10000;
+                let movepower:i32=((hex.power-1) as i32)*weights.spare_power;
                 let mut opportunity:i32=0;
                 for mapdir in MapDirection::iterator() {
                     let mut curr_hex=hex.ndx;
-                    let mut distancefactor:i32=10000;
+                    let mut distancefactor:i32=weights.reach_start;
                     loop{
                         curr_hex = move_dir( curr_hex, mapdir);
                         if(curr_hex as usize == INVALID) || (snap.map.spaces[curr_hex as usize].contents != MapSpaceContents::Playable) || (snap.map.spaces[curr_hex as usize].power != 0){
                             break;
                         }
                         if (access_map[curr_hex as usize] & not_player)==0{
-                            distancefactor*=9;
+                            distancefactor*=weights.open_decay;
                         }
                         else{
-                            distancefactor*=5;
+                            distancefactor*=weights.contested_decay;
                         }
-                        distancefactor/=10;
+                        distancefactor/=1000;
                         opportunity+=distancefactor;
                     }
                 }
-                if opportunity>0{
-                    weight+=1000000000/((1000000000/movepower)+(1000000000/opportunity));
-                }
+                weight+=harmonic( movepower, opportunity, weights.harmonic_scale );
             }
             eval_score[(hex.player-1) as usize]+=weight;
         }
     }
     eval_score
 }
+
+// Harmonic mean style blend, so a stack needs both spare power and room to
+// spread to be worth much
+pub fn harmonic( movepower : i32, opportunity : i32, scale : i32 ) -> i32
+{
+    if movepower <= 0 || opportunity <= 0 {
+        return 0;
+    }
+    let scale = scale as i64;
+    let denom = (scale / movepower as i64) + (scale / opportunity as i64);
+    (scale / denom.max( 1 )) as i32
+}
@@ -0,0 +1,12 @@
+// The game rules without any of the bevy side, so tests and tools can use them
+pub mod gamestate;
+pub mod bitboard;
+pub mod transposition;
+pub mod mapgen;
+pub mod mapfile;
+pub mod position;
+pub mod selfplay;
+pub mod book;
+pub mod puzzle;
+pub mod weights;
+pub mod ronfile;
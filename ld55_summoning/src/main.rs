@@ -18,18 +18,64 @@ use crate::summongame::*;
 pub mod map;
 use crate::map::{ build_map, worldpos_from_mapindex };
 
-pub mod gamestate;
-use gamestate::{ gen_valid_moves, evaluate_position };
+pub use ld55_summoning::{ bitboard, book, gamestate, mapfile, mapgen, position, puzzle, ronfile, selfplay, weights };
+use bitboard::Bitboard;
+use selfplay::bot_move;
+use gamestate::{ gen_valid_moves, line_between, rank_moves, RankedMove };
 
 pub mod titlescreen;
 use titlescreen::TitleScreenPlugin;
 
-use rand::Rng;
+pub mod analysis;
+use analysis::AnalysisPlugin;
+
+pub mod controls;
+use controls::ControlsPlugin;
+
+pub mod touch;
+use touch::TouchPlugin;
+
+pub mod camera;
+use camera::{ CameraPlugin, CameraRig };
+
+pub mod audio;
+use audio::SoundPlugin;
+
+pub mod settings;
+use settings::SettingsPlugin;
+
+pub mod profiles;
+use profiles::{ BotBook, BotWeights, ProfileBook, ProfilesPlugin };
+
+pub mod colors;
+use colors::{ ring_colors, ColorSettings, ColorsPlugin };
+
+pub mod pause;
+use pause::PausePlugin;
+
+pub mod clock;
+use clock::ClockPlugin;
+
+pub mod speed;
+use speed::SpeedPlugin;
+
+pub mod puzzles;
+use puzzles::{ ActivePuzzle, PuzzlesPlugin };
+
+pub mod campaign;
+use campaign::CampaignPlugin;
+
+pub mod editor;
+use editor::EditorPlugin;
+
+pub mod mappool;
+use mappool::MapPoolPlugin;
+
 
 //use std::collections::HashSet;
 use std::{f32::consts::PI, time::Duration};
 
-use crate::gamestate::{ MapDirection, MapSpaceContents, INVALID};
+use crate::gamestate::{ GameSnapshot, MapDirection, MapSpaceContents, INVALID};
 
 
 #[derive(Component)]
@@ -41,9 +87,31 @@ struct PlayerHelp;
 #[derive(Component)]
 struct PlayerScore(i32);
 
+// Moves a ring along a path of local positions, ending on its own space
 #[derive(Component)]
 struct CircleAnimator {
-    target : Vec3,
+    ndx : i32,
+    path : Vec<Vec3>,
+    scale_from : f32,
+    scale_to : f32,
+    elapsed : f32,
+    duration : f32,
+}
+
+// Ripple where a ring lands
+#[derive(Component)]
+struct LandingPulse {
+    pos : Vec3,
+    color : Color,
+    elapsed : f32,
+}
+
+// What the player last used to drive the cursor
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum InputMode {
+    Mouse,
+    Keys,
+    Touch,
 }
 
 #[derive(Component)]
@@ -53,6 +121,14 @@ struct GameCursor {
     drag_from : Option<usize>,
     _drag_dest : Option<usize>,
     split_pct : f32,
+    key_dir : Option<MapDirection>, // direction picked with keys/gamepad instead of dragging
+    input_mode : InputMode,
+}
+
+// Number shown over stacks too big for the ring textures
+#[derive(Component)]
+struct PowerLabel {
+    ndx : usize,
 }
 
 #[derive(Component)]
@@ -60,6 +136,20 @@ struct SplitLabel {
     is_dest : bool
 }
 
+// A suggested move for the local player, shown when they ask for a hint
+struct MoveHint {
+    src : i32,
+    dest : i32,
+    dir : MapDirection,
+    amount : u8,
+    delta : f32,
+}
+
+#[derive(Component)]
+struct HintLabel {
+    hint : Option<MoveHint>,
+}
+
 #[derive(Component)]
 struct AIController {
     turn_timer: Timer,
@@ -88,26 +178,85 @@ fn main() {
         .add_plugins(
             TitleScreenPlugin
         )
+        .add_plugins(
+            AnalysisPlugin
+        )
+        .add_plugins(
+            ControlsPlugin
+        )
+        .add_plugins(
+            TouchPlugin
+        )
+        .add_plugins(
+            CameraPlugin
+        )
+        .add_plugins(
+            SoundPlugin
+        )
+        .add_plugins(
+            SettingsPlugin
+        )
+        .add_plugins(
+            ProfilesPlugin
+        )
+        .add_plugins(
+            ColorsPlugin
+        )
+        .add_plugins(
+            PausePlugin
+        )
+        .add_plugins(
+            ClockPlugin
+        )
+        .add_plugins(
+            SpeedPlugin
+        )
+        .add_plugins(
+            PuzzlesPlugin
+        )
+        .add_plugins(
+            CampaignPlugin
+        )
+        .add_plugins(
+            EditorPlugin
+        )
+        .add_plugins(
+            MapPoolPlugin
+        )
         .init_state::<GameAppState>()
+        .init_state::<PauseState>()
         .insert_resource( GoodStuff::default() )
         .insert_resource( SummonGame::default() )
+        .insert_resource( MatchSettings::default() )
+        .insert_resource( BoardAnimation::default() )
+        .insert_resource( GameSpeed::default() )
         .add_systems(Startup, setup)
 
         .add_systems( OnEnter(GameAppState::Gameplay), (
             setup_gameplay,
             build_map) )
+        .add_systems( OnExit(GameAppState::Gameplay), teardown_gameplay )
+        .add_systems( OnEnter(GameAppState::Restarting), restart_gameplay )
 
         .add_systems(Update, (
             handle_input,
+            handle_hint,
             draw_split_feedback,
-            on_gamestate_changed,
+            draw_hint,
+            // Rings have to be spawned before the animation can tell it's finished
+            (on_gamestate_changed, update_circ_anim).chain(),
             player_guidance,
-            update_circ_anim,
+            update_landing_pulse,
+            update_power_labels,
             update_ui,
-            update_ai).run_if(in_state(GameAppState::Gameplay)))
+            update_ai).run_if(playing))
 
         .add_event::<GameStateChanged>()
         .add_event::<TurnAdvance>()
+        .add_event::<MapBuilt>()
+        .add_event::<CircleLanded>()
+        .add_event::<AnimationComplete>()
+        .add_event::<GameOver>()
 
         .run();
 }
@@ -119,6 +268,7 @@ fn setup(
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut stuff: ResMut<GoodStuff>,
     mut config_store: ResMut<GizmoConfigStore>,
+    colors: Res<ColorSettings>,
     asset_server: Res<AssetServer>
 ) {
 
@@ -131,30 +281,17 @@ fn setup(
     let ring_mesh = Mesh::from( Plane3d { normal: Direction3d::Y } ).with_generated_tangents().unwrap();
     stuff.ring_mesh = meshes.add( ring_mesh );
 
-    stuff.player_stuff[0].color  = Color::rgb_u8(255, 113, 206);
-    stuff.player_stuff[0].color2 = Color::rgb_u8(161, 45, 172 );
-
-    stuff.player_stuff[1].color  = Color::rgb_u8(1, 205, 254);
-    stuff.player_stuff[1].color2 = Color::rgb_u8(1, 150, 114);
-
-    stuff.player_stuff[2].color  = Color::rgb_u8(5, 254, 161);
-    stuff.player_stuff[2].color2 = Color::rgb_u8(1, 152, 30);
+    // Player colors come from the palette, the rings get recolored if they change
+    colors.apply( &mut stuff );
 
-    stuff.player_stuff[3].color  = Color::rgb_u8(161, 39, 255);
-    stuff.player_stuff[3].color2 = Color::rgb_u8(52, 37, 174);
-
-    for i in 1..=20 {
+    for i in 1..=RING_TEX_COUNT {
         //let ring_texname = format!("ring_{:02}.png", i);
         let ring_texname = format!("tx_rings/RingGen_{:02}_BaseColor.PNG", i );
         let ring_emit_texname = format!("tx_rings/RingGen_{:02}_Emissive.PNG", i );
 
         for p in 0..4 {
 
-            let mut color_main = stuff.player_stuff[p].color * 200.0;
-            color_main.set_a(1.0);
-
-            let mut color_support = stuff.player_stuff[p].color * 1.5;
-            color_support.set_a( 1.0 );
+            let (color_support, color_main) = ring_colors( stuff.player_stuff[p].color );
 
             let ring_mtl = StandardMaterial {
                 base_color: color_support,
@@ -165,7 +302,8 @@ fn setup(
                 ..default()
             };
 
-            stuff.player_stuff[p].ring_mtl[i - 1] = materials.add(ring_mtl);
+            let ring_mtl = materials.add(ring_mtl);
+            stuff.player_stuff[p].ring_mtl.push( ring_mtl );
         }
     }
 
@@ -198,18 +336,19 @@ fn setup(
     });
 
     // camera
+    let camera_rig = CameraRig::default();
     commands.spawn( ( Camera3dBundle {
             camera: Camera {
                 hdr: true,
                 ..default()
             },
-            transform: Transform::from_xyz( 0.0, 15.0, 12.0)
-                                    .looking_at( Vec3 { x:0.0, y: 0.0, z : 3.0 }, Vec3::Y),
+            transform: camera_rig.transform(),
             tonemapping: Tonemapping::TonyMcMapface,
             ..default()
             },
             BloomSettings::NATURAL,
-            GameCamera
+            GameCamera,
+            camera_rig,
         ));
 
     // HUD
@@ -269,6 +408,7 @@ fn build_hud(
             ..default()
         }),
         SplitLabel { is_dest : true },
+        GameplayCleanup,
     ));
 
     commands.spawn((
@@ -285,6 +425,25 @@ fn build_hud(
             ..default()
         }),
         SplitLabel { is_dest : false },
+        GameplayCleanup,
+    ));
+
+    // Hint label
+    commands.spawn((
+        TextBundle::from_section("",
+            TextStyle {
+                font_size: 24.,
+                ..default()
+            },
+        )
+        .with_style( Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            left: Val::Px(12.0),
+            ..default()
+        }),
+        HintLabel { hint : None },
+        GameplayCleanup,
     ));
 
     // player score labels
@@ -307,6 +466,7 @@ fn build_hud(
                     ..default()
                 }),
                 PlayerScore( i as i32),
+                GameplayCleanup,
             ));
 
             xx += 50.0;
@@ -318,6 +478,7 @@ fn build_hud(
 fn setup_gameplay (
     asset_server: Res<AssetServer>,
     stuff: Res<GoodStuff>,
+    speed: Res<GameSpeed>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
@@ -358,22 +519,60 @@ fn setup_gameplay (
     transform: Transform::from_scale(Vec3::new(10.0, 10.0, 10.0)),
     //     Transform::from_rotation(Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2)).with_scale( Vec3::new(4.0, 4.0, 4.0) ),
     ..default()
-    }, Ground) );
+    }, Ground, GameplayCleanup) );
 
 
     // cursor with no cube
     commands.spawn((GameCursor { ndx : 0,
         drag_from : None, _drag_dest : None, cursor_world : Vec3::ZERO, split_pct : 0.5,
-        }, Transform::default() ));
+        key_dir : None, input_mode : InputMode::Mouse,
+        }, Transform::default(), GameplayCleanup ));
 
-    commands.spawn( AIController {
-        turn_timer : Timer::new(Duration::from_secs_f32( 3.0 ), TimerMode::Once),
-    });
+    commands.spawn(( AIController {
+        turn_timer : Timer::new(Duration::from_secs_f32( speed.first_turn_delay() ), TimerMode::Once),
+    }, GameplayCleanup ));
 
 
     build_hud(&mut commands, stuff);
 }
 
+// Clear out the match so the next one (or the title screen) starts clean.
+// The rings and overlays hang off the map visuals so they go with them.
+fn teardown_gameplay(
+    mut commands: Commands,
+    mut stuff: ResMut<GoodStuff>,
+    mut game: ResMut<SummonGame>,
+    mut anim: ResMut<BoardAnimation>,
+    mut helper_q: Query<&mut Text, With<PlayerHelp>>,
+    despawn_q: Query<Entity, With<GameplayCleanup>>,
+)
+{
+    println!("Gameplay teardown!");
+
+    for entity in &despawn_q {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    *game = SummonGame::default();
+    *anim = BoardAnimation::default();
+    for pinfo in stuff.player_stuff.iter_mut() {
+        pinfo.out_of_moves = false;
+    }
+
+    for mut text in &mut helper_q {
+        text.sections[0].value = "".into();
+    }
+}
+
+// Gameplay has been torn down by now, go straight back in with the same seats
+fn restart_gameplay(
+    mut game_state: ResMut<NextState<GameAppState>>,
+)
+{
+    println!("Restarting match");
+    game_state.set( GameAppState::Gameplay );
+}
+
 fn handle_input(
     camera_query: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
     ground_query: Query<&GlobalTransform, With<Ground>>,
@@ -382,11 +581,30 @@ fn handle_input(
     mouse_button_input: Res<ButtonInput<MouseButton>>,
     windows: Query<&Window>,
     stuff: Res<GoodStuff>,
+    anim: Res<BoardAnimation>,
     mut game: ResMut<SummonGame>,
     mut ev_gamestate: EventWriter<GameStateChanged>,
     mut ev_turn: EventWriter<TurnAdvance>,
+    mut ev_cursor: EventReader<CursorMoved>,
+    touches: Res<Touches>,
     mut gizmos: Gizmos,
 ) {
+    // The mouse takes over from the keyboard/gamepad as soon as it's used, but
+    // ignore it while fingers are down in case the browser emulates one
+    let mouse_used = ev_cursor.read().count() > 0 || mouse_button_input.get_just_pressed().len() > 0;
+    {
+        let (_, mut cursor_info) = cursor_q.single_mut();
+        if mouse_used && cursor_info.input_mode != InputMode::Mouse && touches.iter().next().is_none() {
+            cursor_info.input_mode = InputMode::Mouse;
+            cursor_info.drag_from = None;
+            cursor_info.key_dir = None;
+        }
+
+        if cursor_info.input_mode != InputMode::Mouse {
+            return;
+        }
+    }
+
     let (camera, camera_transform) = camera_query.single();
     let ground = ground_query.single();
 
@@ -394,17 +612,9 @@ fn handle_input(
         return;
     };
 
-    // Calculate a ray pointing from the camera into the world based on the cursor's position.
-    let Some(ray) = camera.viewport_to_world(camera_transform, cursor_position) else {
-        return;
-    };
-
-    // Calculate if and where the ray is hitting the ground plane.
-    let Some(distance) = ray.intersect_plane(ground.translation(), Plane3d::new(ground.up()))
-    else {
+    let Some(point) = screen_to_ground( camera, camera_transform, ground, cursor_position ) else {
         return;
     };
-    let point = ray.get_point(distance);
 
     // Draw a circle just above the ground plane at that position.
     gizmos.circle(
@@ -448,14 +658,12 @@ fn handle_input(
 
             let drag_from_ndx = cursor_info.drag_from.unwrap() as i32;
             let drag_from_pos = worldpos_from_mapindex(drag_from_ndx as i32);
-            let d = cursor_info.cursor_world.distance( drag_from_pos );
-            let dnorm = ((d - 1.0).max(0.0) / 3.0).min( 1.0);
-
-            //println!("Dist is {}, {}", d, dnorm );
-            cursor_info.split_pct = dnorm;
+            cursor_info.split_pct = split_pct_from_drag( cursor_info.cursor_world, drag_from_pos );
         }
 
-        if mouse_button_input.just_pressed(MouseButton::Left) {
+        // Wait for the last move to finish animating before picking up another,
+        // so a drag is never let go while it can't be played
+        if mouse_button_input.just_pressed(MouseButton::Left) && !anim.busy {
 
             // Make sure there is some power to drag from
             if (ndx != INVALID) && (game.snapshot.map.spaces[ ndx ].power > 1 ) &&
@@ -465,7 +673,7 @@ fn handle_input(
             }
         }
 
-        if mouse_button_input.just_released(MouseButton::Left) {
+        if mouse_button_input.just_released(MouseButton::Left) && !anim.busy {
 
             if cursor_info.drag_from.is_some() {
 
@@ -473,50 +681,7 @@ fn handle_input(
                 let drag_from_pos = worldpos_from_mapindex(drag_from_ndx as i32);
 
                 let mapdir = mapdir_from_drag( cursor_info.cursor_world, drag_from_pos );
-                let found = game.snapshot.map.search_dir( drag_from_ndx,  mapdir );
-                if (found != drag_from_ndx) && (found != gamestate::INVALID as i32)
-                {
-                    let found_ndx = found as usize;
-                    if game.snapshot.map.spaces[ found_ndx ].player == 0 {
-
-                        let src_pow = game.snapshot.map.spaces[ drag_from_ndx as usize ].power as i32;
-                        let split_count = calc_split(cursor_info.split_pct, src_pow);
-                        if split_count > 0 {
-                            game.snapshot.map.spaces[ found_ndx ].player = (active_player + 1) as u8;
-                            game.snapshot.map.spaces[ found_ndx ].power = split_count as u8;
-                            //ev_gamestate.send( GameStateChanged::CircleAdded( found_ndx as i32) );
-                            ev_gamestate.send( GameStateChanged::CircleSplit( drag_from_ndx, found_ndx as i32) );
-
-                            game.snapshot.map.spaces[ drag_from_ndx as usize].power -= split_count as u8;
-                            ev_gamestate.send( GameStateChanged::CircleAdded( drag_from_ndx) );
-
-
-                            // Advance to the next player's turn
-                            let mut pnum = game.player_turn;
-                            loop {
-                                pnum = pnum + 1;
-                                if pnum >= stuff.player_stuff.len() as i32 {
-                                    pnum = 0;
-                                }
-
-                                if stuff.player_stuff[pnum as usize].ptype != PlayerType::NotActive {
-                                    break;
-                                }
-
-                                if pnum == game.player_turn {
-                                    println!("Didn't find any active players?");
-                                    break;
-                                }
-                            }
-                            game.player_turn = pnum;
-                            game.turn_num += 1;
-
-                            game.snapshot.update_scores();
-
-                            ev_turn.send( TurnAdvance(pnum) );
-                        }
-                    }
-                }
+                try_split( &mut game, &stuff, drag_from_ndx, mapdir, cursor_info.split_pct, &mut ev_gamestate, &mut ev_turn );
             }
         }
 
@@ -529,6 +694,135 @@ fn handle_input(
     }
 }
 
+// Where a screen position lands on the ground plane
+fn screen_to_ground( camera : &Camera, camera_transform : &GlobalTransform, ground : &GlobalTransform, screen_pos : Vec2 ) -> Option<Vec3>
+{
+    // Calculate a ray pointing from the camera into the world based on the cursor's position.
+    let ray = camera.viewport_to_world(camera_transform, screen_pos)?;
+
+    // Calculate if and where the ray is hitting the ground plane.
+    let distance = ray.intersect_plane(ground.translation(), Plane3d::new(ground.up()))?;
+    Some( ray.get_point(distance) )
+}
+
+// Dragging further away splits off more
+fn split_pct_from_drag( pos : Vec3, start_pos : Vec3 ) -> f32
+{
+    let d = pos.distance( start_pos );
+    ((d - 1.0).max(0.0) / 3.0).min( 1.0)
+}
+
+// Split part of the stack at src_ndx off in mapdir and pass the turn on,
+// returns false if that isn't a legal move
+fn try_split(
+    game : &mut SummonGame,
+    stuff : &GoodStuff,
+    src_ndx : i32,
+    mapdir : MapDirection,
+    split_pct : f32,
+    ev_gamestate: &mut EventWriter<GameStateChanged>,
+    ev_turn: &mut EventWriter<TurnAdvance>,
+) -> bool
+{
+    // A finished puzzle leaves the board up but nothing more can be played
+    if game.game_over {
+        return false;
+    }
+
+    // Mouse, keys, pad and touch all end up here, so this is where a stack
+    // that isn't the mover's gets turned away
+    let active_player = game.player_turn;
+    if game.snapshot.map.spaces[ src_ndx as usize ].player as i32 != active_player + 1 {
+        return false;
+    }
+
+    let found = game.snapshot.map.search_dir( src_ndx,  mapdir );
+    if (found == src_ndx) || (found == gamestate::INVALID as i32) {
+        return false;
+    }
+
+    let found_ndx = found as usize;
+    if game.snapshot.map.spaces[ found_ndx ].player != 0 {
+        return false;
+    }
+
+    let src_pow = game.snapshot.map.spaces[ src_ndx as usize ].power as i32;
+    let split_count = calc_split(split_pct, src_pow);
+    if split_count <= 0 {
+        return false;
+    }
+
+    game.snapshot.map.spaces[ found_ndx ].player = (active_player + 1) as u8;
+    game.snapshot.map.spaces[ found_ndx ].power = split_count as u8;
+    //ev_gamestate.send( GameStateChanged::CircleAdded( found_ndx as i32) );
+    ev_gamestate.send( GameStateChanged::CircleSplit( src_ndx, found_ndx as i32) );
+
+    game.snapshot.map.spaces[ src_ndx as usize].power -= split_count as u8;
+    ev_gamestate.send( GameStateChanged::CircleAdded( src_ndx) );
+
+    let pnum = advance_turn( game, stuff );
+    ev_turn.send( TurnAdvance(pnum) );
+
+    true
+}
+
+// Play out a whole-board move (from the bot or a timeout), sending the
+// changes so the rings animate. Doesn't advance the turn.
+fn commit_move( game : &mut SummonGame, next : GameSnapshot, ev_gamestate: &mut EventWriter<GameStateChanged> )
+{
+    let old = game.snapshot;
+    game.snapshot = next;
+
+    // check for splits by looking for decrease
+    let mut split_ndx = None;
+    for mapsq in &game.snapshot.map {
+        let oldsq = old.map.spaces[ mapsq.ndx as usize ];
+        if oldsq.power > mapsq.power {
+            split_ndx = Some( mapsq.ndx );
+            break;
+        }
+    }
+
+    // Send any adds
+    for mapsq in &game.snapshot.map {
+        let oldsq = old.map.spaces[ mapsq.ndx as usize ];
+        if oldsq.power != mapsq.power {
+            if split_ndx.is_none() || oldsq.power > mapsq.power {
+                ev_gamestate.send( GameStateChanged::CircleAdded( mapsq.ndx ) );
+            } else {
+                ev_gamestate.send( GameStateChanged::CircleSplit( split_ndx.unwrap(), mapsq.ndx ) );
+            }
+        }
+    }
+}
+
+// Advance to the next active player's turn
+fn advance_turn( game : &mut SummonGame, stuff : &GoodStuff ) -> i32
+{
+    let mut pnum = game.player_turn;
+    loop {
+        pnum = pnum + 1;
+        if pnum >= stuff.player_stuff.len() as i32 {
+            pnum = 0;
+        }
+
+        if stuff.player_stuff[pnum as usize].ptype != PlayerType::NotActive && !game.conceded[pnum as usize] {
+            break;
+        }
+
+        if pnum == game.player_turn {
+            println!("Didn't find any active players?");
+            break;
+        }
+    }
+    game.player_turn = pnum;
+    game.turn_num += 1;
+
+    game.snapshot.update_scores();
+
+    pnum
+}
+
 fn draw_map_dir( gizmos: &mut Gizmos, game : &SummonGame, ndx : i32, dir : MapDirection, color : Color, verbose : bool ) -> Vec3
 {
     let found = game.snapshot.map.search_dir( ndx,  dir );
@@ -597,7 +891,7 @@ fn draw_split_feedback(
         gizmos.arrow( drag_from_pos + offs, cursor_info.cursor_world + offs, Color::YELLOW );
 
         // cursor_info.cursor_world - drag_from_pos;
-        let mapdir = mapdir_from_drag( cursor_info.cursor_world, drag_from_pos );
+        let mapdir = cursor_info.key_dir.unwrap_or_else( || mapdir_from_drag( cursor_info.cursor_world, drag_from_pos ) );
         let dst_pos = draw_map_dir( &mut gizmos, &game, drag_from_ndx as i32, mapdir, player_col, false);
 
         let src_pow = game.snapshot.map.spaces[ drag_from_ndx ].power as i32;
@@ -656,12 +950,100 @@ fn draw_split_feedback(
 
 }
 
+fn handle_hint(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    cursor_q: Query<&GameCursor>,
+    stuff: Res<GoodStuff>,
+    game: Res<SummonGame>,
+    mut hint_q: Query<&mut HintLabel>,
+    mut ev_turn: EventReader<TurnAdvance>,
+)
+{
+    let mut hint_label = hint_q.single_mut();
+
+    // Any hint is stale once the turn moves on
+    if ev_turn.read().count() > 0 {
+        hint_label.hint = None;
+    }
+
+    if !keyboard_input.just_pressed( KeyCode::KeyH ) {
+        return;
+    }
+
+    let pnum = game.player_turn as usize;
+    if stuff.player_stuff[pnum].ptype != PlayerType::Local || cursor_q.single().drag_from.is_some() {
+        return;
+    }
+
+    // Same ranking as the analysis overlay's top move
+    let Some(&RankedMove { src, dest, amount, delta }) = rank_moves( game.snapshot, pnum, game.player_count ).first() else {
+        return;
+    };
+
+    // Express the eval change in cells, a stack standing alone is worth 10000
+    let delta = delta as f32 / 10000.0;
+
+    let dir = game.snapshot.map.dir_to( src, dest ).unwrap_or( MapDirection::North );
+    println!("Hint for player {}: split {} from {} {:?} to {} ({:+.2})", pnum + 1, amount, src, dir, dest, delta );
+
+    hint_label.hint = Some( MoveHint { src, dest, dir, amount, delta } );
+}
+
+fn draw_hint(
+    camera_q: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+    mut hint_q: Query<(&HintLabel, &mut Style, &mut Text, &mut Visibility)>,
+    stuff: Res<GoodStuff>,
+    game: Res<SummonGame>,
+    mut gizmos: Gizmos,
+)
+{
+    let (hint_label, mut style, mut label, mut vis) = hint_q.single_mut();
+
+    let Some(hint) = &hint_label.hint else {
+        *vis = Visibility::Hidden;
+        return;
+    };
+
+    // Same look as a drag in progress, so the player can copy it
+    let offs = Vec3 { x : 0.0, y : 0.15, z : 0.0 };
+    let player_col = stuff.player_stuff[ game.player_turn as usize].color;
+    let src_pos = worldpos_from_mapindex( hint.src );
+    gizmos.circle( src_pos + offs, Direction3d::Y, 0.6, Color::YELLOW );
+    draw_map_dir( &mut gizmos, &game, hint.src, hint.dir, player_col, false );
+    let dst_pos = worldpos_from_mapindex( hint.dest );
+    gizmos.arrow( src_pos + offs, dst_pos + offs, Color::YELLOW );
+
+    // Show the split amount and how much it helps
+    label.sections[0].value = format!("{} ({:+.2})", hint.amount, hint.delta );
+    label.sections[0].style.color = player_col;
+
+    let (camera, camera_global_transform) = camera_q.single();
+    if let Some(viewport_position) = camera.world_to_viewport(camera_global_transform, dst_pos) {
+        style.top = Val::Px(viewport_position.y - 30.0);
+        style.left = Val::Px(viewport_position.x);
+        *vis = Visibility::Visible;
+    }
+}
+
 fn calc_split( split_pct : f32, src_pow: i32) -> i32 {
     let split_count = split_pct * ((src_pow - 1) as f32);
     let split_count = split_count as i32;
     split_count
 }
 
+// Nudge the split by one, for controls that don't drag
+fn step_split( split_pct : f32, src_pow : i32, step : i32 ) -> f32 {
+    // Nothing to step through on a stack that can't split
+    if src_pow < 2 {
+        return split_pct;
+    }
+
+    let split_count = (calc_split( split_pct, src_pow ) + step).clamp( 1, src_pow - 1 );
+
+    // Aim for the middle of the range so calc_split rounds back to split_count
+    ((split_count as f32 + 0.5) / (src_pow - 1) as f32).min( 1.0 )
+}
+
 
 
 // fn spawn_mapspace_empty( mut commands: Commands ) -> Entity {
@@ -677,16 +1059,51 @@ fn calc_split( split_pct : f32, src_pow: i32) -> i32 {
 fn player_guidance(
     //mut commands: Commands,
     mut stuff: ResMut<GoodStuff>,
-    game: Res<SummonGame>,
+    mut game: ResMut<SummonGame>,
+    book: Res<ProfileBook>,
     //mut helper_q: Query<(&mut Text, &mut Style), With<PlayerHelp>>,
     mut helper_q: Query<&mut Text, With<PlayerHelp>>,
     mut score_q: Query<(&mut Text, &PlayerScore), Without<PlayerHelp>>,
-    mut ev_turn: EventReader<TurnAdvance>, )
+    mut ev_turn: EventReader<TurnAdvance>,
+    mut ev_gameover: EventWriter<GameOver>, )
 {
     for ev in ev_turn.read() {
 
         let mut text = helper_q.single_mut();
+
+        // The game ends when nobody can move, or everyone else has conceded
+        let remaining : Vec<usize> = (0..stuff.player_stuff.len())
+            .filter( |p| stuff.player_stuff[*p].ptype != PlayerType::NotActive && !game.conceded[*p] )
+            .collect();
+        let anyone_can_move = remaining.iter().any( |p| !gen_valid_moves( game.snapshot, *p ).is_empty() );
+        let conceded_out = game.player_count > 1 && remaining.len() < 2;
+
+        if (!anyone_can_move || conceded_out) && !game.game_over {
+            game.game_over = true;
+            game.snapshot.update_scores();
+
+            let leaders = game.leaders();
+            text.sections[0].value = match leaders.len() {
+                1 => format!("Game over! {} wins.", book.seat_name( &stuff.player_stuff[ leaders[0] ] ) ),
+                _ => "Game over! It's a tie.".into(),
+            };
+            text.sections[0].style.color = leaders.first().map_or( Color::WHITE, |p| stuff.player_stuff[*p].color );
+            ev_gameover.send( GameOver );
+        }
+
+        if game.player_count > 0 {
+            // Update score displays
+            for (mut text, score) in &mut score_q {
+                text.sections[0].value = format!( "{:02}", game.snapshot.score[ score.0 as usize ]);
+
+            }
+        }
+
         let pinfo = &mut stuff.player_stuff[ev.0 as usize];
+        if game.game_over {
+            continue;
+        }
+
         //text.style.color = pinfo.color;
         text.sections[0].style.color = pinfo.color;
 
@@ -695,39 +1112,39 @@ fn player_guidance(
             pinfo.out_of_moves = true;
 
             text.sections[0].value = if pinfo.ptype == PlayerType::Local {
-                format!("Player {} has no moves and must pass.", ev.0 + 1 )
+                format!("{} has no moves and must pass.", book.seat_name( pinfo ) )
             } else {
                 "Computer Player is out of moves and must pass".into()
             }
 
         } else {
             text.sections[0].value = if pinfo.ptype == PlayerType::Local {
-                format!("Player {}'s turn.", ev.0 + 1 )
+                format!("{}'s turn. Press H for a hint, Tab for analysis.", book.seat_name( pinfo ) )
             } else {
                 "Waiting for Computer Player".into()
             }
         }
 
-        if game.player_count > 0 {
-            // Update score displays
-            for (mut text, score) in &mut score_q {
-                text.sections[0].value = format!( "{:02}", game.snapshot.score[ score.0 as usize ]);
-
-            }
-        }
-
     }
 }
 
 
+fn ring_size( power : u8 ) -> f32 {
+    if power == 1 { 0.9 } else { 1.25 }
+}
+
 fn on_gamestate_changed(
     mut commands: Commands,
     stuff: Res<GoodStuff>,
     gamestate: Res<SummonGame>,
+    mut anim: ResMut<BoardAnimation>,
     mut q_mapvis : Query<&mut MapSpaceVisual>,
     mut ev_gamestate: EventReader<GameStateChanged>, )
 {
-    for ev in ev_gamestate.read() {
+    // Look at the whole batch, a split sends events for both ends in either order
+    let events : Vec<&GameStateChanged> = ev_gamestate.read().collect();
+
+    for ev in &events {
 
         let mut split_from_ndx : Option<usize> = None;
         let spawn_ndx;
@@ -763,39 +1180,107 @@ fn on_gamestate_changed(
             }
 
             //commands.entity(ent_vis).
-            let ring_sz = if spc.power == 1 { 0.9 } else { 1.25 };
-
+            let ring_sz = ring_size( spc.power );
             let targ_pos = Vec3 { x: 0.0, y : 0.2, z : 0.0 };
-            let mut spawn_pos = targ_pos;
-            if split_from_ndx.is_some() {
-                let split_from_ndx = split_from_ndx.unwrap();
-                let start_pos = worldpos_from_mapindex( split_from_ndx as i32 );
-                let targ_pos_w = worldpos_from_mapindex( spawn_ndx as i32 );
-
-                spawn_pos = (start_pos - targ_pos_w) + targ_pos;
-                //println!( "Spawn Pos is {:?}", spawn_pos );
-            }
+            let targ_pos_w = worldpos_from_mapindex( spawn_ndx as i32 );
+
+            let mut animator = CircleAnimator {
+                ndx : spawn_ndx as i32,
+                path : vec![ targ_pos ],
+                scale_from : ring_sz * 0.5,
+                scale_to : ring_sz,
+                elapsed : 0.0,
+                duration : 0.25,
+            };
 
+            if let Some(split_from_ndx) = split_from_ndx {
+                // Slide hex by hex along the line the split travelled
+                animator.path = line_between( split_from_ndx as i32, spawn_ndx as i32 ).iter()
+                    .map( |ndx| (worldpos_from_mapindex( *ndx ) - targ_pos_w) + targ_pos )
+                    .collect();
+                animator.scale_from = ring_sz;
+                animator.duration = (animator.path.len() - 1) as f32 * 0.12 + 0.1;
+            } else {
+                // If this stack just gave some power away, shrink it down from its old size
+                for other in &events {
+                    if let GameStateChanged::CircleSplit( src, dest ) = other {
+                        if *src as usize == spawn_ndx {
+                            let old_power = spc.power + gamestate.snapshot.map.spaces[ *dest as usize ].power;
+                            animator.scale_from = ring_size( old_power ) * 1.3;
+                        }
+                    }
+                }
+            }
 
-            let mtl = stuff.player_stuff[spc.player as usize - 1].ring_mtl[ (spc.power as usize) - 1 ].clone();
+            let mtl = stuff.player_stuff[spc.player as usize - 1].ring_mtl_for( spc.power );
             let ent_ring =
 
             commands.spawn((PbrBundle {
                 mesh: stuff.ring_mesh.clone(),
                 material: mtl,
                 transform: Transform {
-                    translation : spawn_pos,
-                    scale: Vec3::splat( ring_sz ),
+                    translation : animator.path[0],
+                    scale: Vec3::splat( animator.scale_from ),
                     ..default()
                 },
                 ..default()
-            }, NotShadowCaster, CircleAnimator { target : targ_pos }) ).id();
+            }, NotShadowCaster, animator) ).id();
 
 
             let mut vis = q_mapvis.get_mut( gamestate.map_visuals[spawn_ndx] ).unwrap();
             vis.circle = Some(ent_ring);
 
             commands.entity(ent_vis).add_child(ent_ring);
+            anim.busy = true;
+        }
+    }
+}
+
+fn update_power_labels(
+    mut commands: Commands,
+    stuff: Res<GoodStuff>,
+    game: Res<SummonGame>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+    mut label_q: Query<(Entity, &PowerLabel, &mut Style, &mut Text)>,
+)
+{
+    let (camera, camera_global_transform) = camera_q.single();
+    let mut labeled = [false; 100];
+
+    for (ent, lbl, mut style, mut text) in &mut label_q {
+        let spc = game.snapshot.map.spaces[ lbl.ndx ];
+        if (spc.power as usize) <= RING_TEX_COUNT || labeled[ lbl.ndx ] {
+            commands.entity( ent ).despawn();
+            continue;
+        }
+        labeled[ lbl.ndx ] = true;
+
+        text.sections[0].value = format!("{}", spc.power );
+        text.sections[0].style.color = stuff.player_stuff[ spc.player as usize - 1 ].color;
+
+        if let Some(viewport_position) = camera.world_to_viewport( camera_global_transform, worldpos_from_mapindex( lbl.ndx as i32 ) ) {
+            style.top = Val::Px( viewport_position.y - 12.0 );
+            style.left = Val::Px( viewport_position.x - 12.0 );
+        }
+    }
+
+    // New labels get placed next frame
+    for spc in &game.snapshot.map {
+        if (spc.power as usize) > RING_TEX_COUNT && !labeled[ spc.ndx as usize ] {
+            commands.spawn((
+                TextBundle::from_section("",
+                    TextStyle {
+                        font_size: 24.,
+                        ..default()
+                    },
+                )
+                .with_style( Style {
+                    position_type: PositionType::Absolute,
+                    ..default()
+                }),
+                PowerLabel { ndx : spc.ndx as usize },
+                GameplayCleanup,
+            ));
         }
     }
 }
@@ -803,75 +1288,71 @@ fn on_gamestate_changed(
 fn update_ai(
     //mut commands: Commands,
     time: Res<Time>,
+    speed: Res<GameSpeed>,
     stuff: Res<GoodStuff>,
+    bot_weights: Res<BotWeights>,
+    bot_book: Res<BotBook>,
+    mut puzzle: ResMut<ActivePuzzle>,
+    anim: Res<BoardAnimation>,
     mut q_ai : Query<&mut AIController>,
     mut ev_turn: EventWriter<TurnAdvance>,
     mut ev_gamestate: EventWriter<GameStateChanged>,
+    mut ev_anim_done: EventReader<AnimationComplete>,
     mut game: ResMut<SummonGame>,
 ) {
+    if game.game_over {
+        return;
+    }
+
     let pinfo = &stuff.player_stuff[game.player_turn as usize];
     let mut should_advance_turn = false;
     let mut ai = q_ai.single_mut();
+
+    // Don't start thinking until the last move has played out
+    if ev_anim_done.read().count() > 0 {
+        ai.turn_timer.reset();
+    }
+    if anim.busy {
+        return;
+    }
+
+    // Bot-only games in instant mode don't wait at all
+    let instant = speed.instant( &stuff );
+    let delta = time.delta().mul_f32( speed.time_scale() );
+
     if pinfo.ptype == PlayerType::Local && pinfo.out_of_moves {
-        ai.turn_timer.tick( time.delta());
+        ai.turn_timer.tick( delta );
         if ai.turn_timer.finished() {
             should_advance_turn = true;
         }
     } else if pinfo.ptype == PlayerType::AI {
-            ai.turn_timer.tick( time.delta());
-            if ai.turn_timer.finished() {
-                // Take AI Turn
-                let moves = gen_valid_moves( game.snapshot, game.player_turn as usize);
-                if moves.is_empty() {
-                    println!("AI has no valid moves and will pass.");
+            ai.turn_timer.tick( delta );
+            if ai.turn_timer.finished() || instant {
+                // Take AI Turn, from the opening book if it's a hard bot and
+                // the book knows this board, otherwise with this bot's tuned
+                // weights if it has any
+                let pnum = game.player_turn as usize;
+                let board = Bitboard::from_snapshot( &game.snapshot );
+                let weights = bot_weights.for_seat( pinfo );
+                let mut rng = rand::thread_rng();
+                let chosen = if let Some(active) = &puzzle.puzzle {
+                    // Puzzles play their script, or the same bot every time
+                    let mv = active.opponent_move( &board, pnum, game.player_count, puzzle.replies );
+                    puzzle.replies += mv.is_some() as usize;
+                    mv
                 } else {
-                    println!("AI has {} valid moves", moves.len() );
-                    // Choose a move a random
-                    let mut rng = rand::thread_rng();
-
-                    let old = game.snapshot;
-                    game.snapshot = moves[ rng.gen_range( 0..moves.len())];
-
-                    let mut curr_strength:i32=-1000000000;
-                    for curr_move in moves{
-                        let plyr_evals=evaluate_position(curr_move);
-                        let mut new_str:i32=rng.gen_range( 0..1000);
-                        for player in 0..4{
-                            if player==game.player_turn{
-                                new_str+=plyr_evals[player as usize]*(game.player_count-1);
-                            }
-                            else{
-                                new_str-=plyr_evals[player as usize];
-                            }
-                        }
-                        if new_str > curr_strength {
-                            curr_strength=new_str;
-                            game.snapshot=curr_move;
-                        }
+                    // The book is the strongest play, easier bots make their own way
+                    let book_move = if pinfo.bot_difficulty == BotDifficulty::Hard { bot_book.0.lookup( &board, pnum ) } else { None };
+                    if book_move.is_some() {
+                        println!("AI plays from the book.");
                     }
-
-                    // check for splits by looking for decrease
-                    let mut split_ndx = None;
-                    for mapsq in &game.snapshot.map {
-                        let oldsq = old.map.spaces[ mapsq.ndx as usize ];
-                        if oldsq.power > mapsq.power {
-                            split_ndx = Some( mapsq.ndx );
-                            break;
-                        }
-                    }
-
-                    // Send any adds
-                    for mapsq in &game.snapshot.map {
-                        let oldsq = old.map.spaces[ mapsq.ndx as usize ];
-                        if oldsq.power != mapsq.power {
-                            if split_ndx.is_none() || oldsq.power > mapsq.power {
-                                ev_gamestate.send( GameStateChanged::CircleAdded( mapsq.ndx ) );
-                            } else {
-                                ev_gamestate.send( GameStateChanged::CircleSplit( split_ndx.unwrap(), mapsq.ndx ) );
-                            }
-
-
-                        }
+                    book_move.or_else( || bot_move( &mut rng, &board, pnum, game.player_count, &weights, pinfo.bot_difficulty.noise() ) )
+                };
+                match chosen {
+                    None => println!("AI has no valid moves and will pass."),
+                    Some(mv) => {
+                        println!("AI splits {} from {} to {}", mv.amount, mv.src, mv.dest );
+                        commit_move( &mut game, board.apply( mv, pnum ).to_snapshot(), &mut ev_gamestate );
                     }
                 }
 
@@ -882,31 +1363,9 @@ fn update_ai(
     if should_advance_turn {
         // Reset turn timer
         ai.turn_timer.reset();
-        ai.turn_timer.set_duration( Duration::from_secs_f32( 1.0 ) );
-
-        // Advance to the next player's turn
-        // todo Wrap this logic up
-        let mut pnum = game.player_turn;
-        loop {
-            pnum = pnum + 1;
-            if pnum >= stuff.player_stuff.len() as i32 {
-                pnum = 0;
-            }
-
-            if stuff.player_stuff[pnum as usize].ptype != PlayerType::NotActive {
-                break;
-            }
-
-            if pnum == game.player_turn {
-                println!("Didn't find any active players?");
-                break;
-            }
-        }
-        game.player_turn = pnum;
-        game.turn_num += 1;
-
-        game.snapshot.update_scores();
+        ai.turn_timer.set_duration( Duration::from_secs_f32( speed.ai_delay ) );
 
+        let pnum = advance_turn( &mut game, &stuff );
         ev_turn.send( TurnAdvance(pnum) );
     }
 
@@ -926,13 +1385,86 @@ fn update_ui(
     }
 }
 
-fn update_circ_anim( _time: Res<Time>,
-    mut circ_q : Query<(&mut Transform, &CircleAnimator)> )
+fn update_circ_anim(
+    mut commands: Commands,
+    time: Res<Time>,
+    speed: Res<GameSpeed>,
+    stuff: Res<GoodStuff>,
+    game: Res<SummonGame>,
+    mut anim: ResMut<BoardAnimation>,
+    mut circ_q : Query<(Entity, &mut Transform, &mut CircleAnimator)>,
+    mut ev_landed: EventWriter<CircleLanded>,
+    mut ev_done: EventWriter<AnimationComplete>,
+)
+{
+    let mut running = 0;
+    let instant = speed.instant( &stuff );
+    for (ent, mut xform, mut ca ) in &mut circ_q {
+
+        ca.elapsed += time.delta_seconds() * speed.time_scale();
+        if instant {
+            ca.elapsed = ca.duration;
+        }
+        let t = (ca.elapsed / ca.duration).min( 1.0 );
+        let t_ease = t * t * (3.0 - 2.0 * t);
+
+        // Find which leg of the path we're on
+        let legs = ca.path.len() - 1;
+        xform.translation = if legs == 0 {
+            ca.path[0]
+        } else {
+            let leg_t = t_ease * legs as f32;
+            let leg = (leg_t.floor() as usize).min( legs - 1 );
+            ca.path[ leg ].lerp( ca.path[ leg + 1 ], leg_t - leg as f32 )
+        };
+        xform.scale = Vec3::splat( ca.scale_from + (ca.scale_to - ca.scale_from) * t_ease );
+
+        if t < 1.0 {
+            running += 1;
+            continue;
+        }
+
+        // Arrived
+        commands.entity( ent ).remove::<CircleAnimator>();
+        if legs > 0 {
+            ev_landed.send( CircleLanded( ca.ndx ) );
+
+            let spc = game.snapshot.map.spaces[ ca.ndx as usize ];
+            if spc.player > 0 {
+                commands.spawn(( LandingPulse {
+                    pos : worldpos_from_mapindex( ca.ndx ) + Vec3::Y * 0.2,
+                    color : stuff.player_stuff[ spc.player as usize - 1 ].color,
+                    elapsed : 0.0,
+                }, GameplayCleanup ));
+            }
+        }
+    }
+
+    if anim.busy && running == 0 {
+        anim.busy = false;
+        ev_done.send( AnimationComplete );
+    }
+}
+
+fn update_landing_pulse(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut pulse_q : Query<(Entity, &mut LandingPulse)>,
+    mut gizmos: Gizmos,
+)
 {
-    //println!("update_circle_anim");
-    for (mut xform, ca ) in &mut circ_q {
+    let pulse_time = 0.4;
+    for (ent, mut pulse) in &mut pulse_q {
+        pulse.elapsed += time.delta_seconds();
+        if pulse.elapsed >= pulse_time {
+            commands.entity( ent ).despawn();
+            continue;
+        }
 
-        xform.translation = Vec3::lerp( xform.translation, ca.target, 0.1 );
-        //println!("xlate {:?} targ {:?}", xform.translation, ca.target );
+        let t = pulse.elapsed / pulse_time;
+        let mut color = pulse.color;
+        color.set_a( 1.0 - t );
+        gizmos.circle( pulse.pos, Direction3d::Y, 0.6 + t * 0.8, color );
+        gizmos.circle( pulse.pos, Direction3d::Y, 0.4 + t * 0.5, color );
     }
 }
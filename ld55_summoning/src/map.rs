@@ -1,37 +1,58 @@
 use bevy::prelude::*;
 use crate::summongame::*;
 use crate::gamestate::*;
+use crate::mapgen::{ generate_map_with, hex_offset, MapGenOptions };
+use crate::mapfile::MapChoice;
+use crate::puzzles::ActivePuzzle;
+use crate::campaign::{ ActiveCampaign, STAGES };
+
+// The saved board picked on the title screen, if there is one that fits
+// these seats. A pool picks a different map from last time when it can.
+fn pick_board( choice : &MapChoice, campaign : bool, active : [bool; 4], last_pool_map : &mut Option<String> ) -> Option<GameSnapshot>
+{
+    let players = active.iter().filter( |a| **a ).count();
+    let map = match choice {
+        _ if campaign => return None,
+        MapChoice::Generated => return None,
+        MapChoice::Saved( map ) => map,
+        MapChoice::Pool( maps ) => {
+            let map = MapChoice::pick( maps, &mut rand::thread_rng(), players, last_pool_map.as_deref() )?;
+            *last_pool_map = Some( map.name.clone() );
+            map
+        }
+    };
 
-use rand::Rng;
-use rand::prelude::SliceRandom;
+    println!("Playing on map {}", map.name );
+    map.snapshot_for( active ).map_err( |err| println!("Can't use map {}: {}", map.name, err ) ).ok()
+}
 
 pub fn build_map (
     asset_server: Res<AssetServer>,
     stuff: Res<GoodStuff>,
+    settings: Res<MatchSettings>,
+    puzzle: Res<ActivePuzzle>,
+    campaign: Res<ActiveCampaign>,
+    mut last_pool_map: Local<Option<String>>,
     mut commands: Commands,
     mut gamestate: ResMut<SummonGame>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut ev_gamestate: EventWriter<GameStateChanged>,
     mut ev_turn: EventWriter<TurnAdvance>,
+    mut ev_built: EventWriter<MapBuilt>,
 )
 {
 
-    /*
-    // wait to start this here because of browser audio stuff... need to find a better way
-    // MUUUUSSSIICC
-    commands.spawn(AudioBundle {
-        source: asset_server.load("SummoningStuff_OGG.ogg"),
-        settings: PlaybackSettings::LOOP,
-        ..default()
-    });
-    */
+    // Music is started by the SoundPlugin once the player has interacted,
+    // because of browser autoplay rules
 
 
     // Count number of active players to get target size for map
+    let mut active = [ false; 4 ];
     let mut player_count = 0;
     for i in 0..stuff.player_stuff.len() {
         if stuff.player_stuff[i].ptype != PlayerType::NotActive {
+            active[i] = true;
             player_count += 1;
         }
     }
@@ -39,73 +60,33 @@ pub fn build_map (
     // Remember this
     gamestate.player_count = player_count;
 
-
-    // First, set up the map indices and build the map
-    let mut rng = rand::thread_rng();
-    let mut index = 0;
-    let mut space_count = 0;
-    for map_space in &mut gamestate.snapshot.map {
-        map_space.ndx = index;
-        index = index + 1;
-
-        let hex_pos = worldpos_from_mapindex( map_space.ndx );
-
-        // this trims the board and makes it more rounder
-        if hex_pos.length() < 8.0 {
-
-            // todo: replace this with adding some obstacles with preset shapes
-            if rng.gen_ratio(1, 8) {
-                map_space.contents = MapSpaceContents::Blocked;
-            } else {
-                map_space.contents = MapSpaceContents::Playable;
-                space_count += 1;
-            }
+    // The board itself comes from mapgen, so tools can build the same maps,
+    // unless this is a puzzle with its own position or there's a saved map.
+    // Campaign stages have their own generator settings.
+    match puzzle.puzzle.as_ref().and_then( |p| p.start().ok() ) {
+        Some(start) => {
+            gamestate.snapshot = start.snapshot;
+            gamestate.player_turn = start.players[ start.turn ] as i32;
         }
-    }
-
-
-    println!("Hello from build_map, Players {} target spaces {} have {}.",
-            player_count, player_count * 16, space_count );
-
-    let target_spaces = player_count * 16;
-    let mut attempts = 1000;
-    while space_count > target_spaces && attempts > 0{
-        // erode away the board edges
-        let edge_spaces = gamestate.snapshot.map.edge_spaces_corners();
-
-        let random_index = rng.gen_range(0..edge_spaces.len());
-        let selected_index = edge_spaces[random_index];
-
-        // Try removing this space
-        let mut map_copy = gamestate.snapshot.map;
-        map_copy.spaces[selected_index as usize].contents = MapSpaceContents::NotInMap;
-
-        if map_copy.check_reachability() {
-            //gamestate.snapshot.map.spaces[selected_index].contents = MapSpaceContents::NotInMap;
-            gamestate.snapshot.map = map_copy;
-            space_count -= 1;
+        None => {
+            gamestate.snapshot = match pick_board( &settings.map, campaign.stage.is_some(), active, &mut last_pool_map ) {
+                Some(snapshot) => snapshot,
+                None => {
+                    let options = campaign.stage.map_or( MapGenOptions::default(), |ndx| STAGES[ ndx ].map );
+                    let mut rng = rand::thread_rng();
+                    generate_map_with( &mut rng, active, settings.start_power, &options )
+                }
+            };
         }
-
-        attempts -= 1;
-        println!("iter {} edge spaces now has {} entries space_count {}", attempts, edge_spaces.len(), space_count );
     }
 
-    if attempts == 0 {
-        println!("Warning! Failed to erode map.");
-    }
+    let space_count = gamestate.snapshot.map.spaces.iter()
+        .filter( |mapsq| mapsq.contents == MapSpaceContents::Playable ).count();
+    println!("Hello from build_map, Players {} spaces {}.", player_count, space_count );
 
-    // Find starting spaces
-    let mut edge_spaces = gamestate.snapshot.map.edge_spaces();
-    edge_spaces.shuffle( &mut rng );
-
-    for i in 0..stuff.player_stuff.len() {
-        if stuff.player_stuff[i].ptype != PlayerType::NotActive {
-            let selected_index = edge_spaces[i] as usize;
-
-            gamestate.snapshot.map.spaces[ selected_index ].player = (i+1) as u8;
-            gamestate.snapshot.map.spaces[ selected_index ].power = 16;
-
-            ev_gamestate.send( GameStateChanged::CircleAdded( selected_index as i32 ) );
+    for map_space in &gamestate.snapshot.map {
+        if map_space.power > 0 {
+            ev_gamestate.send( GameStateChanged::CircleAdded( map_space.ndx ) );
         }
     }
 
@@ -124,14 +105,14 @@ pub fn build_map (
                     material: materials.add(Color::rgb_u8(96, 60, 100)),
                     transform: Transform::from_translation( hex_pos ),
                     ..default()
-                }, MapSpaceVisual { ndx : map_space.ndx as usize, circle: None } )).id()
+                }, MapSpaceVisual { ndx : map_space.ndx as usize, circle: None }, GameplayCleanup )).id()
             },
             MapSpaceContents::Playable => {
                 commands.spawn( ( SceneBundle {
                     scene: hex_scene.clone(),
                     transform: Transform::from_translation( hex_pos ),
                     ..default()
-                }, MapSpaceVisual { ndx : map_space.ndx as usize, circle: None } )).id()
+                }, MapSpaceVisual { ndx : map_space.ndx as usize, circle: None }, GameplayCleanup )).id()
             },
         };
 
@@ -143,6 +124,7 @@ pub fn build_map (
 
 
     println!("Map size {}", gamestate.map_visuals.len());
+    ev_built.send( MapBuilt );
 
     // Send a turn advance to update the player prompt
     ev_turn.send( TurnAdvance(gamestate.player_turn) );
@@ -151,15 +133,6 @@ pub fn build_map (
 
 pub fn worldpos_from_mapindex( mapindex : i32 ) -> Vec3
 {
-    let row : i32 = mapindex / (MAP_SZ as i32);
-    let col : i32 = mapindex % (MAP_SZ as i32);
-
-    // offset if col is odd
-
-
-    // Make a vec3 from row and col
-    let sqrt3 = 1.7320508075688772;
-    let offset = if col % 2 == 1 { HEX_SZ * sqrt3 / 2.0 } else { 0.0 };
-    Vec3::new((col as f32 - 4.5) * (HEX_SZ * (3.0/2.0) ), 0.0,
-    (-row as f32 + 5.0) * (HEX_SZ * sqrt3) + offset )
+    let (x, z) = hex_offset( mapindex );
+    Vec3::new( x * HEX_SZ, 0.0, z * HEX_SZ )
 }
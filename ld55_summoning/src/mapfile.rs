@@ -0,0 +1,283 @@
+use std::collections::VecDeque;
+use std::path::Path;
+
+use rand::Rng;
+use rand::seq::IteratorRandom;
+
+use crate::gamestate::*;
+use crate::mapgen::hex_offset;
+use crate::position::Position;
+
+// Hand made boards, one per file in MAPS_DIR. A map file is the position.rs
+// text format with the starting stacks on it. The seats in the file only say
+// which stacks go together, whoever is playing gets them in seat order, so a
+// map with stacks for seats 1 and 2 also works for seats 2 and 4.
+pub const MAPS_DIR : &str = "maps";
+pub const MAP_EXT : &str = "txt";
+
+// A stack can't split with less than this
+pub const MIN_STACK_POWER : u8 = 2;
+
+#[derive(Clone, Debug)]
+pub struct MapFile {
+    pub name : String,
+    pub snapshot : GameSnapshot,
+}
+
+impl MapFile {
+    // Seats with a starting stack, 0 based
+    pub fn seats( &self ) -> Vec<usize>
+    {
+        stack_seats( &self.snapshot )
+    }
+
+    pub fn player_count( &self ) -> usize
+    {
+        self.seats().len()
+    }
+
+    pub fn to_text( &self ) -> String
+    {
+        let mut players = self.seats();
+        if players.is_empty() {
+            players.push( 0 );
+        }
+        let position = Position { snapshot: self.snapshot, players, turn: 0, clocks: None };
+        format!("# {}\n{}", self.name, position.to_text() )
+    }
+
+    pub fn from_text( name : &str, text : &str ) -> Result<MapFile, String>
+    {
+        let position = Position::from_text( text )?;
+        Ok( MapFile { name: name.to_string(), snapshot: position.snapshot } )
+    }
+
+    // The name comes from the file name
+    pub fn load( path : &Path ) -> Result<MapFile, String>
+    {
+        let name = path.file_stem().and_then( |stem| stem.to_str() )
+            .ok_or_else( || format!("bad map file name {}", path.display() ) )?;
+        let text = std::fs::read_to_string( path ).map_err( |err| err.to_string() )?;
+        MapFile::from_text( name, &text )
+    }
+
+    pub fn path( dir : &str, name : &str ) -> std::path::PathBuf
+    {
+        Path::new( dir ).join( format!("{}.{}", name, MAP_EXT ) )
+    }
+
+    pub fn save( &self, dir : &str ) -> Result<(), String>
+    {
+        std::fs::create_dir_all( dir ).map_err( |err| err.to_string() )?;
+        std::fs::write( MapFile::path( dir, &self.name ), self.to_text() ).map_err( |err| err.to_string() )
+    }
+
+    // The board for a match with these seats. The map's stacks are handed
+    // out in seat order, so there have to be as many players as stacks.
+    pub fn snapshot_for( &self, active : [bool; 4] ) -> Result<GameSnapshot, String>
+    {
+        let seats = self.seats();
+        let playing : Vec<usize> = (0..4).filter( |p| active[*p] ).collect();
+        if seats.len() != playing.len() {
+            return Err( format!("{} is for {} players, not {}", self.name, seats.len(), playing.len() ) );
+        }
+
+        let mut snap = self.snapshot;
+        for mapsq in &mut snap.map {
+            if mapsq.power > 0 {
+                let from = seats.iter().position( |s| *s + 1 == mapsq.player as usize ).unwrap_or( 0 );
+                mapsq.player = playing[ from ] as u8 + 1;
+            }
+        }
+        snap.update_scores();
+        Ok( snap )
+    }
+}
+
+// Turns a map file into the board for a match with these seats
+pub fn load_board( path : &Path, active : [bool; 4] ) -> Result<GameSnapshot, String>
+{
+    MapFile::load( path )?.snapshot_for( active )
+}
+
+// Where the board for a match comes from
+#[derive(Clone, Debug, Default)]
+pub enum MapChoice {
+    #[default]
+    Generated,
+    Saved( Box<MapFile> ),
+    Pool( Vec<MapFile> ),  // a different one of these each match
+}
+
+impl MapChoice {
+    // The saved maps that work for this many players
+    pub fn fits( maps : &[MapFile], players : usize ) -> impl Iterator<Item = &MapFile>
+    {
+        maps.iter().filter( move |map| map.player_count() == players )
+    }
+
+    // A map from the pool for this many players, trying not to play the
+    // same one twice in a row
+    pub fn pick<'a, R: Rng + ?Sized>( maps : &'a [MapFile], rng : &mut R, players : usize, last : Option<&str> ) -> Option<&'a MapFile>
+    {
+        MapChoice::fits( maps, players ).filter( |map| Some( map.name.as_str() ) != last ).choose( rng )
+            .or_else( || MapChoice::fits( maps, players ).next() )
+    }
+}
+
+pub fn stack_seats( snap : &GameSnapshot ) -> Vec<usize>
+{
+    (0..4).filter( |p| snap.map.spaces.iter().any( |s| s.power > 0 && s.player as usize == p + 1 ) ).collect()
+}
+
+// How many spaces each seat's stacks are nearer to than anyone else's,
+// counting steps between playable spaces. A rough measure of how much room
+// each seat gets. Ties don't count for anyone.
+pub fn seat_reach( snap : &GameSnapshot ) -> [i32; 4]
+{
+    let mut dist = [ [ i32::MAX; MAP_SZ * MAP_SZ ]; 4 ];
+    for (seat, seat_dist) in dist.iter_mut().enumerate() {
+        let mut queue = VecDeque::new();
+        for mapsq in &snap.map {
+            if mapsq.power > 0 && mapsq.player as usize == seat + 1 {
+                seat_dist[ mapsq.ndx as usize ] = 0;
+                queue.push_back( mapsq.ndx );
+            }
+        }
+
+        while let Some(ndx) = queue.pop_front() {
+            for nbr in snap.map.neighbors( ndx, true ) {
+                if seat_dist[ nbr as usize ] == i32::MAX {
+                    seat_dist[ nbr as usize ] = seat_dist[ ndx as usize ] + 1;
+                    queue.push_back( nbr );
+                }
+            }
+        }
+    }
+
+    let mut reach = [ 0; 4 ];
+    for mapsq in &snap.map {
+        let ndx = mapsq.ndx as usize;
+        let best = (0..4).map( |p| dist[p][ndx] ).min().unwrap_or( i32::MAX );
+        let nearest : Vec<usize> = (0..4).filter( |p| dist[*p][ndx] == best ).collect();
+        if best != i32::MAX && nearest.len() == 1 {
+            reach[ nearest[0] ] += 1;
+        }
+    }
+    reach
+}
+
+// Checks a hand made board is playable and doesn't favor a seat. Hands back
+// the seats it has stacks for.
+pub fn validate_map( snap : &GameSnapshot ) -> Result<Vec<usize>, String>
+{
+    let seats = stack_seats( snap );
+    if seats.len() < 2 {
+        return Err( "Needs starting stacks for at least two seats.".into() );
+    }
+    if !snap.map.check_reachability() {
+        return Err( "Some spaces can't be reached from the rest.".into() );
+    }
+    if let Some(weak) = snap.map.spaces.iter().find( |s| s.power > 0 && s.power < MIN_STACK_POWER ) {
+        return Err( format!("Player {}'s stack needs at least {} power to split.", weak.player, MIN_STACK_POWER ) );
+    }
+
+    let mut stacks = [ 0; 4 ];
+    let mut power = [ 0; 4 ];
+    for mapsq in snap.map.spaces.iter().filter( |s| s.power > 0 ) {
+        stacks[ mapsq.player as usize - 1 ] += 1;
+        power[ mapsq.player as usize - 1 ] += mapsq.power as i32;
+    }
+
+    let first = seats[0];
+    for &seat in &seats[ 1.. ] {
+        if stacks[ seat ] != stacks[ first ] {
+            return Err( format!("Player {} has {} stacks but player {} has {}.", seat + 1, stacks[ seat ], first + 1, stacks[ first ] ) );
+        }
+        if power[ seat ] != power[ first ] {
+            return Err( format!("Player {} starts with {} power but player {} has {}.", seat + 1, power[ seat ], first + 1, power[ first ] ) );
+        }
+    }
+
+    // Within a quarter of each other
+    let reach = seat_reach( snap );
+    let most = seats.iter().copied().max_by_key( |s| reach[*s] ).unwrap_or( first );
+    let least = seats.iter().copied().min_by_key( |s| reach[*s] ).unwrap_or( first );
+    if reach[ least ] * 4 < reach[ most ] * 3 {
+        return Err( format!("Player {} has room for {} spaces but player {} only {}.", most + 1, reach[ most ], least + 1, reach[ least ] ) );
+    }
+
+    Ok( seats )
+}
+
+// Every readable, playable map in a directory, by name
+pub fn list_maps( dir : &str ) -> Vec<MapFile>
+{
+    let Ok(entries) = std::fs::read_dir( dir ) else {
+        return Vec::new();
+    };
+
+    let mut maps : Vec<MapFile> = entries.flatten()
+        .map( |entry| entry.path() )
+        .filter( |path| path.extension().is_some_and( |ext| ext == MAP_EXT ) )
+        .filter_map( |path| match MapFile::load( &path ) {
+            Ok(map) => Some(map),
+            Err(err) => {
+                println!("Couldn't read map {}: {}", path.display(), err );
+                None
+            }
+        })
+        .filter( |map| match validate_map( &map.snapshot ) {
+            Ok(_) => true,
+            Err(err) => {
+                println!("Skipping map {}: {}", map.name, err );
+                false
+            }
+        })
+        .collect();
+    maps.sort_by( |a, b| a.name.cmp( &b.name ) );
+    maps
+}
+
+// A name nothing in the directory is using yet
+pub fn unused_map_name( dir : &str ) -> String
+{
+    (1..).map( |n| format!("map_{}", n ) )
+        .find( |name| !MapFile::path( dir, name ).exists() )
+        .unwrap_or_default()
+}
+
+// Draws the board as a small square RGBA picture, a dot for each space in
+// the map. `seat_colors` are for the starting stacks.
+pub fn thumbnail( snap : &GameSnapshot, size : u32, seat_colors : &[[u8; 4]; 4] ) -> Vec<u8>
+{
+    let mut pixels = vec![ 0u8; (size * size * 4) as usize ];
+
+    // The board is about 15 hex sizes across and 17 down, centered a little low
+    let scale = size as f32 / 18.0;
+    let radius = scale * 0.85;
+    for mapsq in &snap.map {
+        let color = match mapsq.contents {
+            MapSpaceContents::NotInMap => continue,
+            MapSpaceContents::Blocked => [ 96, 60, 100, 255 ],
+            MapSpaceContents::Playable if mapsq.power > 0 => seat_colors[ (mapsq.player as usize).clamp( 1, 4 ) - 1 ],
+            MapSpaceContents::Playable => [ 90, 90, 100, 255 ],
+        };
+
+        let (x, y) = hex_offset( mapsq.ndx );
+        let (cx, cy) = (size as f32 / 2.0 + x * scale, size as f32 / 2.0 + (y - 1.3) * scale);
+        let (left, right) = ((cx - radius).max( 0.0 ) as u32, ((cx + radius).ceil() as u32).min( size ));
+        let (top, bottom) = ((cy - radius).max( 0.0 ) as u32, ((cy + radius).ceil() as u32).min( size ));
+        for py in top..bottom {
+            for px in left..right {
+                let (dx, dy) = (px as f32 + 0.5 - cx, py as f32 + 0.5 - cy);
+                if dx * dx + dy * dy <= radius * radius {
+                    let at = ((py * size + px) * 4) as usize;
+                    pixels[ at..at + 4 ].copy_from_slice( &color );
+                }
+            }
+        }
+    }
+
+    pixels
+}
@@ -0,0 +1,117 @@
+use rand::Rng;
+use rand::prelude::SliceRandom;
+use serde::{ Deserialize, Serialize };
+
+use crate::gamestate::*;
+
+// Knobs for the board shape. The defaults are what a normal match gets.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MapGenOptions {
+    pub radius : f32,          // in hex sizes from the middle, before eroding
+    pub blocked_one_in : u32,  // about one space in this many is blocked, 0 for none
+}
+
+impl Default for MapGenOptions {
+    fn default() -> MapGenOptions {
+        MapGenOptions {
+            radius: 8.0,
+            blocked_one_in: 8,
+        }
+    }
+}
+
+// Where the middle of a space is on the board, in hex sizes. The first value
+// goes across the columns and the second goes down the rows.
+pub fn hex_offset( mapindex : i32 ) -> (f32, f32)
+{
+    let row : i32 = mapindex / (MAP_SZ as i32);
+    let col : i32 = mapindex % (MAP_SZ as i32);
+
+    // offset if col is odd
+    let sqrt3 = 3f32.sqrt();
+    let offset = if col % 2 == 1 { sqrt3 / 2.0 } else { 0.0 };
+    ( (col as f32 - 4.5) * (3.0/2.0), (-row as f32 + 5.0) * sqrt3 + offset )
+}
+
+// Builds a random board with a starting stack for each active seat. Takes the
+// rng so benchmarks and tools can get the same map back from a seed.
+pub fn generate_map<R: Rng + ?Sized>( rng : &mut R, active : [bool; 4], start_power : u8 ) -> GameSnapshot
+{
+    generate_map_with( rng, active, start_power, &MapGenOptions::default() )
+}
+
+pub fn generate_map_with<R: Rng + ?Sized>( rng : &mut R, active : [bool; 4], start_power : u8, options : &MapGenOptions ) -> GameSnapshot
+{
+    let mut snap = GameSnapshot::default();
+    let player_count = active.iter().filter( |a| **a ).count() as i32;
+
+    // First, set up the map indices and build the map
+    let mut space_count = 0;
+    for (index, map_space) in (&mut snap.map).into_iter().enumerate() {
+        map_space.ndx = index as i32;
+
+        let (x, y) = hex_offset( map_space.ndx );
+
+        // this trims the board and makes it more rounder
+        if (x * x + y * y).sqrt() < options.radius {
+
+            // todo: replace this with adding some obstacles with preset shapes
+            if options.blocked_one_in > 0 && rng.gen_ratio(1, options.blocked_one_in) {
+                map_space.contents = MapSpaceContents::Blocked;
+            } else {
+                map_space.contents = MapSpaceContents::Playable;
+                space_count += 1;
+            }
+        }
+    }
+
+    // Enough room for every starting stack to spread all the way out
+    // and never less than a space each, however low the power
+    let target_spaces = (player_count * start_power as i32).max( player_count );
+
+    let mut attempts = 1000;
+    while space_count > target_spaces && attempts > 0{
+        // erode away the board edges
+        let edge_spaces = snap.map.edge_spaces_corners();
+
+        let random_index = rng.gen_range(0..edge_spaces.len());
+        let selected_index = edge_spaces[random_index];
+
+        // Try removing this space
+        let mut map_copy = snap.map;
+        map_copy.spaces[selected_index as usize].contents = MapSpaceContents::NotInMap;
+
+        if map_copy.check_reachability() {
+            snap.map = map_copy;
+            space_count -= 1;
+        }
+
+        attempts -= 1;
+    }
+
+    if attempts == 0 {
+        println!("Warning! Failed to erode map.");
+    }
+
+    // Find starting spaces
+    let mut edge_spaces = snap.map.edge_spaces();
+    edge_spaces.shuffle( rng );
+
+    let mut placed = 0;
+    for (i, is_active) in active.iter().enumerate() {
+        if *is_active {
+            let Some(&selected_index) = edge_spaces.get( placed ) else {
+                println!("Warning! No edge space left for player {}.", i + 1 );
+                break;
+            };
+            placed += 1;
+
+            snap.map.spaces[ selected_index as usize ].player = (i+1) as u8;
+            snap.map.spaces[ selected_index as usize ].power = start_power;
+        }
+    }
+
+    snap.update_scores();
+    snap
+}
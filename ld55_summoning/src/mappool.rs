@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{ Extent3d, TextureDimension, TextureFormat };
+
+use crate::summongame::{ GameAppState, GoodStuff, MatchSettings, PlayerType };
+use crate::titlescreen::TitleScreenCleanup;
+use crate::mapfile::{ list_maps, thumbnail, MapChoice, MapFile, MAPS_DIR };
+
+// The map picker on the title screen. Lists the saved maps that fit the
+// seats as they are, each with a little picture of the board, plus
+// generated boards and a random map from the pool each match.
+const THUMBNAIL_SIZE : u32 = 64;
+
+// Sent by the Map option on the title screen
+#[derive(Event)]
+pub struct ToggleMapPicker;
+
+// Sent once the picker has changed MatchSettings::map
+#[derive(Event)]
+pub struct MapChosen;
+
+// Pictures of the maps in the picker, by name. Remade each time it opens
+// since the editor may have changed them.
+#[derive(Resource, Default)]
+struct MapThumbnails( HashMap<String, Handle<Image>> );
+
+#[derive(Component)]
+struct MapPicker;
+
+#[derive(Component)]
+enum MapPickerChoice {
+    Generated,
+    Pool,
+    Saved( usize ),  // into the maps the picker was opened with
+}
+
+// The saved maps as they were when the picker opened
+#[derive(Resource, Default)]
+struct PickerMaps( Vec<MapFile> );
+
+pub struct MapPoolPlugin;
+
+impl Plugin for MapPoolPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource( MapThumbnails::default() )
+            .insert_resource( PickerMaps::default() )
+            .add_event::<ToggleMapPicker>()
+            .add_event::<MapChosen>()
+            .add_systems(Update, (
+                toggle_map_picker,
+                choose_map,
+                ).run_if(in_state(GameAppState::TitleScreen)));
+    }
+}
+
+fn thumbnail_image( map : &MapFile, stuff : &GoodStuff ) -> Image {
+    let colors : [[u8; 4]; 4] = std::array::from_fn( |seat| stuff.player_stuff[ seat ].color.as_rgba_u8() );
+    Image::new(
+        Extent3d { width: THUMBNAIL_SIZE, height: THUMBNAIL_SIZE, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        thumbnail( &map.snapshot, THUMBNAIL_SIZE, &colors ),
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    )
+}
+
+fn toggle_map_picker(
+    mut commands: Commands,
+    stuff: Res<GoodStuff>,
+    settings: Res<MatchSettings>,
+    mut images: ResMut<Assets<Image>>,
+    mut thumbnails: ResMut<MapThumbnails>,
+    mut picker_maps: ResMut<PickerMaps>,
+    picker_q: Query<Entity, With<MapPicker>>,
+    mut ev_toggle: EventReader<ToggleMapPicker>,
+)
+{
+    if ev_toggle.read().count() == 0 {
+        return;
+    }
+
+    // Second press closes it
+    if let Ok(entity) = picker_q.get_single() {
+        commands.entity(entity).despawn_recursive();
+        return;
+    }
+
+    let players = stuff.player_stuff.iter().filter( |p| p.ptype != PlayerType::NotActive ).count();
+    picker_maps.0 = list_maps( MAPS_DIR );
+    thumbnails.0 = picker_maps.0.iter()
+        .map( |map| (map.name.clone(), images.add( thumbnail_image( map, &stuff ) )) )
+        .collect();
+
+    let fitting = MapChoice::fits( &picker_maps.0, players ).count();
+    let selected = |choice : &MapPickerChoice| match (choice, &settings.map) {
+        (MapPickerChoice::Generated, MapChoice::Generated) => true,
+        (MapPickerChoice::Pool, MapChoice::Pool(_)) => true,
+        (MapPickerChoice::Saved( ndx ), MapChoice::Saved( map )) => picker_maps.0[ *ndx ].name == map.name,
+        _ => false,
+    };
+
+    commands.spawn(( NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.0),
+            left: Val::Px(12.0),
+            max_height: Val::Percent(80.0),
+            flex_direction: FlexDirection::Column,
+            flex_wrap: FlexWrap::Wrap,
+            row_gap: Val::Px(6.0),
+            column_gap: Val::Px(6.0),
+            padding: UiRect::all( Val::Px( 10.0 )),
+            ..default()
+        },
+        background_color: BackgroundColor( Color::rgba( 0.0, 0.0, 0.0, 0.8 ) ),
+        z_index: ZIndex::Global( 10 ),
+        ..default()
+    }, MapPicker, TitleScreenCleanup ))
+    .with_children( |parent| {
+        let mut entries = vec![
+            (MapPickerChoice::Generated, "Generated".to_string(), "A new board each match".to_string(), None),
+            (MapPickerChoice::Pool, "Random from pool".to_string(), format!("{} saved maps for {} players", fitting, players ), None),
+        ];
+        for (ndx, map) in picker_maps.0.iter().enumerate() {
+            if map.player_count() == players {
+                entries.push( (MapPickerChoice::Saved( ndx ), map.name.clone(), format!("{} players", map.player_count() ), thumbnails.0.get( &map.name ).cloned()) );
+            }
+        }
+
+        for (choice, name, detail, image) in entries {
+            let highlight = if selected( &choice ) { Color::rgba( 1.0, 0.3, 0.9, 0.4 ) } else { Color::rgba( 1.0, 1.0, 1.0, 0.05 ) };
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(8.0),
+                        padding: UiRect::all( Val::Px( 4.0 )),
+                        ..default()
+                    },
+                    background_color: BackgroundColor( highlight ),
+                    ..default()
+                },
+                choice,
+            ))
+            .with_children( |parent| {
+                if let Some(image) = image {
+                    parent.spawn( ImageBundle {
+                        style: Style {
+                            width: Val::Px( THUMBNAIL_SIZE as f32 ),
+                            height: Val::Px( THUMBNAIL_SIZE as f32 ),
+                            ..default()
+                        },
+                        image: UiImage::new( image ),
+                        ..default()
+                    });
+                }
+
+                parent.spawn( NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        ..default()
+                    },
+                    ..default()
+                }).with_children( |parent| {
+                    parent.spawn(TextBundle::from_section(
+                        name,
+                        TextStyle {
+                            font_size: 20.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                    parent.spawn(TextBundle::from_section(
+                        detail,
+                        TextStyle {
+                            font_size: 14.0,
+                            color: Color::rgb( 0.7, 0.7, 0.7 ),
+                            ..default()
+                        },
+                    ));
+                });
+            });
+        }
+
+        let others = picker_maps.0.len() - fitting;
+        if others > 0 {
+            parent.spawn(TextBundle::from_section(
+                format!("{} more for other player counts", others ),
+                TextStyle {
+                    font_size: 14.0,
+                    color: Color::rgb( 0.7, 0.7, 0.7 ),
+                    ..default()
+                },
+            ));
+        }
+    });
+}
+
+fn choose_map(
+    mut commands: Commands,
+    mut settings: ResMut<MatchSettings>,
+    picker_maps: Res<PickerMaps>,
+    picker_q: Query<Entity, With<MapPicker>>,
+    choice_q: Query<(&Interaction, &MapPickerChoice), Changed<Interaction>>,
+    mut ev_chosen: EventWriter<MapChosen>,
+)
+{
+    for (interaction, choice) in &choice_q {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        settings.map = match choice {
+            MapPickerChoice::Generated => MapChoice::Generated,
+            MapPickerChoice::Pool => MapChoice::Pool( picker_maps.0.clone() ),
+            MapPickerChoice::Saved( ndx ) => MapChoice::Saved( Box::new( picker_maps.0[ *ndx ].clone() ) ),
+        };
+        ev_chosen.send( MapChosen );
+
+        for entity in &picker_q {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
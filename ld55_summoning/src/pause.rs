@@ -0,0 +1,192 @@
+use bevy::prelude::*;
+
+use crate::summongame::{ GameAppState, GoodStuff, PauseState, PlayerType, SummonGame, TurnAdvance };
+use crate::profiles::ProfileBook;
+use crate::{ advance_turn, GameCursor };
+
+// Esc or Start brings up the pause menu during a match. From there a player
+// can concede, restart with the same seats or go back to the title screen.
+#[derive(Component)]
+struct PauseMenu;
+
+#[derive(Component)]
+enum PauseMenuAction {
+    Resume,
+    Concede,
+    Restart,
+    QuitToTitle,
+}
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, (
+                toggle_pause,
+                pause_menu_action,
+                )
+                .run_if(in_state(GameAppState::Gameplay)))
+            .add_systems( OnEnter(PauseState::Paused), pause_setup )
+            .add_systems( OnExit(PauseState::Paused), pause_teardown )
+            .add_systems( OnExit(GameAppState::Gameplay), unpause );
+    }
+}
+
+fn toggle_pause(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    pause_state: Res<State<PauseState>>,
+    mut next_pause: ResMut<NextState<PauseState>>,
+)
+{
+    let pressed = keyboard_input.just_pressed( KeyCode::Escape ) ||
+        gamepads.iter().any( |pad| gamepad_buttons.just_pressed( GamepadButton::new( pad, GamepadButtonType::Start ) ) );
+    if !pressed {
+        return;
+    }
+
+    next_pause.set( match pause_state.get() {
+        PauseState::Running => PauseState::Paused,
+        PauseState::Paused => PauseState::Running,
+    });
+}
+
+fn pause_setup(
+    mut commands: Commands,
+    mut time: ResMut<Time<Virtual>>,
+    stuff: Res<GoodStuff>,
+    game: Res<SummonGame>,
+    book: Res<ProfileBook>,
+)
+{
+    println!("Paused");
+
+    // Stops the clocks and any animation mid-flight
+    time.pause();
+
+    let pinfo = &stuff.player_stuff[ game.player_turn as usize ];
+    let mut buttons = vec![ ("Resume".to_string(), PauseMenuAction::Resume) ];
+    if pinfo.ptype == PlayerType::Local && !game.game_over {
+        buttons.push( (format!("Concede ({})", book.seat_name( pinfo ) ), PauseMenuAction::Concede) );
+    }
+    buttons.push( ("Restart".into(), PauseMenuAction::Restart) );
+    buttons.push( ("Quit to Title".into(), PauseMenuAction::QuitToTitle) );
+
+    commands.spawn(( NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            row_gap: Val::Px(12.0),
+            ..default()
+        },
+        background_color: BackgroundColor( Color::rgba( 0.0, 0.0, 0.0, 0.6 ) ),
+        z_index: ZIndex::Global(10),
+        ..default()
+    }, PauseMenu ))
+    .with_children( |parent| {
+        parent.spawn(TextBundle::from_section(
+            "Paused",
+            TextStyle {
+                font_size: 50.0,
+                color: Color::rgb(1.0, 0.3, 0.9),
+                ..default()
+            },
+        ));
+
+        for (label, action) in buttons {
+            parent.spawn(( ButtonBundle {
+                style: Style {
+                    width: Val::Px(260.0),
+                    height: Val::Px(48.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: BackgroundColor( Color::rgba( 0.2, 0.1, 0.3, 0.9 ) ),
+                ..default()
+            }, action ))
+            .with_children( |parent| {
+                parent.spawn(TextBundle::from_section(
+                    label,
+                    TextStyle {
+                        font_size: 24.0,
+                        ..default()
+                    },
+                ));
+            });
+        }
+    });
+}
+
+fn pause_teardown(
+    mut commands: Commands,
+    mut time: ResMut<Time<Virtual>>,
+    menu_q: Query<Entity, With<PauseMenu>>,
+)
+{
+    println!("Unpaused");
+    time.unpause();
+
+    for entity in &menu_q {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// Leaving the match while paused shouldn't leave us paused
+fn unpause(
+    mut next_pause: ResMut<NextState<PauseState>>,
+)
+{
+    next_pause.set( PauseState::Running );
+}
+
+fn pause_menu_action(
+    interaction_query: Query<(&Interaction, &PauseMenuAction), (Changed<Interaction>, With<Button>)>,
+    stuff: Res<GoodStuff>,
+    mut game: ResMut<SummonGame>,
+    mut cursor_q: Query<&mut GameCursor>,
+    mut next_pause: ResMut<NextState<PauseState>>,
+    mut game_state: ResMut<NextState<GameAppState>>,
+    mut ev_turn: EventWriter<TurnAdvance>,
+)
+{
+    for (interaction, action) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match action {
+            PauseMenuAction::Resume => {
+                next_pause.set( PauseState::Running );
+            }
+            PauseMenuAction::Concede => {
+                // Their stacks stay on the board but they're out of the rotation
+                let pnum = game.player_turn as usize;
+                println!("Player {} concedes", pnum + 1 );
+                game.conceded[ pnum ] = true;
+                for mut cursor_info in &mut cursor_q {
+                    cursor_info.drag_from = None;
+                    cursor_info.key_dir = None;
+                }
+
+                let next = advance_turn( &mut game, &stuff );
+                ev_turn.send( TurnAdvance( next ) );
+                next_pause.set( PauseState::Running );
+            }
+            PauseMenuAction::Restart => {
+                game_state.set( GameAppState::Restarting );
+                next_pause.set( PauseState::Running );
+            }
+            PauseMenuAction::QuitToTitle => {
+                game_state.set( GameAppState::TitleScreen );
+                next_pause.set( PauseState::Running );
+            }
+        }
+    }
+}
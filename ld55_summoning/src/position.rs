@@ -0,0 +1,157 @@
+use crate::gamestate::*;
+
+// A board plus whose turn it is, in a plain text format that's easy to read and
+// edit by hand. Used by perft and for saving a position while debugging bots.
+//
+//   # anything after a hash is a comment
+//   players 1 2 4
+//   turn 2
+//   clocks 30.0 12.5 45.0    # seconds left, in players order, only with a clock
+//   -    -    .    .    x    .    .    .    -    -
+//   ...ten rows, the top row of the board (row 9) first...
+//
+// Each space is `-` when it's not in the map, `x` when blocked, `.` when empty
+// and `player:power` for a stack, like `3:16`.
+#[derive(Clone, Debug)]
+pub struct Position {
+    pub snapshot : GameSnapshot,
+    pub players : Vec<usize>,  // seats still in the game, 0 based
+    pub turn : usize,          // index into players
+    pub clocks : Option<[f32; 4]>,  // seconds left per seat, like SummonGame::clocks
+}
+
+impl Position {
+    pub fn to_text( &self ) -> String
+    {
+        let seats : Vec<String> = self.players.iter().map( |p| (p + 1).to_string() ).collect();
+        let mut text = format!("players {}\nturn {}\n", seats.join(" "), self.players[ self.turn ] + 1 );
+        if let Some(clocks) = self.clocks {
+            let times : Vec<String> = self.players.iter().map( |p| format!("{:.1}", clocks[ *p ] ) ).collect();
+            text.push_str( &format!("clocks {}\n", times.join(" ") ) );
+        }
+
+        for row in (0..MAP_SZ as i32).rev() {
+            let mut tokens = Vec::new();
+            for col in 0..MAP_SZ as i32 {
+                let mapsq = self.snapshot.map.spaces[ map_index( row, col ) as usize ];
+                tokens.push( match mapsq.contents {
+                    MapSpaceContents::NotInMap => "-".to_string(),
+                    MapSpaceContents::Blocked => "x".to_string(),
+                    MapSpaceContents::Playable if mapsq.power == 0 => ".".to_string(),
+                    MapSpaceContents::Playable => format!("{}:{}", mapsq.player, mapsq.power ),
+                });
+            }
+            let line : Vec<String> = tokens.iter().map( |t| format!("{:<4}", t ) ).collect();
+            text.push_str( line.join(" ").trim_end() );
+            text.push( '\n' );
+        }
+
+        text
+    }
+
+    pub fn from_text( text : &str ) -> Result<Position, String>
+    {
+        let mut players = Vec::new();
+        let mut turn_seat = None;
+        let mut times = None;
+        let mut rows = Vec::new();
+
+        for line in text.lines() {
+            let line = line.split( '#' ).next().unwrap_or( "" ).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("players") => {
+                    for word in words {
+                        players.push( parse_seat( word )? );
+                    }
+                }
+                Some("turn") => {
+                    turn_seat = Some( parse_seat( words.next().unwrap_or( "" ) )? );
+                }
+                Some("clocks") => {
+                    times = Some( words.map( parse_time ).collect::<Result<Vec<f32>, String>>()? );
+                }
+                _ => rows.push( line ),
+            }
+        }
+
+        if rows.len() != MAP_SZ {
+            return Err( format!("expected {} board rows, found {}", MAP_SZ, rows.len() ) );
+        }
+        if players.is_empty() {
+            return Err( "missing players line".into() );
+        }
+
+        let turn_seat = turn_seat.unwrap_or( players[0] );
+        let Some(turn) = players.iter().position( |p| *p == turn_seat ) else {
+            return Err( format!("player {} has the turn but isn't playing", turn_seat + 1 ) );
+        };
+
+        let clocks = match times {
+            Some(times) if times.len() != players.len() => {
+                return Err( format!("{} clocks for {} players", times.len(), players.len() ) );
+            }
+            Some(times) => {
+                let mut clocks = [ 0.0; 4 ];
+                for (seat, secs) in players.iter().zip( times ) {
+                    clocks[ *seat ] = secs;
+                }
+                Some( clocks )
+            }
+            None => None,
+        };
+
+        let mut snapshot = GameSnapshot::default();
+        for (rndx, line) in rows.iter().enumerate() {
+            let row = (MAP_SZ - 1 - rndx) as i32;
+            let tokens : Vec<&str> = line.split_whitespace().collect();
+            if tokens.len() != MAP_SZ {
+                return Err( format!("row {} has {} spaces, expected {}", row, tokens.len(), MAP_SZ ) );
+            }
+
+            for (col, token) in tokens.iter().enumerate() {
+                let ndx = map_index( row, col as i32 );
+                let mapsq = &mut snapshot.map.spaces[ ndx as usize ];
+                mapsq.ndx = ndx;
+                match *token {
+                    "-" => mapsq.contents = MapSpaceContents::NotInMap,
+                    "x" => mapsq.contents = MapSpaceContents::Blocked,
+                    "." => mapsq.contents = MapSpaceContents::Playable,
+                    _ => {
+                        let (player, power) = token.split_once( ':' )
+                            .ok_or_else( || format!("bad space '{}' in row {}", token, row ) )?;
+                        mapsq.contents = MapSpaceContents::Playable;
+                        mapsq.player = parse_seat( player )? as u8 + 1;
+                        mapsq.power = power.parse().ok().filter( |p| *p > 0 )
+                            .ok_or_else( || format!("bad power '{}' in row {}", power, row ) )?;
+                    }
+                }
+            }
+        }
+
+        snapshot.update_scores();
+        Ok( Position { snapshot, players, turn, clocks } )
+    }
+}
+
+// Seconds left on a clock
+fn parse_time( word : &str ) -> Result<f32, String>
+{
+    match word.parse::<f32>() {
+        Ok(secs) if secs.is_finite() && secs >= 0.0 => Ok( secs ),
+        _ => Err( format!("bad clock '{}', expected seconds left", word ) ),
+    }
+}
+
+// Seats are written 1 to 4, like the score labels
+fn parse_seat( word : &str ) -> Result<usize, String>
+{
+    match word.parse::<usize>() {
+        Ok(seat) if (1..=4).contains( &seat ) => Ok( seat - 1 ),
+        _ => Err( format!("bad player '{}', expected 1 to 4", word ) ),
+    }
+}
@@ -0,0 +1,202 @@
+use bevy::prelude::*;
+use serde::{ Deserialize, Serialize };
+
+use crate::summongame::{ GameAppState, GameOver, GoodStuff, PlayerStuff, PlayerType, SummonGame };
+use crate::book::{ OpeningBook, BOOK_FILE };
+use crate::puzzles::ActivePuzzle;
+use crate::gamestate::EvalWeights;
+use crate::ronfile::RonFile;
+use crate::weights::{ WeightsFile, WEIGHTS_FILE };
+pub use crate::weights::BOT_NAMES;
+
+// Named players and their lifetime stats, so the office league can keep
+// standings. Names can be edited in the profiles file.
+pub const PROFILES_FILE : &str = "summoning_profiles.ron";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct PlayerProfile {
+    pub name : String,
+    pub portrait : usize,  // index into the human portraits
+    pub games_played : u32,
+    pub games_by_seats : [u32; 5],  // indexed by how many players were in the match
+    pub wins_by_seats : [u32; 5],
+    pub total_score : u32,
+}
+
+impl PlayerProfile {
+    pub fn new( name : &str, portrait : usize ) -> PlayerProfile {
+        PlayerProfile {
+            name: name.into(),
+            portrait,
+            ..default()
+        }
+    }
+
+    pub fn wins( &self ) -> u32 {
+        self.wins_by_seats.iter().sum()
+    }
+
+    pub fn average_score( &self ) -> f32 {
+        if self.games_played == 0 {
+            0.0
+        } else {
+            self.total_score as f32 / self.games_played as f32
+        }
+    }
+
+    pub fn summary( &self ) -> String {
+        format!("{}W / {}G  avg {:.1}", self.wins(), self.games_played, self.average_score() )
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+pub struct ProfileBook {
+    pub profiles : Vec<PlayerProfile>,
+}
+
+impl Default for ProfileBook {
+    fn default() -> ProfileBook {
+        // One per portrait to start with
+        let names = [ "Vex", "Nyx", "Cipher", "Rune", "Sable" ];
+        ProfileBook {
+            profiles: names.iter().enumerate().map( |(i, name)| PlayerProfile::new( name, i ) ).collect(),
+        }
+    }
+}
+
+impl RonFile for ProfileBook {}
+
+impl ProfileBook {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load() -> ProfileBook {
+        match ProfileBook::load_or_report( PROFILES_FILE ) {
+            Some(book) if !book.profiles.is_empty() => book,
+            _ => ProfileBook::default(),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load() -> ProfileBook {
+        ProfileBook::default()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save( &self ) {
+        self.save_or_report( PROFILES_FILE );
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save( &self ) {
+    }
+
+    pub fn get( &self, ndx : i32 ) -> &PlayerProfile {
+        &self.profiles[ (ndx.rem_euclid( self.profiles.len() as i32 )) as usize ]
+    }
+
+    // What to call whoever is sitting in a seat
+    pub fn seat_name( &self, pinfo : &PlayerStuff ) -> String {
+        match pinfo.ptype {
+            PlayerType::Local => self.get( pinfo.human_profile ).name.clone(),
+            PlayerType::AI => BOT_NAMES[ (pinfo.bot_profile.rem_euclid( BOT_NAMES.len() as i32 )) as usize ].into(),
+            PlayerType::NotActive => "Empty".into(),
+        }
+    }
+}
+
+// Evaluation weights for each bot profile, from the tuner
+#[derive(Resource, Default)]
+pub struct BotWeights( pub WeightsFile );
+
+impl BotWeights {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load() -> BotWeights {
+        match WeightsFile::load( WEIGHTS_FILE ) {
+            Ok(file) => {
+                println!("Loaded tuned weights for {:?}", file.bots.keys().collect::<Vec<_>>() );
+                BotWeights( file )
+            }
+            Err(_) => BotWeights::default(),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load() -> BotWeights {
+        BotWeights::default()
+    }
+
+    pub fn for_seat( &self, pinfo : &PlayerStuff ) -> EvalWeights {
+        self.0.for_bot( BOT_NAMES[ (pinfo.bot_profile.rem_euclid( BOT_NAMES.len() as i32 )) as usize ] )
+    }
+}
+
+// Opening moves from self-play, shared by every bot
+#[derive(Resource, Default)]
+pub struct BotBook( pub OpeningBook );
+
+impl BotBook {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load() -> BotBook {
+        match OpeningBook::load( BOOK_FILE ) {
+            Ok(book) => {
+                println!("Loaded opening book with {} positions", book.len() );
+                BotBook( book )
+            }
+            Err(_) => BotBook::default(),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load() -> BotBook {
+        BotBook::default()
+    }
+}
+
+pub struct ProfilesPlugin;
+
+impl Plugin for ProfilesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource( ProfileBook::load() )
+            .insert_resource( BotWeights::load() )
+            .insert_resource( BotBook::load() )
+            .add_systems(Update, record_match_results.run_if(in_state(GameAppState::Gameplay)));
+    }
+}
+
+// Credit every human seat with the finished match
+fn record_match_results(
+    stuff: Res<GoodStuff>,
+    game: Res<SummonGame>,
+    mut book: ResMut<ProfileBook>,
+    puzzle: Res<ActivePuzzle>,
+    mut ev_gameover: EventReader<GameOver>,
+)
+{
+    // Puzzles don't count towards the standings
+    if ev_gameover.read().count() == 0 || puzzle.puzzle.is_some() {
+        return;
+    }
+
+    let seats = (game.player_count as usize).min( 4 );
+    let leaders = game.leaders();
+
+    for (pnum, pinfo) in stuff.player_stuff.iter().enumerate() {
+        if pinfo.ptype != PlayerType::Local {
+            continue;
+        }
+
+        let ndx = (pinfo.human_profile.rem_euclid( book.profiles.len() as i32 )) as usize;
+        let profile = &mut book.profiles[ ndx ];
+        profile.games_played += 1;
+        profile.games_by_seats[ seats ] += 1;
+        profile.total_score += game.snapshot.score[ pnum ].max( 0 ) as u32;
+        if leaders.len() == 1 && leaders[0] == pnum {
+            profile.wins_by_seats[ seats ] += 1;
+        }
+
+        println!("{}: {}", profile.name, profile.summary() );
+    }
+
+    book.save();
+}
@@ -0,0 +1,212 @@
+use std::collections::BTreeSet;
+
+use rand::{ rngs::StdRng, SeedableRng };
+use serde::{ Deserialize, Serialize };
+
+use crate::bitboard::{ BitMove, Bitboard };
+use crate::gamestate::DEFAULT_WEIGHTS;
+use crate::position::Position;
+use crate::ronfile::RonFile;
+use crate::selfplay::bot_move;
+
+// Single player puzzles. Each one is a position in the position.rs text
+// format, a goal for whoever has the first move, and how many moves they
+// get to reach it. The other seats play a script or the strongest bot, so a
+// puzzle plays out the same way every time.
+pub const PUZZLE_PROGRESS_FILE : &str = "summoning_puzzles.ron";
+
+// What the player needs once their moves are used up, or the game ends
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Objective {
+    Outscore { opponent : usize, margin : i32 },  // at least `margin` more spaces than a seat, 0 based
+    Smother { opponent : usize },                 // that seat has nothing left to split
+    Claim { spaces : i32 },                       // hold at least this many spaces
+}
+
+impl Objective {
+    pub fn met( &self, board : &Bitboard, player : usize ) -> bool
+    {
+        match self {
+            Objective::Outscore { opponent, margin } => board.score( player ) - board.score( *opponent ) >= *margin,
+            Objective::Smother { opponent } => board.count_moves( *opponent ) == 0,
+            Objective::Claim { spaces } => board.score( player ) >= *spaces,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Opponent {
+    Bot,
+    Scripted( Vec<(u8, u8, u8)> ),  // (src, dest, amount) in order, the bot takes over if one can't be played
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Puzzle {
+    pub name : String,
+    pub description : String,
+    pub position : String,
+    pub moves : u32,
+    pub objective : Objective,
+    pub opponent : Opponent,
+}
+
+// How a puzzle went, given the player's moves
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PuzzleOutcome {
+    Solved,
+    Failed,
+    Unfinished,  // ran out of moves to try before the puzzle was over
+    Illegal,     // one of the moves can't be played
+}
+
+impl Puzzle {
+    pub fn start( &self ) -> Result<Position, String>
+    {
+        let start = Position::from_text( &self.position )?;
+        match self.objective {
+            Objective::Outscore { opponent, .. } | Objective::Smother { opponent } if !start.players.contains( &opponent ) =>
+                Err( format!("{}: seat {} isn't playing", self.name, opponent + 1 ) ),
+            _ => Ok( start ),
+        }
+    }
+
+    // The seat the player takes, whoever moves first
+    pub fn player( &self ) -> Result<usize, String>
+    {
+        let start = self.start()?;
+        Ok( start.players[ start.turn ] )
+    }
+
+    // The reply from a computer seat. `replies` is how many moves the
+    // computer seats have made so far, to know where the script is up to.
+    pub fn opponent_move( &self, board : &Bitboard, seat : usize, player_count : i32, replies : usize ) -> Option<BitMove>
+    {
+        if let Opponent::Scripted( script ) = &self.opponent {
+            if let Some(&(src, dest, amount)) = script.get( replies ) {
+                let mv = BitMove { src, dest, amount };
+                if board.gen_moves( seat ).contains( &mv ) {
+                    return Some( mv );
+                }
+            }
+        }
+
+        // Noise of 1 never changes the order, so the bot always picks the same move
+        bot_move( &mut StdRng::seed_from_u64( 0 ), board, seat, player_count, &DEFAULT_WEIGHTS, 1 )
+    }
+
+    // Plays the puzzle through with these moves for the player
+    pub fn play( &self, moves : &[BitMove] ) -> PuzzleOutcome
+    {
+        match self.run( moves ) {
+            Ok(outcome) => outcome,
+            Err(_) => PuzzleOutcome::Unfinished,
+        }
+    }
+
+    // Like play, but when the moves run out first hands back the board the
+    // player's next move is made on
+    fn run( &self, moves : &[BitMove] ) -> Result<PuzzleOutcome, Box<Bitboard>>
+    {
+        let Ok(start) = self.start() else {
+            return Ok( PuzzleOutcome::Illegal );
+        };
+        let player = start.players[ start.turn ];
+        let mut board = Bitboard::from_snapshot( &start.snapshot );
+        let mut turn = start.turn;
+        let mut used = 0;
+        let mut replies = 0;
+        let mut passes = 0;
+
+        // Stops when everyone has passed in a row, like self-play
+        while passes < start.players.len() {
+            let seat = start.players[ turn ];
+            let mv = if seat == player {
+                if used == self.moves {
+                    break;
+                }
+                used += 1;
+                if board.count_moves( seat ) == 0 {
+                    None
+                } else {
+                    let Some(mv) = moves.get( used as usize - 1 ) else {
+                        return Err( Box::new( board ) );
+                    };
+                    if !board.gen_moves( seat ).contains( mv ) {
+                        return Ok( PuzzleOutcome::Illegal );
+                    }
+                    Some( *mv )
+                }
+            } else {
+                let mv = self.opponent_move( &board, seat, start.players.len() as i32, replies );
+                replies += mv.is_some() as usize;
+                mv
+            };
+
+            match mv {
+                Some(mv) => {
+                    board = board.apply( mv, seat );
+                    passes = 0;
+                }
+                None => passes += 1,
+            }
+
+            // Judged straight after the player's last move
+            if seat == player && used == self.moves {
+                break;
+            }
+            turn = (turn + 1) % start.players.len();
+        }
+
+        Ok( if self.objective.met( &board, player ) { PuzzleOutcome::Solved } else { PuzzleOutcome::Failed } )
+    }
+
+    // Tries every line for the player, for checking a puzzle can be done
+    pub fn solve( &self ) -> Option<Vec<BitMove>>
+    {
+        let player = self.player().ok()?;
+        let mut moves = Vec::new();
+        if self.search( player, &mut moves ) { Some( moves ) } else { None }
+    }
+
+    fn search( &self, player : usize, moves : &mut Vec<BitMove> ) -> bool
+    {
+        let board = match self.run( moves ) {
+            Ok(outcome) => return outcome == PuzzleOutcome::Solved,
+            Err(board) => board,
+        };
+
+        for mv in board.gen_moves( player ) {
+            moves.push( mv );
+            if self.search( player, moves ) {
+                return true;
+            }
+            moves.pop();
+        }
+        false
+    }
+}
+
+// Which puzzles have been solved, by name
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct PuzzleProgress {
+    pub solved : BTreeSet<String>,
+}
+
+impl PuzzleProgress {
+    pub fn is_solved( &self, puzzle : &Puzzle ) -> bool
+    {
+        self.solved.contains( &puzzle.name )
+    }
+}
+
+impl RonFile for PuzzleProgress {}
+
+// The puzzles that ship with the game
+pub fn bundled_puzzles() -> Vec<Puzzle>
+{
+    ron::from_str( include_str!( "../assets/puzzles.ron" ) ).unwrap_or_else( |err| {
+        println!("Couldn't read the bundled puzzles: {}", err );
+        Vec::new()
+    })
+}
@@ -0,0 +1,336 @@
+use bevy::prelude::*;
+
+use crate::summongame::{ playing, BotDifficulty, GameAppState, GameplayCleanup, GoodStuff, PlayerType, SummonGame, TurnAdvance };
+use crate::titlescreen::TitleScreenCleanup;
+use crate::bitboard::Bitboard;
+use crate::puzzle::{ bundled_puzzles, Puzzle, PuzzleProgress, PUZZLE_PROGRESS_FILE };
+use crate::ronfile::RonFile;
+
+// Puzzle mode. The Puzzles button on the title screen opens a list of the
+// bundled puzzles, and picking one starts a match on its position with the
+// player in the seat to move and bots in the rest. The match stops as soon
+// as the player's moves are used up, and solved puzzles get a tick.
+#[derive(Resource, Default)]
+pub struct PuzzleBook {
+    pub puzzles : Vec<Puzzle>,
+    pub progress : PuzzleProgress,
+}
+
+impl PuzzleBook {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load() -> PuzzleBook {
+        PuzzleBook {
+            puzzles: bundled_puzzles(),
+            progress: PuzzleProgress::load( PUZZLE_PROGRESS_FILE ).unwrap_or_default(),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load() -> PuzzleBook {
+        PuzzleBook { puzzles: bundled_puzzles(), ..default() }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save( &self ) {
+        self.progress.save_or_report( PUZZLE_PROGRESS_FILE );
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save( &self ) {
+    }
+}
+
+// The puzzle being played, if this match is one
+#[derive(Resource, Default)]
+pub struct ActivePuzzle {
+    pub puzzle : Option<Puzzle>,
+    pub player : usize,
+    pub moves_made : u32,
+    pub replies : usize,        // moves the bots have made, for scripted puzzles
+    pub last_seat : Option<usize>,
+    pub solved : Option<bool>,
+}
+
+impl ActivePuzzle {
+    pub fn moves_left( &self ) -> u32 {
+        self.puzzle.as_ref().map_or( 0, |puzzle| puzzle.moves.saturating_sub( self.moves_made ) )
+    }
+}
+
+#[derive(Component)]
+struct PuzzlesButton;
+
+#[derive(Component)]
+struct PuzzleSelect;
+
+#[derive(Component)]
+struct PuzzleChoice( usize );
+
+#[derive(Component)]
+struct PuzzleLabel;
+
+pub struct PuzzlesPlugin;
+
+impl Plugin for PuzzlesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource( PuzzleBook::load() )
+            .insert_resource( ActivePuzzle::default() )
+            .add_systems( OnEnter(GameAppState::TitleScreen), setup_puzzles_button )
+            .add_systems(Update, (
+                toggle_puzzle_select,
+                choose_puzzle,
+                ).run_if(in_state(GameAppState::TitleScreen)))
+            .add_systems( OnEnter(GameAppState::Gameplay), setup_puzzle_label )
+            .add_systems(Update, track_puzzle.run_if(playing));
+    }
+}
+
+fn setup_puzzles_button(
+    mut commands: Commands,
+    mut active: ResMut<ActivePuzzle>,
+)
+{
+    // Back on the title screen, so whatever comes next is a normal match
+    *active = ActivePuzzle::default();
+
+    commands.spawn((
+        ButtonBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(12.0),
+                right: Val::Px(12.0),
+                padding: UiRect::horizontal( Val::Px( 8.0 )),
+                ..default()
+            },
+            background_color: BackgroundColor( Color::rgba( 0.0, 0.0, 0.0, 0.5 ) ),
+            ..default()
+        },
+        PuzzlesButton,
+        TitleScreenCleanup,
+    ))
+    .with_children(|parent| {
+        parent.spawn(TextBundle::from_section(
+            "Puzzles",
+            TextStyle {
+                font_size: 20.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        ));
+    });
+}
+
+fn toggle_puzzle_select(
+    mut commands: Commands,
+    book: Res<PuzzleBook>,
+    button_q: Query<&Interaction, (Changed<Interaction>, With<PuzzlesButton>)>,
+    select_q: Query<Entity, With<PuzzleSelect>>,
+)
+{
+    if !button_q.iter().any( |interaction| *interaction == Interaction::Pressed ) {
+        return;
+    }
+
+    // Second press closes it
+    if let Ok(entity) = select_q.get_single() {
+        commands.entity(entity).despawn_recursive();
+        return;
+    }
+
+    commands.spawn(( NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(44.0),
+            right: Val::Px(12.0),
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(6.0),
+            padding: UiRect::all( Val::Px( 10.0 )),
+            ..default()
+        },
+        background_color: BackgroundColor( Color::rgba( 0.0, 0.0, 0.0, 0.8 ) ),
+        z_index: ZIndex::Global( 10 ),
+        ..default()
+    }, PuzzleSelect, TitleScreenCleanup ))
+    .with_children( |parent| {
+        for (ndx, puzzle) in book.puzzles.iter().enumerate() {
+            let solved = book.progress.is_solved( puzzle );
+            parent.spawn((
+                ButtonBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::all( Val::Px( 4.0 )),
+                        ..default()
+                    },
+                    background_color: BackgroundColor( Color::rgba( 1.0, 1.0, 1.0, 0.05 ) ),
+                    ..default()
+                },
+                PuzzleChoice( ndx ),
+            ))
+            .with_children( |parent| {
+                parent.spawn(TextBundle::from_section(
+                    format!("{} {}", if solved { "[x]" } else { "[ ]" }, puzzle.name ),
+                    TextStyle {
+                        font_size: 20.0,
+                        color: if solved { Color::rgb( 0.5, 1.0, 0.5 ) } else { Color::WHITE },
+                        ..default()
+                    },
+                ));
+                parent.spawn(TextBundle::from_section(
+                    puzzle.description.clone(),
+                    TextStyle {
+                        font_size: 14.0,
+                        color: Color::rgb( 0.7, 0.7, 0.7 ),
+                        ..default()
+                    },
+                ));
+            });
+        }
+
+        parent.spawn(TextBundle::from_section(
+            format!("{} of {} solved", book.puzzles.iter().filter( |p| book.progress.is_solved( p ) ).count(), book.puzzles.len() ),
+            TextStyle {
+                font_size: 14.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        ));
+    });
+}
+
+// Seats the player and the bots the way the puzzle wants and starts it.
+// The title screen puts the saved seats back afterwards.
+fn choose_puzzle(
+    book: Res<PuzzleBook>,
+    mut stuff: ResMut<GoodStuff>,
+    mut active: ResMut<ActivePuzzle>,
+    mut game_state: ResMut<NextState<GameAppState>>,
+    choice_q: Query<(&Interaction, &PuzzleChoice), Changed<Interaction>>,
+)
+{
+    for (interaction, choice) in &choice_q {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let puzzle = &book.puzzles[ choice.0 ];
+        let start = match puzzle.start() {
+            Ok(start) => start,
+            Err(err) => {
+                println!("Puzzle {} is broken: {}", puzzle.name, err );
+                continue;
+            }
+        };
+        let player = start.players[ start.turn ];
+
+        for (pnum, pinfo) in stuff.player_stuff.iter_mut().enumerate() {
+            pinfo.ptype = if pnum == player {
+                PlayerType::Local
+            } else if start.players.contains( &pnum ) {
+                PlayerType::AI
+            } else {
+                PlayerType::NotActive
+            };
+            pinfo.bot_difficulty = BotDifficulty::Hard;
+        }
+
+        println!("Starting puzzle {}", puzzle.name );
+        *active = ActivePuzzle { puzzle: Some( puzzle.clone() ), player, ..default() };
+        game_state.set( GameAppState::Gameplay );
+    }
+}
+
+fn setup_puzzle_label(
+    mut commands: Commands,
+    mut active: ResMut<ActivePuzzle>,
+)
+{
+    // Restarting goes through here too, so start the count over
+    let Some(puzzle) = active.puzzle.clone() else {
+        return;
+    };
+    active.moves_made = 0;
+    active.replies = 0;
+    active.last_seat = None;
+    active.solved = None;
+
+    commands.spawn((
+        TextBundle::from_section( puzzle_text( &puzzle, &active ),
+            TextStyle {
+                font_size: 24.,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(44.0),
+            right: Val::Px(12.0),
+            ..default()
+        }),
+        PuzzleLabel,
+        GameplayCleanup,
+    ));
+}
+
+fn puzzle_text( puzzle : &Puzzle, active : &ActivePuzzle ) -> String {
+    match active.solved {
+        Some(true) => format!("{}: Solved!", puzzle.name ),
+        Some(false) => format!("{}: Not quite. Esc, then Restart to try again.", puzzle.name ),
+        None => format!("{}: {}\nMoves left: {}", puzzle.name, puzzle.description, active.moves_left() ),
+    }
+}
+
+// Counts the player's moves and checks the objective once they're used up,
+// or when the match ends early because nobody can move
+fn track_puzzle(
+    mut book: ResMut<PuzzleBook>,
+    mut active: ResMut<ActivePuzzle>,
+    mut game: ResMut<SummonGame>,
+    mut label_q: Query<&mut Text, With<PuzzleLabel>>,
+    mut ev_turn: EventReader<TurnAdvance>,
+)
+{
+    let Some(puzzle) = active.puzzle.clone() else {
+        ev_turn.clear();
+        return;
+    };
+
+    let mut changed = false;
+    for ev in ev_turn.read() {
+        changed = true;
+        if active.solved.is_some() {
+            continue;
+        }
+
+        if game.turn_num > 0 && active.last_seat == Some( active.player ) {
+            active.moves_made += 1;
+        }
+        active.last_seat = Some( ev.0 as usize );
+
+        if active.moves_left() == 0 || game.game_over {
+            let solved = puzzle.objective.met( &Bitboard::from_snapshot( &game.snapshot ), active.player );
+            println!("Puzzle {} {}", puzzle.name, if solved { "solved" } else { "failed" } );
+            active.solved = Some( solved );
+
+            // Nothing more to play, this also stops the bots
+            game.game_over = true;
+            if solved && book.progress.solved.insert( puzzle.name.clone() ) {
+                book.save();
+            }
+        }
+    }
+
+    if !changed {
+        return;
+    }
+
+    for mut text in &mut label_q {
+        text.sections[0].value = puzzle_text( &puzzle, &active );
+        text.sections[0].style.color = match active.solved {
+            Some(true) => Color::rgb( 0.5, 1.0, 0.5 ),
+            Some(false) => Color::rgb( 1.0, 0.4, 0.4 ),
+            None => Color::WHITE,
+        };
+    }
+}
@@ -0,0 +1,42 @@
+use serde::{ de::DeserializeOwned, Serialize };
+
+// Reading and writing the small RON files the tools and the game share,
+// weights, the opening book and puzzle progress. Anything that serializes
+// gets these by saying `impl RonFile for Thing {}`.
+pub trait RonFile : Serialize + DeserializeOwned {
+    fn from_text( text : &str ) -> Result<Self, String>
+    {
+        ron::from_str( text ).map_err( |err| err.to_string() )
+    }
+
+    fn to_text( &self ) -> Result<String, String>
+    {
+        ron::ser::to_string_pretty( self, ron::ser::PrettyConfig::default() ).map_err( |err| err.to_string() )
+    }
+
+    fn load( path : &str ) -> Result<Self, String>
+    {
+        let text = std::fs::read_to_string( path ).map_err( |err| err.to_string() )?;
+        Self::from_text( &text )
+    }
+
+    fn save( &self, path : &str ) -> Result<(), String>
+    {
+        std::fs::write( path, self.to_text()? ).map_err( |err| err.to_string() )
+    }
+
+    // For the files the game keeps next to itself. Not having one yet is
+    // fine, one that won't read or write is reported and left alone.
+    fn load_or_report( path : &str ) -> Option<Self>
+    {
+        let text = std::fs::read_to_string( path ).ok()?;
+        Self::from_text( &text ).map_err( |err| println!("Couldn't read {}: {}", path, err ) ).ok()
+    }
+
+    fn save_or_report( &self, path : &str )
+    {
+        if let Err(err) = self.save( path ) {
+            println!("Couldn't save {}: {}", path, err );
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use rand::Rng;
+
+use crate::bitboard::{ BitMove, Bitboard };
+use crate::gamestate::*;
+
+// Headless games between bots, for the tuner and for testing. The game's own
+// bots pick their moves with bot_move too, so what gets tuned is what plays.
+
+// The best rated move with some random noise on top, higher noise is an easier
+// bot. Starts from a random move so ties don't always go the same way.
+pub fn bot_move<R: Rng + ?Sized>( rng : &mut R, board : &Bitboard, for_player : usize, player_count : i32,
+                                  weights : &EvalWeights, noise : i32 ) -> Option<BitMove>
+{
+    let moves = board.gen_moves( for_player );
+    if moves.is_empty() {
+        return None;
+    }
+
+    let mut chosen = moves[ rng.gen_range( 0..moves.len() ) ];
+    let mut curr_strength : i32 = -1000000000;
+    for curr_move in moves {
+        let plyr_evals = board.apply( curr_move, for_player ).evaluate_with( weights );
+        let mut new_str : i32 = rng.gen_range( 0..noise.max( 1 ) );
+        new_str += rate_position( plyr_evals, for_player, player_count );
+        if new_str > curr_strength {
+            curr_strength = new_str;
+            chosen = curr_move;
+        }
+    }
+
+    Some( chosen )
+}
+
+#[derive(Clone, Debug)]
+pub struct SelfPlayResult {
+    pub scores : [i32; 4],
+    pub moves : u32,
+    pub history : Vec<(usize, BitMove)>,  // who moved and what, passes left out
+}
+
+impl SelfPlayResult {
+    // Seats tied for the most spaces, like SummonGame::leaders
+    pub fn winners( &self, players : &[usize] ) -> Vec<usize>
+    {
+        let best = players.iter().map( |p| self.scores[ *p ] ).max().unwrap_or( 0 );
+        players.iter().copied().filter( |p| best > 0 && self.scores[ *p ] == best ).collect()
+    }
+}
+
+// Plays until nobody can move. Players take turns in order starting with the
+// first, each with their own weights.
+pub fn play_game<R: Rng + ?Sized>( rng : &mut R, start : &GameSnapshot, players : &[usize],
+                                   weights : &[EvalWeights; 4], noise : i32 ) -> SelfPlayResult
+{
+    let mut board = Bitboard::from_snapshot( start );
+    let mut turn = 0;
+    let mut passes = 0;
+    let mut history = Vec::new();
+
+    // Stuck players never get moves back, so once everyone passes in a row it's over
+    while passes < players.len() {
+        let player = players[ turn ];
+        match bot_move( rng, &board, player, players.len() as i32, &weights[ player ], noise ) {
+            Some(mv) => {
+                board = board.apply( mv, player );
+                history.push( (player, mv) );
+                passes = 0;
+            }
+            None => passes += 1,
+        }
+        turn = (turn + 1) % players.len();
+    }
+
+    let mut scores = [ 0; 4 ];
+    for (player, score) in scores.iter_mut().enumerate() {
+        *score = board.score( player );
+    }
+    SelfPlayResult { scores, moves: history.len() as u32, history }
+}
@@ -0,0 +1,244 @@
+use bevy::{ prelude::*, window::{ PrimaryWindow, WindowResized } };
+use serde::{ Deserialize, Serialize };
+
+use crate::summongame::{ BotDifficulty, ClockMode, GameSpeed, GoodStuff, MatchSettings, PlayerType, TimeoutPenalty, AI_DELAYS, MIN_START_POWER, MAX_START_POWER };
+use crate::profiles::BOT_NAMES;
+use crate::audio::AudioSettings;
+use crate::colors::{ ColorSettings, Palette };
+use crate::puzzles::ActivePuzzle;
+use crate::campaign::ActiveCampaign;
+use crate::mapfile::{ MapChoice, MapFile, MAPS_DIR };
+use crate::ronfile::RonFile;
+
+// Settings that survive between launches, kept in a small RON file next
+// to the game. The web build has nowhere to write so it always starts fresh.
+pub const SETTINGS_FILE : &str = "summoning_settings.ron";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct SavedSeat {
+    pub ptype : PlayerType,
+    pub human_profile : i32,
+    pub bot_profile : i32,
+    pub bot_difficulty : BotDifficulty,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct SavedSettings {
+    pub seats : Vec<SavedSeat>,
+    pub start_power : u8,
+    pub require_human : bool,
+    pub clock : ClockMode,
+    pub timeout_penalty : TimeoutPenalty,
+    pub map : Option<String>,  // name of a saved board in MAPS_DIR
+    pub map_pool : bool,       // or a random one of them each match
+    pub ai_delay : f32,
+    pub instant_bots : bool,
+    pub music_volume : f32,
+    pub sfx_volume : f32,
+    pub muted : bool,
+    pub window_size : Option<(f32, f32)>,
+    pub palette : Palette,
+    pub seat_colors : [usize; 4],
+    pub markers : bool,
+}
+
+impl Default for SavedSettings {
+    fn default() -> SavedSettings {
+        let audio = AudioSettings::default();
+        let colors = ColorSettings::default();
+        let seat = |ptype| SavedSeat { ptype, human_profile: 0, bot_profile: 0, bot_difficulty: BotDifficulty::Normal };
+        SavedSettings {
+            seats: vec![ seat( PlayerType::Local ), seat( PlayerType::AI ), seat( PlayerType::AI ), seat( PlayerType::NotActive ) ],
+            start_power: MatchSettings::default().start_power,
+            require_human: MatchSettings::default().require_human,
+            clock: MatchSettings::default().clock,
+            timeout_penalty: MatchSettings::default().timeout_penalty,
+            map: None,
+            map_pool: false,
+            ai_delay: GameSpeed::default().ai_delay,
+            instant_bots: GameSpeed::default().instant_bots,
+            music_volume: audio.music_volume,
+            sfx_volume: audio.sfx_volume,
+            muted: audio.muted,
+            window_size: None,
+            palette: colors.palette,
+            seat_colors: colors.seat_swatch,
+            markers: colors.markers,
+        }
+    }
+}
+
+impl RonFile for SavedSettings {}
+
+impl SavedSettings {
+    pub fn capture( stuff : &GoodStuff, match_settings : &MatchSettings, speed : &GameSpeed, audio : &AudioSettings, colors : &ColorSettings, window : Option<&Window> ) -> SavedSettings {
+        SavedSettings {
+            seats: stuff.player_stuff.iter().map( |pinfo| SavedSeat {
+                ptype: pinfo.ptype,
+                human_profile: pinfo.human_profile,
+                bot_profile: pinfo.bot_profile,
+                bot_difficulty: pinfo.bot_difficulty,
+            }).collect(),
+            start_power: match_settings.start_power,
+            require_human: match_settings.require_human,
+            clock: match_settings.clock,
+            timeout_penalty: match_settings.timeout_penalty,
+            map: match &match_settings.map {
+                MapChoice::Saved( map ) => Some( map.name.clone() ),
+                _ => None,
+            },
+            map_pool: matches!( match_settings.map, MapChoice::Pool(_) ),
+            ai_delay: speed.ai_delay,
+            instant_bots: speed.instant_bots,
+            music_volume: audio.music_volume,
+            sfx_volume: audio.sfx_volume,
+            muted: audio.muted,
+            window_size: window.map( |w| (w.resolution.width(), w.resolution.height()) ),
+            palette: colors.palette,
+            seat_colors: colors.seat_swatch,
+            markers: colors.markers,
+        }
+    }
+
+    pub fn apply( &self, stuff : &mut GoodStuff, match_settings : &mut MatchSettings, speed : &mut GameSpeed, audio : &mut AudioSettings, colors : &mut ColorSettings, window : Option<Mut<Window>> ) {
+        for (pinfo, seat) in stuff.player_stuff.iter_mut().zip( &self.seats ) {
+            pinfo.ptype = seat.ptype;
+            pinfo.human_profile = seat.human_profile;
+            // Hand edited files can have anything in them
+            pinfo.bot_profile = seat.bot_profile.rem_euclid( BOT_NAMES.len() as i32 );
+            pinfo.bot_difficulty = seat.bot_difficulty;
+        }
+
+        match_settings.start_power = self.start_power.clamp( MIN_START_POWER, MAX_START_POWER );
+        match_settings.require_human = self.require_human;
+        match_settings.clock = self.clock;
+        match_settings.timeout_penalty = self.timeout_penalty;
+        match_settings.map = match &self.map {
+            _ if self.map_pool => MapChoice::Pool( Vec::new() ),  // the title screen fills it in
+            Some(name) => match MapFile::load( &MapFile::path( MAPS_DIR, name ) ) {
+                Ok(map) => MapChoice::Saved( Box::new( map ) ),
+                Err(err) => {
+                    println!("Couldn't load map {}: {}", name, err );
+                    MapChoice::Generated
+                }
+            },
+            None => MapChoice::Generated,
+        };
+        speed.ai_delay = nearest_delay( self.ai_delay );
+        speed.instant_bots = self.instant_bots;
+        audio.music_volume = unit_volume( self.music_volume, AudioSettings::default().music_volume );
+        audio.sfx_volume = unit_volume( self.sfx_volume, AudioSettings::default().sfx_volume );
+        audio.muted = self.muted;
+
+        colors.palette = self.palette;
+        colors.seat_swatch = self.seat_colors;
+        colors.markers = self.markers;
+        colors.apply( stuff );
+
+        if let (Some(mut window), Some((width, height))) = (window, self.window_size) {
+            if width.is_finite() && height.is_finite() && width > 0.0 && height > 0.0 {
+                window.resolution.set( width, height );
+            }
+        }
+    }
+}
+
+// One of the delays the title screen steps through. Anything else, like a
+// negative or NaN delay, came from a hand edited file.
+fn nearest_delay( delay : f32 ) -> f32 {
+    if !delay.is_finite() {
+        return GameSpeed::default().ai_delay;
+    }
+    AI_DELAYS.iter().copied()
+        .min_by( |a, b| (a - delay).abs().total_cmp( &(b - delay).abs() ) )
+        .unwrap_or( GameSpeed::default().ai_delay )
+}
+
+// Volumes go from silent to full, anything else came from a hand edited file
+fn unit_volume( volume : f32, default : f32 ) -> f32 {
+    if volume.is_finite() { volume.clamp( 0.0, 1.0 ) } else { default }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_settings() -> Option<SavedSettings> {
+    SavedSettings::load_or_report( SETTINGS_FILE )
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load_settings() -> Option<SavedSettings> {
+    None
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_settings( saved : &SavedSettings ) {
+    saved.save_or_report( SETTINGS_FILE );
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save_settings( _saved : &SavedSettings ) {
+}
+
+// Seconds the window has to keep its size before it's saved, so dragging
+// the window edge writes the file once at the end
+const RESIZE_SAVE_DELAY : f32 = 0.5;
+
+// What was last written, so we only touch the file when something changed
+#[derive(Resource, Default)]
+pub struct SettingsFile {
+    pub last_saved : Option<SavedSettings>,
+    resized_at : Option<f32>,
+}
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource( SettingsFile::default() )
+            .add_systems( PostUpdate, save_settings_on_change );
+    }
+}
+
+fn save_settings_on_change(
+    stuff: Res<GoodStuff>,
+    match_settings: Res<MatchSettings>,
+    speed: Res<GameSpeed>,
+    audio: Res<AudioSettings>,
+    colors: Res<ColorSettings>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    puzzle: Res<ActivePuzzle>,
+    campaign: Res<ActiveCampaign>,
+    time: Res<Time>,
+    mut ev_resize: EventReader<WindowResized>,
+    mut settings_file: ResMut<SettingsFile>,
+)
+{
+    if ev_resize.read().count() > 0 {
+        settings_file.resized_at = Some( time.elapsed_seconds() );
+    }
+
+    // Nothing to compare against until the title screen has loaded them,
+    // and puzzles and the campaign set up their own seats that shouldn't be kept
+    if settings_file.last_saved.is_none() || puzzle.puzzle.is_some() || campaign.active {
+        return;
+    }
+
+    let resize_done = match settings_file.resized_at {
+        Some(at) if time.elapsed_seconds() - at < RESIZE_SAVE_DELAY => return,
+        Some(_) => true,
+        None => false,
+    };
+    let changed = stuff.is_changed() || match_settings.is_changed() || speed.is_changed() || audio.is_changed() || colors.is_changed();
+    if !changed && !resize_done {
+        return;
+    }
+    settings_file.resized_at = None;
+
+    let current = SavedSettings::capture( &stuff, &match_settings, &speed, &audio, &colors, window_q.get_single().ok() );
+    if settings_file.last_saved.as_ref() != Some( &current ) {
+        println!("Saving settings to {}", SETTINGS_FILE );
+        save_settings( &current );
+        settings_file.last_saved = Some( current );
+    }
+}
@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+
+use crate::summongame::{ playing, GameAppState, GameSpeed, GameplayCleanup, FAST_FORWARD_SCALE };
+
+// F toggles fast forward during a match, handy for watching bots play
+#[derive(Component)]
+struct FastForwardLabel;
+
+pub struct SpeedPlugin;
+
+impl Plugin for SpeedPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems( OnEnter(GameAppState::Gameplay), setup_speed_label )
+            .add_systems(Update, toggle_fast_forward.run_if(playing));
+    }
+}
+
+fn setup_speed_label(
+    mut commands: Commands,
+    speed: Res<GameSpeed>,
+)
+{
+    commands.spawn((
+        TextBundle::from_section( fast_forward_text( &speed ),
+            TextStyle {
+                font_size: 24.,
+                color: Color::YELLOW,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            right: Val::Px(12.0),
+            ..default()
+        }),
+        FastForwardLabel,
+        GameplayCleanup,
+    ));
+}
+
+fn fast_forward_text( speed : &GameSpeed ) -> String {
+    if speed.fast_forward {
+        format!(">> x{}", FAST_FORWARD_SCALE )
+    } else {
+        "".into()
+    }
+}
+
+fn toggle_fast_forward(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut speed: ResMut<GameSpeed>,
+    mut label_q: Query<&mut Text, With<FastForwardLabel>>,
+)
+{
+    if !keyboard_input.just_pressed( KeyCode::KeyF ) {
+        return;
+    }
+
+    speed.fast_forward = !speed.fast_forward;
+    println!("Fast forward {}", if speed.fast_forward { "on" } else { "off" } );
+
+    for mut text in &mut label_q {
+        text.sections[0].value = fast_forward_text( &speed );
+    }
+}
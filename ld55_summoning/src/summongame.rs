@@ -1,7 +1,9 @@
 use bevy::prelude::*;
+use serde::{ Deserialize, Serialize };
 
 
 use crate::gamestate::{GameSnapshot, INVALID};
+use crate::mapfile::MapChoice;
 
 // Global State of the game
 #[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
@@ -9,9 +11,31 @@ pub enum GameAppState {
     #[default]
     TitleScreen,
     Gameplay,
+    Restarting,  // passes straight back to Gameplay so the match gets torn down and rebuilt
+    Campaign,    // the list of campaign stages
+    Editor,      // making boards by hand
 }
 
-#[derive(Default, PartialEq, Debug)]
+// Only means anything during Gameplay. Kept separate so pausing doesn't
+// run the gameplay OnExit/OnEnter.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
+pub enum PauseState {
+    #[default]
+    Running,
+    Paused,
+}
+
+// Run condition for the systems that drive a match
+pub fn playing( app_state: Res<State<GameAppState>>, pause_state: Res<State<PauseState>> ) -> bool {
+    *app_state.get() == GameAppState::Gameplay && *pause_state.get() == PauseState::Running
+}
+
+// Everything spawned for a match gets this so it can be cleared out
+// when leaving gameplay
+#[derive(Component)]
+pub struct GameplayCleanup;
+
+#[derive(Default, PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum PlayerType {
     Local,
     AI,
@@ -19,18 +43,59 @@ pub enum PlayerType {
     NotActive
 }
 
+// How much randomness the bot mixes into its choice
+#[derive(Default, PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum BotDifficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl BotDifficulty {
+    pub fn next( &self ) -> BotDifficulty {
+        match self {
+            BotDifficulty::Easy => BotDifficulty::Normal,
+            BotDifficulty::Normal => BotDifficulty::Hard,
+            BotDifficulty::Hard => BotDifficulty::Easy,
+        }
+    }
+
+    // Random noise added to each move rating, 10000 is about one space
+    pub fn noise( &self ) -> i32 {
+        match self {
+            BotDifficulty::Easy => 150000,
+            BotDifficulty::Normal => 1000,
+            BotDifficulty::Hard => 1,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct PlayerStuff
 {
     pub color: Color,
     pub color2 : Color,
-    pub ring_mtl: [ Handle<StandardMaterial>; 21 ],
+    pub ring_mtl: Vec<Handle<StandardMaterial>>, // by power, starting at 1
     pub ptype : PlayerType,
     pub bot_profile : i32,
+    pub bot_difficulty : BotDifficulty,
     pub human_profile : i32,
     pub out_of_moves : bool,
 }
 
+impl PlayerStuff {
+    // Ring look for a stack. Powers past the last ring texture reuse it
+    // and get a number drawn over them instead.
+    pub fn ring_mtl_for( &self, power : u8 ) -> Handle<StandardMaterial> {
+        let ndx = (power as usize).clamp( 1, self.ring_mtl.len() ) - 1;
+        self.ring_mtl[ ndx ].clone()
+    }
+}
+
+// How many RingGen textures there are in assets/tx_rings
+pub const RING_TEX_COUNT : usize = 20;
+
 // Resource  stuff
 #[derive(Resource,Default)]
 pub struct GoodStuff {
@@ -47,6 +112,27 @@ pub enum GameStateChanged {
 #[derive(Event)]
 pub struct TurnAdvance(pub i32);
 
+// Sent when a ring sliding across the board reaches its space
+#[derive(Event)]
+pub struct CircleLanded(pub i32);
+
+// Sent when every ring animation on the board has finished
+#[derive(Event)]
+pub struct AnimationComplete;
+
+#[derive(Resource, Default)]
+pub struct BoardAnimation {
+    pub busy : bool,
+}
+
+// Sent when nobody has a move left
+#[derive(Event)]
+pub struct GameOver;
+
+// Sent once the board for a new game is laid out
+#[derive(Event)]
+pub struct MapBuilt;
+
 
 #[derive(Component)]
 pub struct Ground;
@@ -60,6 +146,136 @@ pub struct MapSpaceVisual
 
 pub const HEX_SZ : f32 = 1.0;
 
+// Optional clock for human seats. Per turn resets every turn, total game
+// is a chess clock that only counts down.
+#[derive(Default, PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum ClockMode {
+    #[default]
+    Off,
+    PerTurn(u32),    // seconds
+    TotalGame(u32),
+}
+
+impl ClockMode {
+    // Steps through a few presets
+    pub fn next( &self ) -> ClockMode {
+        match self {
+            ClockMode::Off => ClockMode::PerTurn( 15 ),
+            ClockMode::PerTurn( 15 ) => ClockMode::PerTurn( 30 ),
+            ClockMode::PerTurn( 30 ) => ClockMode::PerTurn( 60 ),
+            ClockMode::PerTurn( _ ) => ClockMode::TotalGame( 180 ),
+            ClockMode::TotalGame( 180 ) => ClockMode::TotalGame( 300 ),
+            ClockMode::TotalGame( 300 ) => ClockMode::TotalGame( 600 ),
+            ClockMode::TotalGame( _ ) => ClockMode::Off,
+        }
+    }
+
+    pub fn seconds( &self ) -> Option<f32> {
+        match self {
+            ClockMode::Off => None,
+            ClockMode::PerTurn( secs ) | ClockMode::TotalGame( secs ) => Some( *secs as f32 ),
+        }
+    }
+
+    pub fn label( &self ) -> String {
+        match self {
+            ClockMode::Off => "Off".into(),
+            ClockMode::PerTurn( secs ) => format!("{}s / turn", secs ),
+            ClockMode::TotalGame( secs ) => format!("{} min game", secs / 60 ),
+        }
+    }
+}
+
+// What happens when a human's clock runs out
+#[derive(Default, PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum TimeoutPenalty {
+    #[default]
+    AutoPass,
+    AutoMove,  // the bot plays for them
+}
+
+impl TimeoutPenalty {
+    pub fn next( &self ) -> TimeoutPenalty {
+        match self {
+            TimeoutPenalty::AutoPass => TimeoutPenalty::AutoMove,
+            TimeoutPenalty::AutoMove => TimeoutPenalty::AutoPass,
+        }
+    }
+
+    pub fn label( &self ) -> &'static str {
+        match self {
+            TimeoutPenalty::AutoPass => "Pass",
+            TimeoutPenalty::AutoMove => "Bot Move",
+        }
+    }
+}
+
+// How fast bots move and the board animates
+#[derive(Resource)]
+pub struct GameSpeed {
+    pub ai_delay : f32,      // seconds a bot waits before moving, and before a forced pass
+    pub instant_bots : bool, // no waiting at all when nobody human is playing
+    pub fast_forward : bool,
+}
+
+pub const AI_DELAYS : [f32; 5] = [ 0.1, 0.25, 0.5, 1.0, 2.0 ];
+pub const FAST_FORWARD_SCALE : f32 = 4.0;
+
+impl Default for GameSpeed {
+    fn default() -> GameSpeed {
+        GameSpeed {
+            ai_delay: 1.0,
+            instant_bots: false,
+            fast_forward: false,
+        }
+    }
+}
+
+impl GameSpeed {
+    pub fn next_delay( &mut self ) {
+        let ndx = AI_DELAYS.iter().position( |d| *d >= self.ai_delay ).unwrap_or( 0 );
+        self.ai_delay = AI_DELAYS[ (ndx + 1) % AI_DELAYS.len() ];
+    }
+
+    pub fn time_scale( &self ) -> f32 {
+        if self.fast_forward { FAST_FORWARD_SCALE } else { 1.0 }
+    }
+
+    // Give the board a moment to appear before the first move
+    pub fn first_turn_delay( &self ) -> f32 {
+        self.ai_delay * 3.0
+    }
+
+    pub fn instant( &self, stuff : &GoodStuff ) -> bool {
+        self.instant_bots && !stuff.player_stuff.iter().any( |p| p.ptype == PlayerType::Local )
+    }
+}
+
+// Rules picked on the title screen before a match
+#[derive(Resource)]
+pub struct MatchSettings {
+    pub start_power : u8,
+    pub require_human : bool,  // don't allow bot-only matches
+    pub clock : ClockMode,
+    pub timeout_penalty : TimeoutPenalty,
+    pub map : MapChoice,
+}
+
+pub const MIN_START_POWER : u8 = 2;
+pub const MAX_START_POWER : u8 = 99;
+
+impl Default for MatchSettings {
+    fn default() -> MatchSettings {
+        MatchSettings {
+            start_power: 16,
+            require_human: false,
+            clock: ClockMode::Off,
+            timeout_penalty: TimeoutPenalty::AutoPass,
+            map: MapChoice::Generated,
+        }
+    }
+}
+
 // FIXME: this should be a singleton component and not a resource
 #[derive(Resource)]
 pub struct SummonGame {
@@ -68,7 +284,18 @@ pub struct SummonGame {
     pub map_visuals: Vec<Entity>,
     pub player_count : i32,
     pub player_turn : i32,
-    pub turn_num : i32
+    pub turn_num : i32,
+    pub game_over : bool,
+    pub conceded : [bool; 4],
+    pub clocks : [f32; 4],  // seconds left for each seat, when there's a clock
+}
+
+impl SummonGame {
+    // Players tied for the most spaces. Anyone who conceded can't win.
+    pub fn leaders( &self ) -> Vec<usize> {
+        let best = (0..4).filter( |p| !self.conceded[*p] ).map( |p| self.snapshot.score[p] ).max().unwrap_or( 0 );
+        (0..4).filter( |p| !self.conceded[*p] && best > 0 && self.snapshot.score[*p] == best ).collect()
+    }
 }
 
 impl Default for SummonGame {
@@ -79,6 +306,9 @@ impl Default for SummonGame {
             player_count: 0,
             player_turn: 0,
             turn_num: 0,
+            game_over: false,
+            conceded: [false; 4],
+            clocks: [0.0; 4],
         }
     }
 }
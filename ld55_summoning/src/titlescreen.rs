@@ -1,5 +1,11 @@
-use bevy::prelude::* ;
-use crate::summongame::{ GameAppState, PlayerType, GoodStuff };
+use bevy::{ prelude::*, window::PrimaryWindow };
+use crate::summongame::{ GameAppState, GameSpeed, PlayerType, GoodStuff, MatchSettings, MIN_START_POWER, MAX_START_POWER };
+use crate::settings::{ load_settings, SettingsFile };
+use crate::audio::AudioSettings;
+use crate::profiles::ProfileBook;
+use crate::colors::ColorSettings;
+use crate::mapfile::{ list_maps, MapChoice, MAPS_DIR };
+use crate::mappool::{ MapChosen, ToggleMapPicker };
 
 #[derive(Component)]
 pub struct TitleScreenCleanup;
@@ -13,6 +19,16 @@ struct PlayerSetting
 #[derive(Component)]
 struct ProfilePic;
 
+#[derive(Component)]
+struct PlayerNameLabel;
+
+// Parts of a player tile drawn in the seat's color
+#[derive(Component)]
+struct SeatTint
+{
+    pnum : i32,
+}
+
 #[derive(Event)]
 struct PlayerSettingsChanged;
 
@@ -21,13 +37,130 @@ struct PlayerSettingsChanged;
 enum PlayerSettingsButtonAction {
     ChangeProfile(i32),
     ChangeMode(i32),
+    ChangeDifficulty,
+}
+
+// Clicking the swatch steps the seat to the next free color
+#[derive(Component)]
+struct ColorSwatch;
+
+
+#[derive(Component)]
+struct StartPowerLabel;
+
+#[derive(Component, Clone, Copy)]
+enum OptionLabel {
+    Palette,
+    Markers,
+    RequireHuman,
+    Clock,
+    TimeoutPenalty,
+    BotDelay,
+    InstantBots,
+    Map,
+    Editor,
 }
 
+fn option_label( option : OptionLabel, settings : &MatchSettings, speed : &GameSpeed, colors : &ColorSettings ) -> String {
+    match option {
+        OptionLabel::Palette => format!("Colors: {}", colors.palette.name() ),
+        OptionLabel::Markers => format!("Markers: {}", if colors.markers { "On" } else { "Off" } ),
+        OptionLabel::RequireHuman => format!("Humans: {}", if settings.require_human { "Required" } else { "Optional" } ),
+        OptionLabel::Clock => format!("Clock: {}", settings.clock.label() ),
+        OptionLabel::TimeoutPenalty => format!("Timeout: {}", settings.timeout_penalty.label() ),
+        OptionLabel::BotDelay => format!("Bot Delay: {}s", speed.ai_delay ),
+        OptionLabel::InstantBots => format!("Bots Only: {}", if speed.instant_bots { "Instant" } else { "Watch" } ),
+        OptionLabel::Map => match &settings.map {
+            MapChoice::Generated => "Map: Generated".into(),
+            MapChoice::Saved( map ) => format!("Map: {}", map.name ),
+            MapChoice::Pool( maps ) => format!("Map: Random of {}", maps.len() ),
+        },
+        OptionLabel::Editor => "Map Editor".into(),
+    }
+}
+
+#[derive(Component)]
+struct StartButton;
+
+#[derive(Component)]
+struct StartErrorLabel;
+
+// Why a match can't start with the seats as they are
+#[derive(Debug, PartialEq)]
+pub enum SeatProblem {
+    NotEnoughPlayers,
+    NoHumans,
+    BadProfile(usize),
+    SharedProfile(String),
+    MapSize(String, usize),
+    NoPoolMaps(usize),
+}
+
+impl SeatProblem {
+    pub fn message( &self ) -> String {
+        match self {
+            SeatProblem::NotEnoughPlayers => "At least two seats need a player.".into(),
+            SeatProblem::NoHumans => "At least one seat needs to be human.".into(),
+            SeatProblem::BadProfile( pnum ) => format!("Player {} has no valid profile.", pnum + 1 ),
+            SeatProblem::SharedProfile( name ) => format!("{} is seated more than once.", name ),
+            SeatProblem::MapSize( name, players ) => format!("{} is a {} player map.", name, players ),
+            SeatProblem::NoPoolMaps( players ) => format!("No saved maps for {} players.", players ),
+        }
+    }
+}
+
+pub fn validate_seats( stuff : &GoodStuff, book : &ProfileBook, settings : &MatchSettings ) -> Result<(), SeatProblem> {
+    let active = stuff.player_stuff.iter().filter( |p| p.ptype != PlayerType::NotActive ).count();
+    if active < 2 {
+        return Err( SeatProblem::NotEnoughPlayers );
+    }
+
+    match &settings.map {
+        MapChoice::Saved( map ) if map.player_count() != active => {
+            return Err( SeatProblem::MapSize( map.name.clone(), map.player_count() ) );
+        }
+        MapChoice::Pool( maps ) if MapChoice::fits( maps, active ).next().is_none() => {
+            return Err( SeatProblem::NoPoolMaps( active ) );
+        }
+        _ => {}
+    }
+
+    let humans : Vec<(usize, i32)> = stuff.player_stuff.iter().enumerate()
+        .filter( |(_, p)| p.ptype == PlayerType::Local )
+        .map( |(pnum, p)| (pnum, p.human_profile) )
+        .collect();
+
+    if settings.require_human && humans.is_empty() {
+        return Err( SeatProblem::NoHumans );
+    }
+
+    // Stats go to the profile, so each one can only sit in one seat
+    for (i, &(pnum, profile)) in humans.iter().enumerate() {
+        if profile < 0 || profile as usize >= book.profiles.len() {
+            return Err( SeatProblem::BadProfile( pnum ) );
+        }
+        if humans[ ..i ].iter().any( |&(_, other)| other == profile ) {
+            return Err( SeatProblem::SharedProfile( book.get( profile ).name.clone() ) );
+        }
+    }
+
+    Ok(())
+}
 
 // Menu Action
 #[derive(Component)]
 enum MainMenuAction {
     StartGame,
+    ChangeStartPower(i32),
+    ChangePalette,
+    ToggleMarkers,
+    ToggleRequireHuman,
+    ChangeClock,
+    ChangeTimeoutPenalty,
+    ChangeBotDelay,
+    ToggleInstantBots,
+    ChangeMap,
+    OpenEditor,
 }
 
 // Resource  stuff
@@ -55,6 +188,8 @@ impl Plugin for TitleScreenPlugin {
                 player_settings,
                 player_settings_action,
                 main_menu_action,
+                map_chosen,
+                update_start_button,
 
                 )
                 .run_if(in_state(GameAppState::TitleScreen)))
@@ -68,15 +203,42 @@ fn title_setup(
     mut commands: Commands,
     mut stuff: ResMut<GoodStuff>,
     mut title_stuff: ResMut<TitleScreenStuff>,
+    mut settings: ResMut<MatchSettings>,
+    mut speed: ResMut<GameSpeed>,
+    mut audio: ResMut<AudioSettings>,
+    mut settings_file: ResMut<SettingsFile>,
+    book: Res<ProfileBook>,
+    mut colors: ResMut<ColorSettings>,
+    mut window_q: Query<&mut Window, With<PrimaryWindow>>,
     mut ev_settings: EventWriter<PlayerSettingsChanged>,
 ) {
     println!("Title screen setup!");
 
+    // Restore the seats and options from last time, or the defaults. The
+    // file is only read on the first visit, after a match the last saved
+    // settings put back anything a puzzle or the campaign changed.
+    match settings_file.last_saved.clone() {
+        Some(saved) => saved.apply( &mut stuff, &mut settings, &mut speed, &mut audio, &mut colors, None ),
+        None => {
+            let saved = load_settings().unwrap_or_default();
+            saved.apply( &mut stuff, &mut settings, &mut speed, &mut audio, &mut colors, window_q.get_single_mut().ok() );
+            settings_file.last_saved = Some( saved );
+        }
+    }
+
+    // The editor may have saved maps since the pool was made
+    if let MapChoice::Pool( maps ) = &mut settings.map {
+        *maps = list_maps( MAPS_DIR );
+    }
+
 
     let title_img = asset_server.load("summoner_title.png");
     let playerframe_img = asset_server.load("ui_playerframe.png");
     let border_img = asset_server.load("panel-transparent-border-027.png");
 
+    // We come back here after each match, don't pile up duplicates
+    title_stuff.pics_human.clear();
+    title_stuff.pics_bot.clear();
 
     for i in 1..=5
     {
@@ -90,6 +252,12 @@ fn title_setup(
 
     title_stuff.pic_none = asset_server.load( "portrait_none.png");
 
+    // Saved seats might point past the profiles or pictures we have now
+    for pinfo in stuff.player_stuff.iter_mut() {
+        pinfo.human_profile = pinfo.human_profile.rem_euclid( book.profiles.len() as i32 );
+        pinfo.bot_profile = pinfo.bot_profile.rem_euclid( title_stuff.pics_bot.len() as i32 );
+    }
+
     let slicer = TextureSlicer {
         border: BorderRect::square(22.0),
         center_scale_mode: SliceScaleMode::Stretch,
@@ -143,7 +311,7 @@ fn title_setup(
 
                 for i in 0..4 {
 
-                    tileparent.spawn(
+                    tileparent.spawn((
 
                         ImageBundle {
                             style: Style {
@@ -157,23 +325,47 @@ fn title_setup(
                             image: UiImage::new(playerframe_img.clone() ),
                             background_color: BackgroundColor( stuff.player_stuff[i].color, ),
                             ..default()
-                        })
+                        }, SeatTint { pnum: i as i32 } ))
                         .with_children(|parent| {
 
-                            //==== Player#1 header
-                            parent.spawn(TextBundle::from_section(
-                                format!("Player {}", (i+1) ).clone(),
-                                TextStyle {
-                                    //font: asset_server.load("Cyberthrone.ttf"),
-                                    font_size: 14.0,
-                                    color: Color::rgb(1.0, 1.0, 1.0),
+                            //==== Player#1 header, with the color swatch
+                            parent.spawn( NodeBundle {
+                                style: Style {
+                                    width: Val::Px(130.0 * tile_scale),
+                                    margin: UiRect::new( Val::Px( 12.0 ), Val::Px( 8.0 ), Val::Px( 15.0 ), Val::Px( 6.0 )),
+                                    justify_content: JustifyContent::SpaceBetween,
+                                    align_items: AlignItems::Center,
                                     ..default()
                                 },
-                            ).with_style( Style {
-                                width: Val::Px(130.0 * tile_scale),
-                                margin: UiRect::new( Val::Px( 12.0 ), Val::Px( 8.0 ), Val::Px( 15.0 ), Val::Px( 6.0 )),
                                 ..default()
-                            }));
+                            }).with_children( |hdr_parent| {
+                                hdr_parent.spawn(TextBundle::from_section(
+                                    format!("Player {}", (i+1) ).clone(),
+                                    TextStyle {
+                                        //font: asset_server.load("Cyberthrone.ttf"),
+                                        font_size: 14.0,
+                                        color: Color::rgb(1.0, 1.0, 1.0),
+                                        ..default()
+                                    },
+                                ));
+
+                                hdr_parent.spawn((
+                                    ButtonBundle {
+                                        style: Style {
+                                            width: Val::Px(14.0),
+                                            height: Val::Px(14.0),
+                                            border: UiRect::all( Val::Px( 1.0 )),
+                                            ..default()
+                                        },
+                                        border_color: BorderColor( Color::WHITE ),
+                                        background_color: BackgroundColor( stuff.player_stuff[i].color ),
+                                        ..default()
+                                    },
+                                    PlayerSetting { pnum: i as i32 },
+                                    SeatTint { pnum: i as i32 },
+                                    ColorSwatch,
+                                ));
+                            });
 
                             // Profile Frame
                             parent.spawn( NodeBundle {
@@ -235,7 +427,7 @@ fn title_setup(
 
 
                             //==== PlayerName
-                            parent.spawn(TextBundle::from_section(
+                            parent.spawn((TextBundle::from_section(
                                 "Name",
                                 TextStyle {
                                     //font: asset_server.load("Cyberthrone.ttf"),
@@ -246,12 +438,39 @@ fn title_setup(
                             ).with_style( Style {
                                 width: Val::Px(130.0 * tile_scale),
                                 margin: UiRect::new( Val::Px( 40.0 ), Val::Px( 20.0 ),
-                                                        Val::Px( 6.0 ), Val::Px( 15.0 )),
+                                                        Val::Px( 6.0 ), Val::Px( 2.0 )),
                                 ..default()
-                            }));
+                            }), PlayerSetting { pnum: i as i32 }, PlayerNameLabel ));
+
+                            // Bot difficulty for AI seats, profile stats for humans
+                            parent.spawn(
+                                (ButtonBundle {
+                                    style: Style {
+                                        justify_content: JustifyContent::Center,
+                                        align_items: AlignItems::Center,
+                                        height: Val::Px(16.0),
+                                        margin: UiRect::bottom( Val::Px( 4.0 )),
+                                        ..default()
+                                    },
+                                    background_color: BackgroundColor( Color::NONE ),
+                                    ..default()
+                                },
+                                PlayerSetting{ pnum: i as i32},
+                                PlayerSettingsButtonAction::ChangeDifficulty,
+                             ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    "",
+                                    TextStyle {
+                                        font_size: 14.0,
+                                        color: stuff.player_stuff[i].color2,
+                                        ..default()
+                                    },
+                                ));
+                            });
 
                             // Human/AI/None Selection Bar
-                            parent.spawn( NodeBundle {
+                            parent.spawn(( NodeBundle {
                                 style: Style {
                                     width: Val::Percent(95.0),
                                     //flex_wrap: FlexWrap::Wrap,
@@ -261,7 +480,7 @@ fn title_setup(
                                 },
                                 background_color: BackgroundColor( stuff.player_stuff[i].color ),
                                 ..default()
-                            })
+                            }, SeatTint { pnum: i as i32 } ))
                             .with_children( |btnparent| {
 
                                 //for btn_ndx in 0..3 {
@@ -305,6 +524,135 @@ fn title_setup(
             });
 
 
+            // ---- Match Rules -----------------------
+            parent.spawn( NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }).with_children( |rules_parent| {
+
+                rules_parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(16.0),
+                            height: Val::Px(16.0),
+                            ..default()
+                        },
+                        image: asset_server.load("btn-arrow-left.png" ).into(),
+                        ..default()
+                    },
+                    MainMenuAction::ChangeStartPower( -1 ),
+                ));
+
+                rules_parent.spawn((TextBundle::from_section(
+                    format!("Start Power: {}", settings.start_power ),
+                    TextStyle {
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ), StartPowerLabel ));
+
+                rules_parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(16.0),
+                            height: Val::Px(16.0),
+                            ..default()
+                        },
+                        image: asset_server.load("btn-arrow-right.png" ).into(),
+                        ..default()
+                    },
+                    MainMenuAction::ChangeStartPower( 1 ),
+                ));
+
+                rules_parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::horizontal( Val::Px( 8.0 )),
+                            margin: UiRect::left( Val::Px( 10.0 )),
+                            ..default()
+                        },
+                        background_color: BackgroundColor( Color::rgba( 0.0, 0.0, 0.0, 0.5 ) ),
+                        ..default()
+                    },
+                    MainMenuAction::ToggleRequireHuman,
+                ))
+                .with_children(|parent| {
+                    parent.spawn((TextBundle::from_section(
+                        option_label( OptionLabel::RequireHuman, &settings, &speed, &colors ),
+                        TextStyle {
+                            font_size: 20.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ), OptionLabel::RequireHuman ));
+                });
+            });
+
+            // ---- Color and Clock Options -----------------------
+            let option_rows = [
+                [ (MainMenuAction::ChangePalette, OptionLabel::Palette),
+                  (MainMenuAction::ToggleMarkers, OptionLabel::Markers) ],
+                [ (MainMenuAction::ChangeClock, OptionLabel::Clock),
+                  (MainMenuAction::ChangeTimeoutPenalty, OptionLabel::TimeoutPenalty) ],
+                [ (MainMenuAction::ChangeBotDelay, OptionLabel::BotDelay),
+                  (MainMenuAction::ToggleInstantBots, OptionLabel::InstantBots) ],
+                [ (MainMenuAction::ChangeMap, OptionLabel::Map),
+                  (MainMenuAction::OpenEditor, OptionLabel::Editor) ],
+            ];
+            for options in option_rows {
+                parent.spawn( NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                }).with_children( |options_parent| {
+
+                    for (action, option) in options {
+                        let label = option_label( option, &settings, &speed, &colors );
+                        options_parent.spawn((
+                            ButtonBundle {
+                                style: Style {
+                                    padding: UiRect::horizontal( Val::Px( 8.0 )),
+                                    ..default()
+                                },
+                                background_color: BackgroundColor( Color::rgba( 0.0, 0.0, 0.0, 0.5 ) ),
+                                ..default()
+                            },
+                            action,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((TextBundle::from_section(
+                                label,
+                                TextStyle {
+                                    font_size: 20.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                },
+                            ), option ));
+                        });
+                    }
+                });
+            }
+
+            // ---- Why we can't start yet -----------------------
+            parent.spawn((TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 20.0,
+                    color: Color::rgb( 1.0, 0.4, 0.4 ),
+                    ..default()
+                },
+            ), StartErrorLabel ));
+
             // ---- Start Game Button -----------------------
             parent
                 .spawn((
@@ -324,6 +672,7 @@ fn title_setup(
                     },
                     ImageScaleMode::Sliced(slicer.clone()),
                     MainMenuAction::StartGame,
+                    StartButton,
                 ))
                 .with_children(|parent| {
                     parent.spawn(TextBundle::from_section(
@@ -337,12 +686,6 @@ fn title_setup(
                 });
         });
 
-    // setup player status
-    stuff.player_stuff[0].ptype = PlayerType::Local;
-    stuff.player_stuff[1].ptype = PlayerType::AI;
-    stuff.player_stuff[2].ptype = PlayerType::AI;
-    stuff.player_stuff[3].ptype = PlayerType::NotActive;
-
 
     // let mut yy = 350.0;
     // for i in 0..4 {
@@ -375,6 +718,8 @@ fn title_update (
     // mut world : &mut World,
     //mut commands: Commands,
     mut stuff: ResMut<GoodStuff>,
+    book: Res<ProfileBook>,
+    settings: Res<MatchSettings>,
     mut game_state: ResMut<NextState<GameAppState>>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
     mut ev_settings: EventWriter<PlayerSettingsChanged>,
@@ -410,19 +755,13 @@ fn title_update (
         ev_settings.send( PlayerSettingsChanged );
     }
 
-    let mut pcount : i32 = 0;
-    for i in 0..4 {
-        if stuff.player_stuff[i].ptype != PlayerType::NotActive {
-            pcount += 1;
-        }
-    }
-
     // Start game?
-    if pcount > 0 {
-        if keyboard_input.just_pressed( KeyCode::Enter ) ||
-            keyboard_input.just_pressed( KeyCode::Space )
-        {
-            game_state.set(GameAppState::Gameplay);
+    if keyboard_input.just_pressed( KeyCode::Enter ) ||
+        keyboard_input.just_pressed( KeyCode::Space )
+    {
+        match validate_seats( &stuff, &book, &settings ) {
+            Ok(()) => game_state.set(GameAppState::Gameplay),
+            Err(problem) => println!("Can't start: {}", problem.message() ),
         }
     }
 }
@@ -431,8 +770,11 @@ fn player_settings(
     stuff: Res<GoodStuff>,
     title_stuff: Res<TitleScreenStuff>,
     mut setting_q: Query<(&Children, &mut BackgroundColor, &PlayerSetting, &PlayerSettingsButtonAction)>,
+    book: Res<ProfileBook>,
     mut profile_pic_q : Query<(&PlayerSetting, &mut UiImage), With<ProfilePic>>,
-    mut text_query: Query<&mut Text>,
+    mut name_q : Query<(&PlayerSetting, &mut Text), With<PlayerNameLabel>>,
+    mut tint_q : Query<(&SeatTint, &mut BackgroundColor), Without<PlayerSettingsButtonAction>>,
+    mut text_query: Query<&mut Text, Without<PlayerNameLabel>>,
     mut ev_settings: EventReader<PlayerSettingsChanged>,
 ) {
 
@@ -463,14 +805,35 @@ fn player_settings(
                 bg.0 = btncolor;
                 text.sections[0].style.color = txtcolor;
             }
+
+            if let PlayerSettingsButtonAction::ChangeDifficulty = plr_action {
+                let mut text = text_query.get_mut(children[0]).unwrap();
+                text.sections[0].style.color = stuff.player_stuff[pndx].color2;
+                text.sections[0].value = match stuff.player_stuff[pndx].ptype {
+                    PlayerType::AI => format!("{:?} Bot", stuff.player_stuff[pndx].bot_difficulty ),
+                    PlayerType::Local => book.get( stuff.player_stuff[pndx].human_profile ).summary(),
+                    PlayerType::NotActive => "".into(),
+                };
+            }
         }
 
-        // TODO: Disable the "Start Game" button here if no players are active
+        for (name_plr, mut text) in &mut name_q {
+            text.sections[0].value = book.seat_name( &stuff.player_stuff[ name_plr.pnum as usize ] );
+            text.sections[0].style.color = stuff.player_stuff[ name_plr.pnum as usize ].color;
+        }
+
+        // Colors may have changed too
+        for (tint, mut bg) in &mut tint_q {
+            bg.0 = stuff.player_stuff[ tint.pnum as usize ].color;
+        }
 
         // Check that all settings have the right profile pic
         for (pic_plr, mut pic_img) in &mut profile_pic_q {
             let pic = match (stuff.player_stuff[ pic_plr.pnum as usize ].ptype) {
-                PlayerType::Local => &title_stuff.pics_human[ stuff.player_stuff[ pic_plr.pnum as usize ].human_profile as usize ],
+                PlayerType::Local => {
+                    let profile = book.get( stuff.player_stuff[ pic_plr.pnum as usize ].human_profile );
+                    &title_stuff.pics_human[ profile.portrait % title_stuff.pics_human.len() ]
+                },
                 PlayerType::AI => &title_stuff.pics_bot[ stuff.player_stuff[ pic_plr.pnum as usize ].bot_profile as usize ],
                 _ => &title_stuff.pic_none,
             };
@@ -487,12 +850,24 @@ fn player_settings_action(
     mut profile_pic_q : Query<(&PlayerSetting, &mut UiImage), With<ProfilePic>>,
     mut stuff: ResMut<GoodStuff>,
     title_stuff: Res<TitleScreenStuff>,
+    book: Res<ProfileBook>,
+    mut colors: ResMut<ColorSettings>,
     interaction_query: Query<
         (&Interaction, &PlayerSettingsButtonAction, &PlayerSetting),
         (Changed<Interaction>, With<Button>),
     >,
+    swatch_query: Query<(&Interaction, &PlayerSetting), (Changed<Interaction>, With<ColorSwatch>)>,
     mut ev_settings: EventWriter<PlayerSettingsChanged>,
 ) {
+    for (interaction, player) in &swatch_query {
+        if *interaction == Interaction::Pressed {
+            colors.cycle_seat( player.pnum as usize, 1 );
+            colors.apply( &mut stuff );
+            println!("Change color PLR {} to swatch {}", player.pnum, colors.seat_swatch[ player.pnum as usize ] );
+            ev_settings.send( PlayerSettingsChanged );
+        }
+    }
+
     for (interaction, menu_button_action, player) in &interaction_query {
         if *interaction == Interaction::Pressed {
             match menu_button_action {
@@ -508,13 +883,14 @@ fn player_settings_action(
                                     let mut v = stuff.player_stuff[player.pnum as usize].human_profile as i32;
                                     v += inc;
                                     if (v < 0) {
-                                        v = title_stuff.pics_human.len() as i32 - 1;
-                                    } else if (v >= title_stuff.pics_human.len() as i32) {
+                                        v = book.profiles.len() as i32 - 1;
+                                    } else if (v >= book.profiles.len() as i32) {
                                         v = 0;
                                     }
 
                                     stuff.player_stuff[player.pnum as usize].human_profile = v;
-                                    pic_img.texture = title_stuff.pics_human[v as usize].clone();
+                                    let portrait = book.get( v ).portrait % title_stuff.pics_human.len();
+                                    pic_img.texture = title_stuff.pics_human[portrait].clone();
 
                                 } else if stuff.player_stuff[player.pnum as usize].ptype == PlayerType::AI {
 
@@ -532,6 +908,14 @@ fn player_settings_action(
                     }
                 }
 
+                PlayerSettingsButtonAction::ChangeDifficulty => {
+                    let pinfo = &mut stuff.player_stuff[player.pnum as usize];
+                    if pinfo.ptype == PlayerType::AI {
+                        pinfo.bot_difficulty = pinfo.bot_difficulty.next();
+                        println!("Change difficulty PLR {} to {:?}", player.pnum, pinfo.bot_difficulty );
+                    }
+                }
+
                 PlayerSettingsButtonAction::ChangeMode(mode) => {
                     println!("Change mode PLR {} mode {}", player.pnum, mode );
                     stuff.player_stuff[player.pnum as usize].ptype = match mode {
@@ -549,37 +933,132 @@ fn player_settings_action(
 }
 
 fn main_menu_action (
-    stuff: Res<GoodStuff>,
+    mut stuff: ResMut<GoodStuff>,
+    book: Res<ProfileBook>,
+    mut settings: ResMut<MatchSettings>,
+    mut speed: ResMut<GameSpeed>,
+    mut colors: ResMut<ColorSettings>,
+    mut power_label_q: Query<&mut Text, With<StartPowerLabel>>,
+    mut option_label_q: Query<(&mut Text, &OptionLabel), Without<StartPowerLabel>>,
     mut game_state: ResMut<NextState<GameAppState>>,
     interaction_query: Query<
         (&Interaction, &MainMenuAction),
         (Changed<Interaction>, With<Button>),
     >,
     mut ev_settings: EventWriter<PlayerSettingsChanged>,
+    mut ev_picker: EventWriter<ToggleMapPicker>,
 ) {
 
-    let mut pcount : i32 = 0;
-    for i in 0..4 {
-        if stuff.player_stuff[i].ptype != PlayerType::NotActive {
-            pcount += 1;
-        }
-    }
-
-
     for (interaction, menu_button_action) in &interaction_query {
         if *interaction == Interaction::Pressed {
             match menu_button_action {
                 MainMenuAction::StartGame => {
-                    if (pcount > 0) {
+                    // The button shows why when it's disabled
+                    if validate_seats( &stuff, &book, &settings ).is_ok() {
                         println!("Start Game");
                         game_state.set(GameAppState::Gameplay);
-                    } // else feedback
+                    }
+                }
+                MainMenuAction::ChangeStartPower(inc) => {
+                    let power = (settings.start_power as i32 + inc).clamp( MIN_START_POWER as i32, MAX_START_POWER as i32 );
+                    settings.start_power = power as u8;
+
+                    let mut text = power_label_q.single_mut();
+                    text.sections[0].value = format!("Start Power: {}", settings.start_power );
+                }
+                MainMenuAction::ChangePalette => {
+                    let palette = colors.palette.next();
+                    colors.set_palette( palette );
+                    colors.apply( &mut stuff );
+                    ev_settings.send( PlayerSettingsChanged );
+                }
+                MainMenuAction::ToggleMarkers => {
+                    colors.markers = !colors.markers;
+                }
+                MainMenuAction::ToggleRequireHuman => {
+                    settings.require_human = !settings.require_human;
+                    ev_settings.send( PlayerSettingsChanged );
+                }
+                MainMenuAction::ChangeClock => {
+                    settings.clock = settings.clock.next();
+                }
+                MainMenuAction::ChangeTimeoutPenalty => {
+                    settings.timeout_penalty = settings.timeout_penalty.next();
+                }
+                MainMenuAction::ChangeBotDelay => {
+                    speed.next_delay();
+                }
+                MainMenuAction::ToggleInstantBots => {
+                    speed.instant_bots = !speed.instant_bots;
+                }
+                MainMenuAction::ChangeMap => {
+                    ev_picker.send( ToggleMapPicker );
+                }
+                MainMenuAction::OpenEditor => {
+                    game_state.set( GameAppState::Editor );
                 }
             }
+
+            for (mut text, option) in &mut option_label_q {
+                text.sections[0].value = option_label( *option, &settings, &speed, &colors );
+            }
         }
     }
 }
 
+// The map picker is in mappool.rs, catch the labels up when it picks one
+fn map_chosen(
+    settings: Res<MatchSettings>,
+    speed: Res<GameSpeed>,
+    colors: Res<ColorSettings>,
+    mut option_label_q: Query<(&mut Text, &OptionLabel)>,
+    mut ev_chosen: EventReader<MapChosen>,
+    mut ev_settings: EventWriter<PlayerSettingsChanged>,
+)
+{
+    if ev_chosen.read().count() == 0 {
+        return;
+    }
+
+    for (mut text, option) in &mut option_label_q {
+        text.sections[0].value = option_label( *option, &settings, &speed, &colors );
+    }
+    ev_settings.send( PlayerSettingsChanged );
+}
+
+// Grey out Start and say what's wrong whenever the seats change
+fn update_start_button(
+    stuff: Res<GoodStuff>,
+    book: Res<ProfileBook>,
+    settings: Res<MatchSettings>,
+    mut button_q: Query<(&Children, &mut BackgroundColor), With<StartButton>>,
+    mut error_q: Query<&mut Text, With<StartErrorLabel>>,
+    mut text_q: Query<&mut Text, Without<StartErrorLabel>>,
+    mut ev_settings: EventReader<PlayerSettingsChanged>,
+)
+{
+    if ev_settings.read().count() == 0 {
+        return;
+    }
+
+    let result = validate_seats( &stuff, &book, &settings );
+    let enabled = result.is_ok();
+
+    for (children, mut tint) in &mut button_q {
+        tint.0 = if enabled { Color::WHITE } else { Color::rgba( 0.5, 0.5, 0.5, 0.5 ) };
+        if let Ok(mut text) = text_q.get_mut( children[0] ) {
+            text.sections[0].style.color = if enabled { Color::rgb(1.0, 0.3, 0.9) } else { Color::rgb( 0.4, 0.4, 0.4 ) };
+        }
+    }
+
+    for mut text in &mut error_q {
+        text.sections[0].value = match &result {
+            Ok(()) => "".into(),
+            Err(problem) => problem.message(),
+        };
+    }
+}
+
 fn title_teardown(
     mut commands: Commands,
     despawn_q: Query<Entity, With<TitleScreenCleanup>>) {
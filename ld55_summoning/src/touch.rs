@@ -0,0 +1,253 @@
+use bevy::prelude::*;
+
+use crate::summongame::{ playing, BoardAnimation, GameAppState, GameStateChanged, GameplayCleanup, GoodStuff, MapSpaceVisual, PlayerType, SummonGame, TurnAdvance };
+use crate::map::worldpos_from_mapindex;
+use crate::camera::CameraRig;
+use crate::{ mapdir_from_drag, screen_to_ground, split_pct_from_drag, step_split, try_split,
+             GameCamera, GameCursor, Ground, InputMode };
+
+// Touch controls for phones and tablets. One finger drags a split like the
+// mouse does, but the move waits on the confirm bar so a fat finger can fix
+// the amount. Two fingers pan and pinch-zoom the camera.
+#[derive(Resource, Default)]
+struct TouchState {
+    drag_touch : Option<u64>,
+    pending : bool,
+    last_pinch : Option<(Vec2, f32)>,  // midpoint and distance between two fingers
+}
+
+#[derive(Component)]
+struct TouchConfirmBar;
+
+#[derive(Component)]
+enum TouchButtonAction {
+    Confirm,
+    Cancel,
+    SplitLess,
+    SplitMore,
+}
+
+pub struct TouchPlugin;
+
+impl Plugin for TouchPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource( TouchState::default() )
+            .add_systems( OnEnter(GameAppState::Gameplay), setup_touch_controls )
+            .add_systems(Update, (
+                handle_touch,
+                touch_button_action,
+                )
+                .chain()
+                .run_if(playing))
+            .add_systems( OnExit(GameAppState::Gameplay), reset_touch );
+    }
+}
+
+fn setup_touch_controls(
+    mut commands: Commands,
+)
+{
+    commands.spawn(( NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(12.0),
+            bottom: Val::Px(12.0),
+            column_gap: Val::Px(8.0),
+            ..default()
+        },
+        visibility: Visibility::Hidden,
+        ..default()
+    }, TouchConfirmBar, GameplayCleanup ))
+    .with_children( |parent| {
+        let buttons = [
+            ("-", TouchButtonAction::SplitLess),
+            ("+", TouchButtonAction::SplitMore),
+            ("Cancel", TouchButtonAction::Cancel),
+            ("Confirm", TouchButtonAction::Confirm),
+        ];
+        for (label, action) in buttons {
+            parent.spawn(( ButtonBundle {
+                style: Style {
+                    min_width: Val::Px(64.0),
+                    height: Val::Px(64.0),
+                    padding: UiRect::horizontal( Val::Px(12.0) ),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: BackgroundColor( Color::rgba( 0.0, 0.0, 0.0, 0.6 ) ),
+                ..default()
+            }, action ))
+            .with_children( |parent| {
+                parent.spawn(TextBundle::from_section(
+                    label,
+                    TextStyle {
+                        font_size: 30.0,
+                        ..default()
+                    },
+                ));
+            });
+        }
+    });
+}
+
+fn reset_touch(
+    mut touch_state: ResMut<TouchState>,
+)
+{
+    *touch_state = TouchState::default();
+}
+
+fn handle_touch(
+    touches: Res<Touches>,
+    mut touch_state: ResMut<TouchState>,
+    mut camera_q: Query<(&Camera, &GlobalTransform, &mut CameraRig), With<GameCamera>>,
+    ground_q: Query<&GlobalTransform, With<Ground>>,
+    maptile_q: Query<(&GlobalTransform, &MapSpaceVisual)>,
+    mut cursor_q: Query<(&mut Transform, &mut GameCursor)>,
+    mut bar_q: Query<(&Node, &GlobalTransform, &mut Visibility), With<TouchConfirmBar>>,
+    stuff: Res<GoodStuff>,
+    game: Res<SummonGame>,
+)
+{
+    let (mut cursor_transform, mut cursor_info) = cursor_q.single_mut();
+    let (bar_node, bar_xform, mut bar_vis) = bar_q.single_mut();
+
+    // Two fingers drive the camera and call off any drag in progress
+    let active : Vec<_> = touches.iter().collect();
+    if active.len() >= 2 {
+        let mid = (active[0].position() + active[1].position()) * 0.5;
+        let dist = active[0].position().distance( active[1].position() ).max( 1.0 );
+        if let Some((last_mid, last_dist)) = touch_state.last_pinch {
+            // Drag the board along with the fingers, pinching apart moves in
+            let (_, _, mut rig) = camera_q.single_mut();
+            let pan = mid - last_mid;
+            rig.pan( Vec2::new( -pan.x, pan.y ) * 0.0015 );
+            rig.zoom( last_dist / dist );
+        }
+        touch_state.last_pinch = Some( (mid, dist) );
+
+        if touch_state.drag_touch.is_some() {
+            touch_state.drag_touch = None;
+            cursor_info.drag_from = None;
+        }
+        return;
+    }
+    touch_state.last_pinch = None;
+
+    let (camera, camera_transform, _) = camera_q.single();
+    let ground = ground_q.single();
+    let active_player = game.player_turn;
+    let local_turn = stuff.player_stuff[ active_player as usize ].ptype == PlayerType::Local;
+
+    for touch in touches.iter_just_pressed() {
+        // Leave touches on the confirm bar to the buttons
+        if *bar_vis != Visibility::Hidden && bar_node.logical_rect( bar_xform ).contains( touch.position() ) {
+            continue;
+        }
+
+        let Some(point) = screen_to_ground( camera, camera_transform, ground, touch.position() ) else {
+            continue;
+        };
+
+        let closest = maptile_q.iter()
+            .min_by( |a, b| a.0.translation().distance( point ).total_cmp( &b.0.translation().distance( point ) ) );
+
+        if let Some((tile_xform, vis)) = closest {
+            cursor_info.input_mode = InputMode::Touch;
+            cursor_info.ndx = vis.ndx;
+            cursor_info.cursor_world = point;
+            cursor_transform.translation = tile_xform.translation();
+
+            // Start a drag on one of our stacks
+            let mapsq = game.snapshot.map.spaces[ vis.ndx ];
+            if local_turn && mapsq.power > 1 && mapsq.player == (active_player + 1) as u8 {
+                cursor_info.drag_from = Some( vis.ndx );
+                cursor_info.key_dir = None;
+                touch_state.drag_touch = Some( touch.id() );
+                touch_state.pending = false;
+            }
+        }
+    }
+
+    if let Some(drag_id) = touch_state.drag_touch {
+        if let Some(touch) = touches.get_pressed( drag_id ) {
+            if let (Some(point), Some(drag_from)) = (screen_to_ground( camera, camera_transform, ground, touch.position() ), cursor_info.drag_from) {
+                cursor_info.cursor_world = point;
+                cursor_info.split_pct = split_pct_from_drag( point, worldpos_from_mapindex( drag_from as i32 ) );
+            }
+        } else {
+            // Finger lifted, hold the split until it's confirmed
+            touch_state.drag_touch = None;
+            touch_state.pending = false;
+            if let Some(drag_from) = cursor_info.drag_from {
+                let mapdir = mapdir_from_drag( cursor_info.cursor_world, worldpos_from_mapindex( drag_from as i32 ) );
+                if game.snapshot.map.search_dir( drag_from as i32, mapdir ) != drag_from as i32 {
+                    touch_state.pending = true;
+                } else {
+                    cursor_info.drag_from = None;
+                }
+            }
+        }
+    }
+
+    // The turn may have moved on, or another input taken over
+    if cursor_info.input_mode != InputMode::Touch {
+        touch_state.pending = false;
+    } else if !local_turn {
+        touch_state.pending = false;
+        cursor_info.drag_from = None;
+    }
+
+    *bar_vis = if touch_state.pending { Visibility::Visible } else { Visibility::Hidden };
+}
+
+fn touch_button_action(
+    interaction_query: Query<(&Interaction, &TouchButtonAction), (Changed<Interaction>, With<Button>)>,
+    mut touch_state: ResMut<TouchState>,
+    mut cursor_q: Query<&mut GameCursor>,
+    stuff: Res<GoodStuff>,
+    anim: Res<BoardAnimation>,
+    mut game: ResMut<SummonGame>,
+    mut ev_gamestate: EventWriter<GameStateChanged>,
+    mut ev_turn: EventWriter<TurnAdvance>,
+)
+{
+    if !touch_state.pending {
+        return;
+    }
+
+    let mut cursor_info = cursor_q.single_mut();
+    let Some(src) = cursor_info.drag_from else {
+        return;
+    };
+
+    for (interaction, action) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let src_pow = game.snapshot.map.spaces[ src ].power as i32;
+        match action {
+            TouchButtonAction::SplitLess => {
+                cursor_info.split_pct = step_split( cursor_info.split_pct, src_pow, -1 );
+            }
+            TouchButtonAction::SplitMore => {
+                cursor_info.split_pct = step_split( cursor_info.split_pct, src_pow, 1 );
+            }
+            TouchButtonAction::Cancel => {
+                touch_state.pending = false;
+                cursor_info.drag_from = None;
+            }
+            TouchButtonAction::Confirm if !anim.busy => {
+                let mapdir = mapdir_from_drag( cursor_info.cursor_world, worldpos_from_mapindex( src as i32 ) );
+                let split_pct = cursor_info.split_pct;
+                try_split( &mut game, &stuff, src as i32, mapdir, split_pct, &mut ev_gamestate, &mut ev_turn );
+                touch_state.pending = false;
+                cursor_info.drag_from = None;
+            }
+            TouchButtonAction::Confirm => {}
+        }
+    }
+}
@@ -0,0 +1,168 @@
+// A fixed size cache for tree searches, keyed by the Zobrist hash from
+// GameSnapshot::zobrist or Bitboard::zobrist. Holds evaluate_position results
+// and search scores with the depth and bound they were found at. A slot is
+// picked by the low bits of the hash, and when two positions want the same
+// slot the deeper search keeps it.
+
+use crate::gamestate::evaluate_position;
+use crate::gamestate::GameSnapshot;
+
+// What a stored search score means, for alpha-beta style searches
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower,   // failed high, the real score is at least this
+    Upper,   // failed low, the real score is at most this
+}
+
+// What a search found from a position
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    pub depth : u8,
+    pub score : i32,
+    pub bound : Bound,
+    pub best : Option<(u8, u8, u8)>,  // (src, dest, amount) like find_split
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct TtEntry {
+    key : u64,
+    search : Option<SearchResult>,
+    eval : Option<[i32; 4]>,   // evaluate_position, when we had it
+}
+
+impl TtEntry {
+    fn depth( &self ) -> Option<u8>
+    {
+        self.search.map( |search| search.depth )
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TtStats {
+    pub probes : u64,
+    pub hits : u64,
+    pub stores : u64,
+    pub replaced : u64,   // a different position was pushed out
+    pub eval_probes : u64,
+    pub eval_hits : u64,
+}
+
+impl TtStats {
+    pub fn hit_rate( &self ) -> f32
+    {
+        if self.probes == 0 { 0.0 } else { self.hits as f32 / self.probes as f32 }
+    }
+
+    pub fn eval_hit_rate( &self ) -> f32
+    {
+        if self.eval_probes == 0 { 0.0 } else { self.eval_hits as f32 / self.eval_probes as f32 }
+    }
+}
+
+pub struct TranspositionTable {
+    slots : Vec<Option<TtEntry>>,
+    pub stats : TtStats,
+}
+
+impl TranspositionTable {
+    // Rounded down to a power of two so a slot is just the low bits of the key
+    pub fn new( max_entries : usize ) -> TranspositionTable
+    {
+        let size = if max_entries <= 1 { 1 } else { 1 << (usize::BITS - 1 - max_entries.leading_zeros()) };
+        TranspositionTable {
+            slots: vec![ None; size ],
+            stats: TtStats::default(),
+        }
+    }
+
+    pub fn capacity( &self ) -> usize
+    {
+        self.slots.len()
+    }
+
+    pub fn len( &self ) -> usize
+    {
+        self.slots.iter().filter( |slot| slot.is_some() ).count()
+    }
+
+    pub fn is_empty( &self ) -> bool
+    {
+        self.slots.iter().all( |slot| slot.is_none() )
+    }
+
+    pub fn clear( &mut self )
+    {
+        self.slots.iter_mut().for_each( |slot| *slot = None );
+        self.stats = TtStats::default();
+    }
+
+    fn slot( &self, key : u64 ) -> usize
+    {
+        (key as usize) & (self.slots.len() - 1)
+    }
+
+    pub fn probe( &mut self, key : u64 ) -> Option<SearchResult>
+    {
+        self.stats.probes += 1;
+        let found = self.slots[ self.slot( key ) ]
+            .filter( |entry| entry.key == key )
+            .and_then( |entry| entry.search );
+        if found.is_some() {
+            self.stats.hits += 1;
+        }
+        found
+    }
+
+    // A search result. Keeps the eval if one was cached for this position, and
+    // won't push out a deeper search.
+    pub fn store( &mut self, key : u64, result : SearchResult )
+    {
+        let ndx = self.slot( key );
+        let mut eval = None;
+        if let Some(old) = self.slots[ ndx ] {
+            if old.depth().is_some_and( |depth| depth > result.depth ) {
+                return;
+            }
+            if old.key == key {
+                eval = old.eval;
+            } else {
+                self.stats.replaced += 1;
+            }
+        }
+
+        self.stats.stores += 1;
+        self.slots[ ndx ] = Some( TtEntry { key, search: Some( result ), eval } );
+    }
+
+    // evaluate_position, only worked out once per position while it stays in the table
+    pub fn evaluate( &mut self, key : u64, snap : &GameSnapshot ) -> [i32; 4]
+    {
+        self.evaluate_with( key, || evaluate_position( *snap ) )
+    }
+
+    // Same for any evaluator, like Bitboard::evaluate
+    pub fn evaluate_with( &mut self, key : u64, eval_fn : impl FnOnce() -> [i32; 4] ) -> [i32; 4]
+    {
+        self.stats.eval_probes += 1;
+        let ndx = self.slot( key );
+        if let Some(entry) = self.slots[ ndx ].as_mut() {
+            if entry.key == key {
+                if let Some(eval) = entry.eval {
+                    self.stats.eval_hits += 1;
+                    return eval;
+                }
+                let eval = eval_fn();
+                entry.eval = Some( eval );
+                return eval;
+            }
+        }
+
+        // Evals are cheap to redo, so they never push out a search result
+        let eval = eval_fn();
+        if self.slots[ ndx ].is_none_or( |old| old.search.is_none() ) {
+            self.slots[ ndx ] = Some( TtEntry { key, search: None, eval: Some( eval ) } );
+        }
+        eval
+    }
+}
@@ -0,0 +1,29 @@
+use std::collections::BTreeMap;
+
+use serde::{ Deserialize, Serialize };
+
+use crate::gamestate::EvalWeights;
+use crate::ronfile::RonFile;
+
+// Tuned evaluation weights per bot, by name, in a small RON file next to the
+// game. The tuner writes it, and any bot that isn't in it plays with the
+// default weights.
+pub const WEIGHTS_FILE : &str = "summoning_weights.ron";
+
+// The bots on the title screen, their weights are filed under these names
+pub const BOT_NAMES : [&str; 3] = [ "Glitch", "Daemon", "Overclock" ];
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct WeightsFile {
+    pub bots : BTreeMap<String, EvalWeights>,
+}
+
+impl WeightsFile {
+    pub fn for_bot( &self, name : &str ) -> EvalWeights
+    {
+        self.bots.get( name ).copied().unwrap_or_default()
+    }
+}
+
+impl RonFile for WeightsFile {}
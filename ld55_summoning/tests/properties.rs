@@ -0,0 +1,223 @@
+// Property tests on random boards. The boards don't have to be ones mapgen would
+// make, the rules should hold up on anything.
+
+use ld55_summoning::gamestate::*;
+use ld55_summoning::bitboard::{ BitMove, Bitboard };
+use ld55_summoning::book::{ normalize, Orientation };
+use proptest::prelude::*;
+
+// What goes in one space: the contents, then an owner and power if it has a stack
+fn arb_space() -> impl Strategy<Value = (MapSpaceContents, u8, u8)>
+{
+    prop_oneof![
+        1 => Just( (MapSpaceContents::NotInMap, 0, 0) ),
+        1 => Just( (MapSpaceContents::Blocked, 0, 0) ),
+        6 => Just( (MapSpaceContents::Playable, 0, 0) ),
+        2 => (1..=4u8, 1..=16u8).prop_map( |(player, power)| (MapSpaceContents::Playable, player, power) ),
+    ]
+}
+
+fn arb_board() -> impl Strategy<Value = GameSnapshot>
+{
+    prop::collection::vec( arb_space(), MAP_SZ * MAP_SZ ).prop_map( |spaces| {
+        let mut snap = GameSnapshot::default();
+        for (ndx, (contents, player, power)) in spaces.into_iter().enumerate() {
+            let mapsq = &mut snap.map.spaces[ ndx ];
+            mapsq.ndx = ndx as i32;
+            mapsq.contents = contents;
+            mapsq.player = player;
+            mapsq.power = power;
+        }
+        snap.update_scores();
+        snap
+    })
+}
+
+fn arb_weights() -> impl Strategy<Value = EvalWeights>
+{
+    (0..50000i32, 0..50000i32, 1..50000i32, 0..=1000i32, 0..=1000i32).prop_map(
+        |(stack, spare_power, reach_start, open_decay, contested_decay)| EvalWeights {
+            stack, spare_power, reach_start, open_decay, contested_decay, ..DEFAULT_WEIGHTS
+        })
+}
+
+fn total_power( snap : &GameSnapshot ) -> i32
+{
+    snap.map.spaces.iter().map( |mapsq| mapsq.power as i32 ).sum()
+}
+
+proptest! {
+    #[test]
+    fn no_move_empties_the_source( snap in arb_board(), player in 0..4usize ) {
+        for next in gen_valid_moves( snap, player ) {
+            for (before, after) in snap.map.spaces.iter().zip( next.map.spaces.iter() ) {
+                if before.power > 0 {
+                    prop_assert!( after.power > 0, "space {} was emptied", before.ndx );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn moves_conserve_power( snap in arb_board(), player in 0..4usize ) {
+        let before = total_power( &snap );
+        for next in gen_valid_moves( snap, player ) {
+            prop_assert_eq!( total_power( &next ), before );
+        }
+    }
+
+    #[test]
+    fn mover_owns_the_destination( snap in arb_board(), player in 0..4usize ) {
+        for next in gen_valid_moves( snap, player ) {
+            let Some( (src, dest, amt) ) = find_split( &snap, &next ) else {
+                return Err( TestCaseError::fail( "move didn't change the board" ) );
+            };
+
+            let oldsrc = snap.map.spaces[ src as usize ];
+            let newdest = next.map.spaces[ dest as usize ];
+            prop_assert_eq!( oldsrc.player as usize, player + 1 );
+            prop_assert_eq!( newdest.player as usize, player + 1 );
+            prop_assert_eq!( newdest.power, amt );
+            prop_assert_eq!( newdest.contents, MapSpaceContents::Playable );
+            prop_assert_eq!( snap.map.spaces[ dest as usize ].power, 0 );
+            prop_assert!( snap.map.dir_to( src, dest ).is_some() );
+        }
+    }
+
+    #[test]
+    fn moves_only_touch_two_spaces( snap in arb_board(), player in 0..4usize ) {
+        for next in gen_valid_moves( snap, player ) {
+            let changed = snap.map.spaces.iter().zip( next.map.spaces.iter() )
+                .filter( |(before, after)| before.power != after.power || before.player != after.player )
+                .count();
+            prop_assert_eq!( changed, 2 );
+        }
+    }
+
+    #[test]
+    fn search_dir_lands_on_an_open_space( snap in arb_board(), ndx in 0..(MAP_SZ * MAP_SZ) as i32 ) {
+        for dir in MapDirection::iterator() {
+            let dest = snap.map.search_dir( ndx, dir );
+            prop_assert!( dest != INVALID as i32 );
+            if dest != ndx {
+                let mapsq = snap.map.spaces[ dest as usize ];
+                prop_assert_eq!( mapsq.contents, MapSpaceContents::Playable );
+                prop_assert_eq!( mapsq.power, 0 );
+            }
+        }
+    }
+
+    #[test]
+    fn evaluation_only_scores_players_on_the_board( snap in arb_board() ) {
+        let evals = evaluate_position( snap );
+        for player in 0..4 {
+            // Every stack is worth at least its 10000 base
+            prop_assert!( evals[ player ] >= snap.score[ player ] * 10000 );
+            if snap.score[ player ] == 0 {
+                prop_assert_eq!( evals[ player ], 0 );
+            }
+        }
+    }
+
+    #[test]
+    fn best_move_is_a_valid_move( snap in arb_board(), player in 0..4usize ) {
+        let moves = gen_valid_moves( snap, player );
+        match best_move( snap, player, 4 ) {
+            Some( next ) => prop_assert!( moves.iter().any( |m| m.map.spaces.iter().zip( next.map.spaces.iter() )
+                .all( |(a, b)| a.power == b.power && a.player == b.player ) ) ),
+            None => prop_assert!( moves.is_empty() ),
+        }
+    }
+
+    // The bitboard has to agree with the snapshot rules on everything
+
+    #[test]
+    fn bitboard_round_trip( snap in arb_board() ) {
+        let board = Bitboard::from_snapshot( &snap );
+        let back = board.to_snapshot();
+        for (a, b) in snap.map.spaces.iter().zip( back.map.spaces.iter() ) {
+            prop_assert_eq!( (a.ndx, a.contents, a.player, a.power), (b.ndx, b.contents, b.player, b.power) );
+        }
+        prop_assert_eq!( Bitboard::from_snapshot( &back ), board );
+    }
+
+    #[test]
+    fn bitboard_slides_like_search_dir( snap in arb_board(), ndx in 0..(MAP_SZ * MAP_SZ) ) {
+        let board = Bitboard::from_snapshot( &snap );
+        for (dndx, dir) in MapDirection::iterator().enumerate() {
+            prop_assert_eq!( board.slide( ndx, dndx ) as i32, snap.map.search_dir( ndx as i32, dir ) );
+        }
+    }
+
+    #[test]
+    fn bitboard_moves_match( snap in arb_board(), player in 0..4usize ) {
+        let board = Bitboard::from_snapshot( &snap );
+        let expected : Vec<BitMove> = gen_valid_moves( snap, player ).iter()
+            .map( |next| {
+                let (src, dest, amount) = find_split( &snap, next ).unwrap();
+                BitMove { src: src as u8, dest: dest as u8, amount }
+            })
+            .collect();
+        let moves = board.gen_moves( player );
+        prop_assert_eq!( &moves, &expected );
+        prop_assert_eq!( board.count_moves( player ), moves.len() as u64 );
+
+        for (mv, next) in moves.iter().zip( gen_valid_moves( snap, player ) ) {
+            prop_assert_eq!( board.apply( *mv, player ), Bitboard::from_snapshot( &next ) );
+        }
+    }
+
+    #[test]
+    fn bitboard_evaluates_the_same( snap in arb_board() ) {
+        let board = Bitboard::from_snapshot( &snap );
+        prop_assert_eq!( board.evaluate(), evaluate_position( snap ) );
+    }
+
+    #[test]
+    fn bitboard_perft_matches( snap in arb_board() ) {
+        let board = Bitboard::from_snapshot( &snap );
+        let players = [ 0, 1, 2, 3 ];
+        prop_assert_eq!( board.perft( &players, 0, 2 ), perft( snap, &players, 0, 2 ) );
+    }
+
+    #[test]
+    fn bitboard_hashes_the_same( snap in arb_board(), to_move in 0..4usize ) {
+        prop_assert_eq!( Bitboard::from_snapshot( &snap ).zobrist( to_move ), snap.zobrist( to_move ) );
+    }
+
+    #[test]
+    fn incremental_hash_matches_rehash( snap in arb_board(), player in 0..4usize ) {
+        let hash = snap.zobrist( player );
+        for next in gen_valid_moves( snap, player ) {
+            let (src, dest, amt) = find_split( &snap, &next ).unwrap();
+            let src_power = snap.map.spaces[ src as usize ].power;
+            let updated = zobrist_split( hash, (player + 1) as u8, src as usize, src_power, dest as usize, amt );
+            prop_assert_eq!( updated, next.zobrist( player ) );
+        }
+    }
+
+    #[test]
+    fn bitboard_weighted_eval_matches( snap in arb_board(), weights in arb_weights() ) {
+        let board = Bitboard::from_snapshot( &snap );
+        prop_assert_eq!( board.evaluate_with( &weights ), evaluate_position_with( snap, &weights ) );
+    }
+
+    #[test]
+    fn turned_boards_play_the_same( snap in arb_board(), player in 0..4usize ) {
+        let board = Bitboard::from_snapshot( &snap );
+        let (key, _) = normalize( &board, player );
+        let mut moves : Vec<BitMove> = board.gen_moves( player );
+        moves.sort_by_key( |mv| (mv.src, mv.dest, mv.amount) );
+
+        for orient in Orientation::candidates( &board ) {
+            let turned = orient.board( &board );
+            prop_assert_eq!( normalize( &turned, player ).0, key );
+
+            let mut expected : Vec<BitMove> = moves.iter().map( |mv| orient.apply_move( *mv ).unwrap() ).collect();
+            let mut actual = turned.gen_moves( player );
+            expected.sort_by_key( |mv| (mv.src, mv.dest, mv.amount) );
+            actual.sort_by_key( |mv| (mv.src, mv.dest, mv.amount) );
+            prop_assert_eq!( actual, expected );
+        }
+    }
+}
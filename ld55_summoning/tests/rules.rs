@@ -0,0 +1,956 @@
+// Regression tests for the board rules. These pin down how the hex grid is laid
+// out and how stacks slide, so changes to gamestate.rs can't quietly change the game.
+
+use rand::{ rngs::StdRng, SeedableRng };
+
+use ld55_summoning::bitboard::{ BitMove, Bitboard };
+use ld55_summoning::book::{ normalize, OpeningBook, Orientation, BOOK_MIN_GAMES };
+use ld55_summoning::gamestate::*;
+use ld55_summoning::mapfile::{ list_maps, load_board, seat_reach, thumbnail, validate_map, MapChoice, MapFile };
+use ld55_summoning::mapgen::{ generate_map, generate_map_with, MapGenOptions };
+use ld55_summoning::position::Position;
+use ld55_summoning::puzzle::{ bundled_puzzles, Objective, Opponent, Puzzle, PuzzleOutcome, PuzzleProgress };
+use ld55_summoning::ronfile::RonFile;
+use ld55_summoning::selfplay::play_game;
+use ld55_summoning::transposition::{ Bound, SearchResult, TranspositionTable };
+use ld55_summoning::weights::WeightsFile;
+
+// Every space on the board playable and empty
+fn open_board() -> GameSnapshot
+{
+    let mut snap = GameSnapshot::default();
+    for (ndx, mapsq) in (&mut snap.map).into_iter().enumerate() {
+        mapsq.contents = MapSpaceContents::Playable;
+        mapsq.ndx = ndx as i32;
+    }
+    snap
+}
+
+fn place( snap : &mut GameSnapshot, ndx : i32, player : u8, power : u8 )
+{
+    let mapsq = &mut snap.map.spaces[ ndx as usize ];
+    mapsq.player = player;
+    mapsq.power = power;
+}
+
+fn total_power( snap : &GameSnapshot ) -> i32
+{
+    snap.map.spaces.iter().map( |mapsq| mapsq.power as i32 ).sum()
+}
+
+fn opposite( dir : MapDirection ) -> MapDirection
+{
+    match dir {
+        MapDirection::North => MapDirection::South,
+        MapDirection::NorthEast => MapDirection::SouthWest,
+        MapDirection::NorthWest => MapDirection::SouthEast,
+        MapDirection::South => MapDirection::North,
+        MapDirection::SouthWest => MapDirection::NorthEast,
+        MapDirection::SouthEast => MapDirection::NorthWest,
+    }
+}
+
+const NOWHERE : i32 = INVALID as i32;
+
+#[test]
+fn move_dir_even_column()
+{
+    let ndx = map_index( 4, 4 );
+    assert_eq!( move_dir( ndx, MapDirection::North ), map_index( 5, 4 ) );
+    assert_eq!( move_dir( ndx, MapDirection::NorthEast ), map_index( 5, 5 ) );
+    assert_eq!( move_dir( ndx, MapDirection::NorthWest ), map_index( 5, 3 ) );
+    assert_eq!( move_dir( ndx, MapDirection::South ), map_index( 3, 4 ) );
+    assert_eq!( move_dir( ndx, MapDirection::SouthWest ), map_index( 4, 3 ) );
+    assert_eq!( move_dir( ndx, MapDirection::SouthEast ), map_index( 4, 5 ) );
+}
+
+#[test]
+fn move_dir_odd_column()
+{
+    let ndx = map_index( 4, 5 );
+    assert_eq!( move_dir( ndx, MapDirection::North ), map_index( 5, 5 ) );
+    assert_eq!( move_dir( ndx, MapDirection::NorthEast ), map_index( 4, 6 ) );
+    assert_eq!( move_dir( ndx, MapDirection::NorthWest ), map_index( 4, 4 ) );
+    assert_eq!( move_dir( ndx, MapDirection::South ), map_index( 3, 5 ) );
+    assert_eq!( move_dir( ndx, MapDirection::SouthWest ), map_index( 3, 4 ) );
+    assert_eq!( move_dir( ndx, MapDirection::SouthEast ), map_index( 3, 6 ) );
+}
+
+#[test]
+fn move_dir_off_the_edges()
+{
+    // Left edge is an even column
+    let left = map_index( 3, 0 );
+    assert_eq!( move_dir( left, MapDirection::NorthWest ), NOWHERE );
+    assert_eq!( move_dir( left, MapDirection::SouthWest ), NOWHERE );
+    assert_eq!( move_dir( left, MapDirection::NorthEast ), map_index( 4, 1 ) );
+
+    // Right edge is an odd column
+    let right = map_index( 3, 9 );
+    assert_eq!( move_dir( right, MapDirection::NorthEast ), NOWHERE );
+    assert_eq!( move_dir( right, MapDirection::SouthEast ), NOWHERE );
+    assert_eq!( move_dir( right, MapDirection::NorthWest ), map_index( 3, 8 ) );
+
+    // Top and bottom rows
+    assert_eq!( move_dir( map_index( 9, 4 ), MapDirection::North ), NOWHERE );
+    assert_eq!( move_dir( map_index( 9, 4 ), MapDirection::NorthEast ), NOWHERE );
+    assert_eq!( move_dir( map_index( 9, 5 ), MapDirection::NorthEast ), map_index( 9, 6 ) );
+    assert_eq!( move_dir( map_index( 0, 5 ), MapDirection::South ), NOWHERE );
+    assert_eq!( move_dir( map_index( 0, 5 ), MapDirection::SouthWest ), NOWHERE );
+    assert_eq!( move_dir( map_index( 0, 4 ), MapDirection::SouthWest ), map_index( 0, 3 ) );
+
+    // Corners
+    assert_eq!( move_dir( map_index( 0, 0 ), MapDirection::South ), NOWHERE );
+    assert_eq!( move_dir( map_index( 9, 9 ), MapDirection::North ), NOWHERE );
+}
+
+#[test]
+fn move_dir_and_back()
+{
+    for ndx in 0..(MAP_SZ * MAP_SZ) as i32 {
+        for dir in MapDirection::iterator() {
+            let next = move_dir( ndx, dir );
+            if next != NOWHERE {
+                assert_eq!( move_dir( next, opposite( dir ) ), ndx, "{} {:?}", ndx, dir );
+            }
+        }
+    }
+}
+
+#[test]
+fn map_index_out_of_range()
+{
+    assert_eq!( map_index( -1, 0 ), NOWHERE );
+    assert_eq!( map_index( 0, -1 ), NOWHERE );
+    assert_eq!( map_index( MAP_SZ as i32, 0 ), NOWHERE );
+    assert_eq!( map_index( 0, MAP_SZ as i32 ), NOWHERE );
+    assert_eq!( map_index( 2, 7 ), 27 );
+}
+
+#[test]
+fn search_dir_slides_to_the_edge()
+{
+    let snap = open_board();
+    let start = map_index( 4, 4 );
+    assert_eq!( snap.map.search_dir( start, MapDirection::North ), map_index( 9, 4 ) );
+    assert_eq!( snap.map.search_dir( start, MapDirection::South ), map_index( 0, 4 ) );
+    assert_eq!( snap.map.search_dir( start, MapDirection::SouthWest ), map_index( 2, 0 ) );
+    assert_eq!( snap.map.search_dir( start, MapDirection::NorthEast ), map_index( 7, 9 ) );
+
+    // Already against the edge, nowhere to go
+    let edge = map_index( 9, 4 );
+    assert_eq!( snap.map.search_dir( edge, MapDirection::North ), edge );
+}
+
+#[test]
+fn search_dir_stops_before_stacks()
+{
+    let mut snap = open_board();
+    let start = map_index( 2, 4 );
+    place( &mut snap, map_index( 7, 4 ), 2, 3 );
+    assert_eq!( snap.map.search_dir( start, MapDirection::North ), map_index( 6, 4 ) );
+
+    // Our own stacks block too
+    place( &mut snap, map_index( 4, 4 ), 1, 1 );
+    assert_eq!( snap.map.search_dir( start, MapDirection::North ), map_index( 3, 4 ) );
+
+    // Right next to it, can't move at all
+    place( &mut snap, map_index( 3, 4 ), 3, 1 );
+    assert_eq!( snap.map.search_dir( start, MapDirection::North ), start );
+}
+
+#[test]
+fn search_dir_stops_before_blocked_and_holes()
+{
+    let mut snap = open_board();
+    snap.map.spaces[ map_index( 3, 6 ) as usize ].contents = MapSpaceContents::Blocked;
+    assert_eq!( snap.map.search_dir( map_index( 5, 3 ), MapDirection::SouthEast ), map_index( 4, 5 ) );
+
+    snap.map.spaces[ map_index( 8, 4 ) as usize ].contents = MapSpaceContents::NotInMap;
+    assert_eq!( snap.map.search_dir( map_index( 1, 4 ), MapDirection::North ), map_index( 7, 4 ) );
+
+    snap.map.spaces[ map_index( 6, 4 ) as usize ].contents = MapSpaceContents::Blocked;
+    assert_eq!( snap.map.search_dir( map_index( 1, 4 ), MapDirection::North ), map_index( 5, 4 ) );
+}
+
+#[test]
+fn dir_to_matches_search_dir()
+{
+    let mut snap = open_board();
+    place( &mut snap, map_index( 7, 4 ), 2, 3 );
+    let start = map_index( 2, 4 );
+    assert_eq!( snap.map.dir_to( start, map_index( 6, 4 ) ), Some( MapDirection::North ) );
+    assert_eq!( snap.map.dir_to( start, map_index( 5, 4 ) ), None );
+}
+
+#[test]
+fn line_between_includes_both_ends()
+{
+    let line = line_between( map_index( 2, 4 ), map_index( 5, 4 ) );
+    assert_eq!( line, vec![ map_index( 2, 4 ), map_index( 3, 4 ), map_index( 4, 4 ), map_index( 5, 4 ) ] );
+}
+
+#[test]
+fn open_board_is_reachable()
+{
+    let snap = open_board();
+    assert!( snap.map.check_reachability() );
+}
+
+#[test]
+fn blocked_row_splits_the_board()
+{
+    // Every step on the grid changes row by at most one, so a full row cuts it in two
+    let mut snap = open_board();
+    for col in 0..MAP_SZ as i32 {
+        snap.map.spaces[ map_index( 5, col ) as usize ].contents = MapSpaceContents::Blocked;
+    }
+    assert!( !snap.map.check_reachability() );
+
+    // One gap is enough to join them back up
+    snap.map.spaces[ map_index( 5, 3 ) as usize ].contents = MapSpaceContents::Playable;
+    assert!( snap.map.check_reachability() );
+}
+
+#[test]
+fn holes_dont_need_to_be_reachable()
+{
+    let mut snap = open_board();
+    for ndx in [ 0, 11, 45, 99 ] {
+        snap.map.spaces[ ndx ].contents = MapSpaceContents::NotInMap;
+    }
+    snap.map.spaces[ 46 ].contents = MapSpaceContents::Blocked;
+    assert!( snap.map.check_reachability() );
+
+    // A single playable space cut off from the rest
+    let mut snap = open_board();
+    let island = map_index( 0, 0 );
+    for nbr in snap.map.neighbors( island, true ) {
+        snap.map.spaces[ nbr as usize ].contents = MapSpaceContents::Blocked;
+    }
+    assert!( !snap.map.check_reachability() );
+}
+
+#[test]
+fn single_stack_move_count()
+{
+    // Six directions, and a stack of 4 can send 1, 2 or 3
+    let mut snap = open_board();
+    place( &mut snap, map_index( 4, 4 ), 1, 4 );
+    assert_eq!( gen_valid_moves( snap, 0 ).len(), 18 );
+
+    // Nothing for the other players, and a stack of 1 can't split
+    assert!( gen_valid_moves( snap, 1 ).is_empty() );
+    place( &mut snap, map_index( 4, 4 ), 1, 1 );
+    assert!( gen_valid_moves( snap, 0 ).is_empty() );
+}
+
+#[test]
+fn boxed_in_stack_has_no_moves()
+{
+    let mut snap = open_board();
+    let ndx = map_index( 4, 4 );
+    place( &mut snap, ndx, 2, 5 );
+    for nbr in snap.map.neighbors( ndx, true ) {
+        place( &mut snap, nbr, 3, 1 );
+    }
+    assert!( gen_valid_moves( snap, 1 ).is_empty() );
+}
+
+#[test]
+fn moves_conserve_power_and_claim_destination()
+{
+    let mut snap = open_board();
+    place( &mut snap, map_index( 4, 4 ), 1, 6 );
+    place( &mut snap, map_index( 7, 4 ), 2, 3 );
+    place( &mut snap, map_index( 2, 7 ), 1, 2 );
+    snap.map.spaces[ map_index( 4, 6 ) as usize ].contents = MapSpaceContents::Blocked;
+
+    let before = total_power( &snap );
+    let moves = gen_valid_moves( snap, 0 );
+    assert!( !moves.is_empty() );
+
+    for next in &moves {
+        assert_eq!( total_power( next ), before );
+
+        let (src, dest, amt) = find_split( &snap, next ).expect( "a move should change two spaces" );
+        assert_eq!( snap.map.spaces[ src as usize ].player, 1 );
+        assert_eq!( next.map.spaces[ dest as usize ].player, 1 );
+        assert_eq!( snap.map.spaces[ dest as usize ].power, 0 );
+        assert!( next.map.spaces[ src as usize ].power >= 1 );
+        assert_eq!( next.map.spaces[ dest as usize ].power, amt );
+        assert!( snap.map.dir_to( src, dest ).is_some() );
+    }
+}
+
+#[test]
+fn scores_count_occupied_spaces()
+{
+    let mut snap = open_board();
+    place( &mut snap, 10, 1, 4 );
+    place( &mut snap, 20, 1, 1 );
+    place( &mut snap, 30, 3, 2 );
+    snap.update_scores();
+    assert_eq!( snap.score, [ 2, 0, 1, 0 ] );
+}
+
+#[test]
+fn rate_position_weighs_against_everyone_else()
+{
+    assert_eq!( rate_position( [ 10, 4, 3, 0 ], 0, 3 ), 10 * 2 - 4 - 3 );
+    assert_eq!( rate_position( [ 10, 4, 3, 0 ], 1, 2 ), 4 - 10 - 3 );
+}
+
+// Golden values. If one of these changes on purpose, the bots play differently,
+// so update the numbers along with the change.
+
+fn golden_board() -> GameSnapshot
+{
+    let mut snap = open_board();
+    for ndx in [ 0, 9, 90, 99, 44, 55 ] {
+        snap.map.spaces[ ndx ].contents = MapSpaceContents::NotInMap;
+    }
+    for ndx in [ 23, 67 ] {
+        snap.map.spaces[ ndx ].contents = MapSpaceContents::Blocked;
+    }
+    place( &mut snap, map_index( 1, 1 ), 1, 16 );
+    place( &mut snap, map_index( 8, 8 ), 2, 16 );
+    place( &mut snap, map_index( 1, 8 ), 3, 7 );
+    place( &mut snap, map_index( 8, 1 ), 4, 3 );
+    place( &mut snap, map_index( 4, 2 ), 1, 1 );
+    place( &mut snap, map_index( 5, 7 ), 2, 2 );
+    snap
+}
+
+#[test]
+fn evaluate_lone_stack()
+{
+    let mut snap = open_board();
+    place( &mut snap, map_index( 4, 4 ), 1, 1 );
+    assert_eq!( evaluate_position( snap ), [ 10000, 0, 0, 0 ] );
+
+    place( &mut snap, map_index( 4, 4 ), 1, 8 );
+    assert_eq!( evaluate_position( snap ), GOLDEN_LONE_STACK );
+}
+
+#[test]
+fn evaluate_golden_board()
+{
+    let snap = golden_board();
+    assert_eq!( evaluate_position( snap ), GOLDEN_BOARD );
+    assert_eq!( gen_valid_moves( snap, 0 ).len(), GOLDEN_MOVES[0] );
+    assert_eq!( gen_valid_moves( snap, 1 ).len(), GOLDEN_MOVES[1] );
+    assert_eq!( gen_valid_moves( snap, 2 ).len(), GOLDEN_MOVES[2] );
+    assert_eq!( gen_valid_moves( snap, 3 ).len(), GOLDEN_MOVES[3] );
+}
+
+#[test]
+fn golden_best_move()
+{
+    let snap = golden_board();
+    let next = best_move( snap, 0, 4 ).expect( "player 1 has moves" );
+    assert_eq!( find_split( &snap, &next ), Some( GOLDEN_BEST_MOVE ) );
+}
+
+#[test]
+fn ranked_moves_lead_with_the_best()
+{
+    let snap = golden_board();
+    let ranked = rank_moves( snap, 0, 4 );
+    assert_eq!( ranked.len(), GOLDEN_MOVES[0] );
+    assert!( ranked.windows( 2 ).all( |pair| pair[0].delta >= pair[1].delta ) );
+    let (src, dest, amount) = GOLDEN_BEST_MOVE;
+    assert_eq!( (ranked[0].src, ranked[0].dest, ranked[0].amount), (src, dest, amount) );
+}
+
+const GOLDEN_LONE_STACK : [i32;4] = [ 62083, 0, 0, 0 ];
+const GOLDEN_BOARD : [i32;4] = [ 64628, 92690, 49032, 26530 ];
+const GOLDEN_MOVES : [usize;4] = [ 75, 80, 36, 12 ];
+const GOLDEN_BEST_MOVE : (i32, i32, u8) = (11, 71, 9);
+
+// Perft counts from a fixed map. Faster move generation has to find the same moves.
+
+fn seeded_map( seed : u64 ) -> GameSnapshot
+{
+    let mut rng = StdRng::seed_from_u64( seed );
+    generate_map( &mut rng, [ true; 4 ], 16 )
+}
+
+#[test]
+fn perft_from_seed()
+{
+    let snap = seeded_map( 1 );
+    let players = [ 0, 1, 2, 3 ];
+    assert_eq!( perft( snap, &players, 0, 1 ), 45 );
+    assert_eq!( perft( snap, &players, 0, 2 ), 1350 );
+    assert_eq!( perft( snap, &players, 0, 3 ), 40500 );
+}
+
+#[test]
+fn perft_passes_when_stuck()
+{
+    // Player 2 can't split a single summon, so their turn passes
+    let mut snap = open_board();
+    place( &mut snap, map_index( 4, 4 ), 1, 3 );
+    place( &mut snap, map_index( 8, 8 ), 2, 1 );
+    assert_eq!( perft( snap, &[ 0, 1 ], 0, 1 ), 12 );
+    assert_eq!( perft( snap, &[ 0, 1 ], 1, 1 ), 1 );
+    assert_eq!( perft( snap, &[ 0, 1 ], 1, 2 ), 12 );
+}
+
+#[test]
+fn generated_maps_are_playable()
+{
+    for seed in 0..20 {
+        let snap = seeded_map( seed );
+        assert!( snap.map.check_reachability(), "seed {}", seed );
+        assert_eq!( snap.score, [ 1, 1, 1, 1 ], "seed {}", seed );
+    }
+}
+
+#[test]
+fn tiny_start_power_still_seats_everyone()
+{
+    for seed in 0..20 {
+        let mut rng = StdRng::seed_from_u64( seed );
+        let snap = generate_map( &mut rng, [ true, false, false, true ], 1 );
+        assert_eq!( snap.score, [ 1, 0, 0, 1 ], "seed {}", seed );
+
+        let mut rng = StdRng::seed_from_u64( seed );
+        generate_map( &mut rng, [ true, false, false, true ], 0 );
+    }
+}
+
+#[test]
+fn map_options_change_the_board()
+{
+    let in_map = |snap : &GameSnapshot, contents : MapSpaceContents| snap.map.spaces.iter().filter( |s| s.contents == contents ).count();
+    let small = MapGenOptions { radius: 6.0, blocked_one_in: 0 };
+    for seed in 0..10 {
+        // The defaults are the normal maps, from the same seed
+        let mut rng = StdRng::seed_from_u64( seed );
+        let default_map = generate_map_with( &mut rng, [ true; 4 ], 16, &MapGenOptions::default() );
+        assert_eq!( Position { snapshot: default_map, players: vec![ 0, 1, 2, 3 ], turn: 0, clocks: None }.to_text(),
+                    Position { snapshot: seeded_map( seed ), players: vec![ 0, 1, 2, 3 ], turn: 0, clocks: None }.to_text(), "seed {}", seed );
+
+        let mut rng = StdRng::seed_from_u64( seed );
+        let snap = generate_map_with( &mut rng, [ true, true, false, false ], 12, &small );
+        assert!( snap.map.check_reachability(), "seed {}", seed );
+        assert_eq!( in_map( &snap, MapSpaceContents::Blocked ), 0, "seed {}", seed );
+        assert!( in_map( &snap, MapSpaceContents::Playable ) < in_map( &default_map, MapSpaceContents::Playable ) + in_map( &default_map, MapSpaceContents::Blocked ), "seed {}", seed );
+        assert_eq!( snap.score, [ 1, 1, 0, 0 ], "seed {}", seed );
+    }
+}
+
+#[test]
+fn map_files_check_fairness()
+{
+    let mut snap = open_board();
+    place( &mut snap, map_index( 2, 4 ), 1, 12 );
+    place( &mut snap, map_index( 7, 4 ), 3, 12 );
+    assert_eq!( validate_map( &snap ), Ok( vec![ 0, 2 ] ) );
+
+    // Everyone needs the same power, and a similar share of the board
+    let mut uneven = snap;
+    place( &mut uneven, map_index( 7, 4 ), 3, 10 );
+    assert!( validate_map( &uneven ).is_err() );
+
+    let mut cornered = snap;
+    place( &mut cornered, map_index( 2, 4 ), 0, 0 );
+    place( &mut cornered, map_index( 0, 0 ), 1, 12 );
+    let reach = seat_reach( &cornered );
+    assert!( reach[0] < reach[2], "{:?}", reach );
+    assert!( validate_map( &cornered ).is_err() );
+
+    let mut alone = snap;
+    place( &mut alone, map_index( 7, 4 ), 0, 0 );
+    assert!( validate_map( &alone ).is_err() );
+
+    let mut split = snap;
+    for col in 0..MAP_SZ as i32 {
+        split.map.spaces[ map_index( 5, col ) as usize ].contents = MapSpaceContents::Blocked;
+    }
+    assert!( validate_map( &split ).is_err() );
+}
+
+#[test]
+fn map_file_round_trip()
+{
+    let mut snap = open_board();
+    snap.map.spaces[ 0 ].contents = MapSpaceContents::NotInMap;
+    snap.map.spaces[ 45 ].contents = MapSpaceContents::Blocked;
+    place( &mut snap, map_index( 2, 4 ), 1, 12 );
+    place( &mut snap, map_index( 7, 4 ), 3, 12 );
+    let map = MapFile { name: "Test".into(), snapshot: snap };
+
+    let loaded = MapFile::from_text( "Test", &map.to_text() ).expect( "should load what it saved" );
+    assert_eq!( loaded.to_text(), map.to_text() );
+    assert_eq!( loaded.seats(), vec![ 0, 2 ] );
+
+    // The stacks go to whoever is playing, in seat order
+    let seated = loaded.snapshot_for( [ false, true, false, true ] ).expect( "two players fit" );
+    assert_eq!( seated.map.spaces[ map_index( 2, 4 ) as usize ].player, 2 );
+    assert_eq!( seated.map.spaces[ map_index( 7, 4 ) as usize ].player, 4 );
+    assert_eq!( seated.score, [ 0, 1, 0, 1 ] );
+    assert!( loaded.snapshot_for( [ true, true, true, false ] ).is_err() );
+}
+
+#[test]
+fn map_pool_picks_by_player_count()
+{
+    let mut two = open_board();
+    place( &mut two, map_index( 2, 4 ), 1, 12 );
+    place( &mut two, map_index( 7, 4 ), 2, 12 );
+    let mut three = two;
+    place( &mut three, map_index( 5, 0 ), 3, 12 );
+    let maps = vec![
+        MapFile { name: "A".into(), snapshot: two },
+        MapFile { name: "B".into(), snapshot: three },
+        MapFile { name: "C".into(), snapshot: two },
+    ];
+
+    let names = |players| MapChoice::fits( &maps, players ).map( |m| m.name.clone() ).collect::<Vec<_>>();
+    assert_eq!( names( 2 ), vec![ "A", "C" ] );
+    assert_eq!( names( 3 ), vec![ "B" ] );
+    assert!( names( 4 ).is_empty() );
+
+    // Doesn't repeat the last map unless it's the only one
+    let mut rng = StdRng::seed_from_u64( 3 );
+    for _ in 0..10 {
+        assert_eq!( MapChoice::pick( &maps, &mut rng, 2, Some( "A" ) ).map( |m| m.name.as_str() ), Some( "C" ) );
+        assert_eq!( MapChoice::pick( &maps, &mut rng, 3, Some( "B" ) ).map( |m| m.name.as_str() ), Some( "B" ) );
+    }
+    assert!( MapChoice::pick( &maps, &mut rng, 4, None ).is_none() );
+}
+
+#[test]
+fn map_file_loads_as_a_board()
+{
+    let mut snap = open_board();
+    place( &mut snap, map_index( 2, 4 ), 1, 12 );
+    place( &mut snap, map_index( 7, 4 ), 2, 12 );
+    let dir = std::env::temp_dir().join( format!("summoning_maps_{}", std::process::id() ) );
+    let map = MapFile { name: "Loaded".into(), snapshot: snap };
+    map.save( dir.to_str().unwrap() ).expect( "should save" );
+
+    let path = MapFile::path( dir.to_str().unwrap(), "Loaded" );
+    let board = load_board( &path, [ true, false, true, false ] ).expect( "should load" );
+    assert_eq!( board.score, [ 1, 0, 1, 0 ] );
+    assert_eq!( board.map.spaces[ map_index( 7, 4 ) as usize ].power, 12 );
+    assert_eq!( MapFile::load( &path ).map( |m| m.name ), Ok( "Loaded".to_string() ) );
+    assert!( load_board( &path, [ true; 4 ] ).is_err() );
+    std::fs::remove_dir_all( &dir ).ok();
+}
+
+#[test]
+fn listed_maps_are_playable()
+{
+    let mut fair = open_board();
+    place( &mut fair, map_index( 2, 4 ), 1, 12 );
+    place( &mut fair, map_index( 7, 4 ), 2, 12 );
+    let mut uneven = fair;
+    place( &mut uneven, map_index( 7, 4 ), 2, 10 );
+
+    let dir = std::env::temp_dir().join( format!("summoning_list_{}", std::process::id() ) );
+    let dir_name = dir.to_str().unwrap();
+    MapFile { name: "Fair".into(), snapshot: fair }.save( dir_name ).expect( "should save" );
+    MapFile { name: "Uneven".into(), snapshot: uneven }.save( dir_name ).expect( "should save" );
+
+    let names : Vec<String> = list_maps( dir_name ).into_iter().map( |m| m.name ).collect();
+    assert_eq!( names, vec![ "Fair" ] );
+    std::fs::remove_dir_all( &dir ).ok();
+}
+
+#[test]
+fn thumbnails_show_the_board()
+{
+    let mut snap = open_board();
+    snap.map.spaces[ 0 ].contents = MapSpaceContents::NotInMap;
+    place( &mut snap, 45, 2, 12 );
+    let colors = [ [ 255, 0, 0, 255 ], [ 0, 255, 0, 255 ], [ 0, 0, 255, 255 ], [ 255, 255, 0, 255 ] ];
+    let pixels = thumbnail( &snap, 64, &colors );
+    assert_eq!( pixels.len(), 64 * 64 * 4 );
+
+    let at = |ndx : i32| {
+        let (x, y) = ld55_summoning::mapgen::hex_offset( ndx );
+        let (px, py) = ((32.0 + x * 64.0 / 18.0) as usize, (32.0 + (y - 1.3) * 64.0 / 18.0) as usize);
+        pixels[ (py * 64 + px) * 4..(py * 64 + px) * 4 + 4 ].to_vec()
+    };
+    assert_eq!( at( 45 ), vec![ 0, 255, 0, 255 ] );
+    assert_eq!( at( 0 )[3], 0 );
+    assert_eq!( at( 1 ), vec![ 90, 90, 100, 255 ] );
+}
+
+#[test]
+fn position_text_round_trip()
+{
+    let position = Position { snapshot: seeded_map( 55 ), players: vec![ 0, 2, 3 ], turn: 1, clocks: None };
+    let loaded = Position::from_text( &position.to_text() ).expect( "should load what it saved" );
+    assert_eq!( loaded.players, position.players );
+    assert_eq!( loaded.turn, position.turn );
+    assert_eq!( loaded.to_text(), position.to_text() );
+    assert_eq!( loaded.clocks, None );
+    for (a, b) in loaded.snapshot.map.spaces.iter().zip( position.snapshot.map.spaces.iter() ) {
+        assert_eq!( (a.ndx, a.contents, a.player, a.power), (b.ndx, b.contents, b.player, b.power) );
+    }
+}
+
+#[test]
+fn position_text_errors()
+{
+    assert!( Position::from_text( "players 1 2\nturn 1\n" ).is_err() );
+
+    let mut text = Position { snapshot: open_board(), players: vec![ 0, 1 ], turn: 0, clocks: None }.to_text();
+    assert!( Position::from_text( &text.replace( "turn 1", "turn 3" ) ).is_err() );
+    text = text.replacen( ".", "5:2", 1 );
+    assert!( Position::from_text( &text ).is_err() );
+
+    let text = Position { snapshot: open_board(), players: vec![ 0, 1 ], turn: 0, clocks: Some( [ 5.0; 4 ] ) }.to_text();
+    assert!( Position::from_text( &text.replace( "clocks 5.0 5.0", "clocks 5.0" ) ).is_err() );
+    assert!( Position::from_text( &text.replace( "clocks 5.0 5.0", "clocks 5.0 -1" ) ).is_err() );
+    assert!( Position::from_text( &text.replace( "clocks 5.0 5.0", "clocks NaN 5.0" ) ).is_err() );
+}
+
+#[test]
+fn position_text_keeps_the_clocks()
+{
+    let position = Position { snapshot: seeded_map( 55 ), players: vec![ 0, 2, 3 ], turn: 1, clocks: Some( [ 30.0, 0.0, 12.5, 45.0 ] ) };
+    let text = position.to_text();
+    assert!( text.contains( "clocks 30.0 12.5 45.0\n" ), "{}", text );
+
+    let loaded = Position::from_text( &text ).expect( "should load what it saved" );
+    assert_eq!( loaded.clocks, position.clocks );
+    assert_eq!( loaded.to_text(), text );
+}
+
+// Zobrist hashes and the transposition table
+
+#[test]
+fn same_board_by_different_moves_hashes_the_same()
+{
+    let mut snap = open_board();
+    place( &mut snap, map_index( 4, 4 ), 1, 8 );
+    place( &mut snap, map_index( 1, 1 ), 2, 8 );
+
+    // Player 1 goes north then south, or south then north
+    let split = |snap : &GameSnapshot, dir : MapDirection, amt : u8| {
+        let mut next = *snap;
+        let src = map_index( 4, 4 ) as usize;
+        let dest = snap.map.search_dir( src as i32, dir ) as usize;
+        next.map.spaces[ src ].power -= amt;
+        next.map.spaces[ dest ].power += amt;
+        next.map.spaces[ dest ].player = 1;
+        next
+    };
+    let a = split( &split( &snap, MapDirection::North, 2 ), MapDirection::South, 3 );
+    let b = split( &split( &snap, MapDirection::South, 3 ), MapDirection::North, 2 );
+    assert_eq!( a.zobrist( 1 ), b.zobrist( 1 ) );
+    assert_ne!( a.zobrist( 1 ), a.zobrist( 0 ) );
+    assert_ne!( a.zobrist( 1 ), snap.zobrist( 1 ) );
+
+    // Same spaces, different power
+    let c = split( &split( &snap, MapDirection::North, 3 ), MapDirection::South, 2 );
+    assert_ne!( a.zobrist( 1 ), c.zobrist( 1 ) );
+}
+
+#[test]
+fn incremental_hash_matches()
+{
+    let snap = seeded_map( 55 );
+    let hash = snap.zobrist( 0 );
+    for next in gen_valid_moves( snap, 0 ) {
+        let (src, dest, amt) = find_split( &snap, &next ).unwrap();
+        let src_power = snap.map.spaces[ src as usize ].power;
+        let updated = zobrist_split( hash, 1, src as usize, src_power, dest as usize, amt ) ^ zobrist_turn( 0 ) ^ zobrist_turn( 1 );
+        assert_eq!( updated, next.zobrist( 1 ) );
+    }
+}
+
+fn result( depth : u8, score : i32 ) -> SearchResult
+{
+    SearchResult { depth, score, bound: Bound::Exact, best: None }
+}
+
+#[test]
+fn table_stores_and_probes()
+{
+    let mut table = TranspositionTable::new( 1000 );
+    assert_eq!( table.capacity(), 512 );
+    assert!( table.is_empty() );
+
+    assert_eq!( table.probe( 42 ), None );
+    table.store( 42, result( 3, 17 ) );
+    assert_eq!( table.probe( 42 ), Some( result( 3, 17 ) ) );
+
+    // Same slot, different position
+    assert_eq!( table.probe( 42 + 512 ), None );
+    assert_eq!( table.stats.probes, 3 );
+    assert_eq!( table.stats.hits, 1 );
+    assert!( (table.stats.hit_rate() - 1.0 / 3.0).abs() < 1e-6 );
+}
+
+#[test]
+fn deeper_searches_keep_their_slot()
+{
+    let mut table = TranspositionTable::new( 16 );
+    table.store( 5, result( 4, 100 ) );
+
+    // Shallower result for the same slot doesn't replace it, deeper does
+    table.store( 5 + 16, result( 2, 7 ) );
+    assert_eq!( table.probe( 5 ).map( |r| r.score ), Some( 100 ) );
+    assert_eq!( table.probe( 5 + 16 ), None );
+
+    table.store( 5 + 16, result( 6, 7 ) );
+    assert_eq!( table.probe( 5 ), None );
+    assert_eq!( table.probe( 5 + 16 ).map( |r| r.score ), Some( 7 ) );
+    assert_eq!( table.stats.replaced, 1 );
+
+    table.clear();
+    assert!( table.is_empty() );
+    assert_eq!( table.stats.stores, 0 );
+}
+
+#[test]
+fn table_caches_evaluations()
+{
+    let snap = golden_board();
+    let key = snap.zobrist( 0 );
+    let mut table = TranspositionTable::new( 64 );
+    assert_eq!( table.evaluate( key, &snap ), GOLDEN_BOARD );
+    assert_eq!( table.evaluate( key, &snap ), GOLDEN_BOARD );
+    assert_eq!( table.stats.eval_probes, 2 );
+    assert_eq!( table.stats.eval_hits, 1 );
+
+    // A cached eval isn't a search result
+    assert_eq!( table.probe( key ), None );
+
+    // And storing a search result keeps the eval
+    table.store( key, result( 1, 5 ) );
+    assert_eq!( table.evaluate_with( key, || panic!( "should be cached" ) ), GOLDEN_BOARD );
+}
+
+// Evaluation weights and self-play
+
+#[test]
+fn default_weights_are_the_old_evaluation()
+{
+    let snap = golden_board();
+    assert_eq!( evaluate_position_with( snap, &EvalWeights::default() ), GOLDEN_BOARD );
+
+    // Only counting stacks
+    let weights = EvalWeights { spare_power: 0, ..DEFAULT_WEIGHTS };
+    assert_eq!( evaluate_position_with( snap, &weights ), [ 20000, 20000, 10000, 10000 ] );
+}
+
+#[test]
+fn weights_file_round_trip()
+{
+    let mut file = WeightsFile::default();
+    assert_eq!( file.for_bot( "Glitch" ), DEFAULT_WEIGHTS );
+
+    let tuned = EvalWeights { open_decay: 850, ..DEFAULT_WEIGHTS };
+    file.bots.insert( "Glitch".into(), tuned );
+    let loaded = WeightsFile::from_text( &file.to_text().unwrap() ).unwrap();
+    assert_eq!( loaded.for_bot( "Glitch" ), tuned );
+    assert_eq!( loaded.for_bot( "Daemon" ), DEFAULT_WEIGHTS );
+
+    // Anything left out of the file keeps its default
+    let partial = WeightsFile::from_text( "(bots: { \"Daemon\": (stack: 5) })" ).unwrap();
+    assert_eq!( partial.for_bot( "Daemon" ), EvalWeights { stack: 5, ..DEFAULT_WEIGHTS } );
+}
+
+#[test]
+fn self_play_is_repeatable()
+{
+    let map = seeded_map( 1 );
+    let players = [ 0, 1, 2, 3 ];
+    let weights = [ DEFAULT_WEIGHTS; 4 ];
+    let a = play_game( &mut StdRng::seed_from_u64( 9 ), &map, &players, &weights, 1000 );
+    let b = play_game( &mut StdRng::seed_from_u64( 9 ), &map, &players, &weights, 1000 );
+    assert_eq!( a.scores, b.scores );
+    assert_eq!( a.moves, b.moves );
+
+    // Every move fills one space, and the four starting stacks were already down
+    assert_eq!( a.scores.iter().sum::<i32>(), a.moves as i32 + 4 );
+    assert!( !a.winners( &players ).is_empty() );
+}
+
+// Opening book
+
+#[test]
+fn orientations_keep_neighbors()
+{
+    // Trim the open board to a block that every orientation fits
+    let mut snap = open_board();
+    for mapsq in &mut snap.map {
+        if mapsq.ndx % 10 > 7 || mapsq.ndx / 10 > 7 {
+            mapsq.contents = MapSpaceContents::NotInMap;
+        }
+    }
+    let board = Bitboard::from_snapshot( &snap );
+    let orients = Orientation::candidates( &board );
+    assert_eq!( orients.len(), 4 );
+
+    for orient in orients {
+        for ndx in 0..100 {
+            let Some(turned) = orient.apply( ndx ) else { continue };
+            assert_eq!( orient.invert( turned ), Some( ndx ) );
+
+            let mut expected : Vec<i32> = snap.map.neighbors( ndx as i32, true ).iter()
+                .map( |n| orient.apply( *n as usize ).unwrap() as i32 ).collect();
+            let mut actual = board_with( &orient, &snap ).map.neighbors( turned as i32, true );
+            expected.sort();
+            actual.sort();
+            assert_eq!( actual, expected, "{:?} at {}", orient, ndx );
+        }
+    }
+}
+
+fn board_with( orient : &Orientation, snap : &GameSnapshot ) -> GameSnapshot
+{
+    orient.board( &Bitboard::from_snapshot( snap ) ).to_snapshot()
+}
+
+#[test]
+fn book_finds_turned_boards()
+{
+    let snap = seeded_map( 1 );
+    let board = Bitboard::from_snapshot( &snap );
+    let mv = board.gen_moves( 0 )[ 3 ];
+
+    let mut book = OpeningBook::new( 6 );
+    for _ in 0..BOOK_MIN_GAMES - 1 {
+        book.record( &board, 0, mv, 1.0 );
+    }
+    assert_eq!( book.lookup( &board, 0 ), None );
+    book.record( &board, 0, mv, 1.0 );
+    assert_eq!( book.lookup( &board, 0 ), Some( mv ) );
+    assert_eq!( book.lookup( &board, 1 ), None );
+
+    // The same board the other way up gets the same move, turned to match
+    let turned_orient = Orientation::candidates( &board ).into_iter()
+        .find( |orient| orient.flip_rows && orient.mirror_cols ).unwrap();
+    let turned = turned_orient.board( &board );
+    assert_eq!( normalize( &turned, 0 ).0, normalize( &board, 0 ).0 );
+    assert_eq!( book.lookup( &turned, 0 ), turned_orient.apply_move( mv ) );
+
+    let loaded = OpeningBook::from_text( &book.to_text().unwrap() ).unwrap();
+    assert_eq!( loaded, book );
+}
+
+#[test]
+fn book_prefers_moves_that_win()
+{
+    let board = Bitboard::from_snapshot( &seeded_map( 2 ) );
+    let moves : Vec<BitMove> = board.gen_moves( 0 );
+    let mut book = OpeningBook::new( 6 );
+    for i in 0..8 {
+        book.record( &board, 0, moves[ 0 ], if i < 2 { 1.0 } else { 0.0 } );
+        book.record( &board, 0, moves[ 1 ], if i < 6 { 1.0 } else { 0.0 } );
+    }
+    assert_eq!( book.lookup( &board, 0 ), Some( moves[ 1 ] ) );
+}
+
+#[test]
+fn book_built_from_self_play()
+{
+    let map = seeded_map( 3 );
+    let players = [ 0, 1, 2, 3 ];
+    let mut book = OpeningBook::new( 4 );
+    book.build( &mut StdRng::seed_from_u64( 5 ), &[ map ], &players, 6, &[ DEFAULT_WEIGHTS; 4 ], 1000 );
+
+    // Every game starts from the same board, so the first move was seen each time
+    let first = book.moves( &Bitboard::from_snapshot( &map ), 0 );
+    assert_eq!( first.iter().map( |m| m.played ).sum::<u32>(), 6 );
+    assert!( book.len() > 1 );
+}
+
+// Puzzles
+
+#[test]
+fn bundled_puzzles_can_be_solved()
+{
+    let puzzles = bundled_puzzles();
+    assert!( puzzles.len() >= 5 );
+
+    for puzzle in &puzzles {
+        let start = puzzle.start().unwrap_or_else( |err| panic!( "{}: {}", puzzle.name, err ) );
+        let player = puzzle.player().unwrap();
+        let board = Bitboard::from_snapshot( &start.snapshot );
+        assert!( !puzzle.objective.met( &board, player ), "{} starts solved", puzzle.name );
+
+        let solution = puzzle.solve().unwrap_or_else( || panic!( "{} can't be solved", puzzle.name ) );
+        assert_eq!( puzzle.play( &solution ), PuzzleOutcome::Solved );
+        assert_eq!( puzzle.play( &[] ), PuzzleOutcome::Unfinished );
+
+        // Not just any move will do
+        if puzzle.moves == 1 {
+            assert!( board.gen_moves( player ).iter().any( |mv| puzzle.play( &[ *mv ] ) == PuzzleOutcome::Failed ) );
+        }
+    }
+}
+
+#[test]
+fn scripted_opponent_falls_back_to_the_bot()
+{
+    let mut start = open_board();
+    place( &mut start, 0, 1, 3 );
+    place( &mut start, 99, 2, 3 );
+    let position = Position { snapshot: start, players: vec![ 0, 1 ], turn: 0, clocks: None };
+    let mut puzzle = Puzzle {
+        name: "test".into(),
+        description: "".into(),
+        position: position.to_text(),
+        moves: 2,
+        objective: Objective::Outscore { opponent: 1, margin: 0 },
+        opponent: Opponent::Scripted( vec![ (99, 9, 1), (0, 1, 1) ] ),
+    };
+
+    let board = Bitboard::from_snapshot( &start );
+    let scripted = BitMove { src: 99, dest: 9, amount: 1 };
+    assert_eq!( puzzle.opponent_move( &board, 1, 2, 0 ), Some( scripted ) );
+
+    // Seat 2 doesn't own space 0, so the bot moves instead
+    let bot = puzzle.opponent_move( &board, 1, 2, 1 ).unwrap();
+    assert_eq!( bot.src, 99 );
+
+    let first = board.gen_moves( 0 )[ 0 ];
+    assert_eq!( puzzle.play( &[ first, BitMove { src: 50, dest: 51, amount: 1 } ] ), PuzzleOutcome::Illegal );
+    puzzle.moves = 1;
+    assert_eq!( puzzle.play( &[ first ] ), PuzzleOutcome::Solved );
+}
+
+#[test]
+fn puzzle_opponent_has_to_be_playing()
+{
+    let mut start = open_board();
+    place( &mut start, 0, 1, 3 );
+    place( &mut start, 99, 2, 3 );
+    let position = Position { snapshot: start, players: vec![ 0, 1 ], turn: 0, clocks: None };
+    let mut puzzle = Puzzle {
+        name: "test".into(),
+        description: "".into(),
+        position: position.to_text(),
+        moves: 1,
+        objective: Objective::Smother { opponent: 3 },
+        opponent: Opponent::Bot,
+    };
+    assert!( puzzle.start().is_err() );
+    assert_eq!( puzzle.play( &[ BitMove { src: 0, dest: 1, amount: 1 } ] ), PuzzleOutcome::Illegal );
+
+    puzzle.objective = Objective::Outscore { opponent: 7, margin: 0 };
+    assert!( puzzle.start().is_err() );
+    puzzle.objective = Objective::Smother { opponent: 1 };
+    assert!( puzzle.start().is_ok() );
+}
+
+#[test]
+fn puzzle_progress_round_trip()
+{
+    let puzzles = bundled_puzzles();
+    let mut progress = PuzzleProgress::default();
+    progress.solved.insert( puzzles[ 0 ].name.clone() );
+    let loaded = PuzzleProgress::from_text( &progress.to_text().unwrap() ).unwrap();
+    assert!( loaded.is_solved( &puzzles[ 0 ] ) );
+    assert!( !loaded.is_solved( &puzzles[ 1 ] ) );
+}